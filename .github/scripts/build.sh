@@ -22,6 +22,16 @@ if [[ "$BRANCH_NAME" == "main" ]]; then
         GCROOT="/nix/var/nix/gcroots/$filename"
 
         ssh -o StrictHostKeyChecking=no -o UserKnownHostsFile=/dev/null -i ./id_ed25519 root@caligari -- "rm $GCROOT; ln -s $CLOSURE $GCROOT"
+
+        # Lets ras tell which machines still run an older closure
+        set +x
+        curl -fsS -X POST \
+            -H "Authorization: Bearer $RAS_TOKEN" \
+            -H "Content-Type: application/json" \
+            -d "{\"closure\": \"$CLOSURE\"}" \
+            "http://hallewell:8438/herd/machines/${filename%-closure}/desired" \
+            || echo "Failed to set the desired closure of ${filename%-closure}"
+        set -x
     done
 fi
 
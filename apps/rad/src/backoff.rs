@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+use rand::Rng as _;
+
+const INITIAL_DELAY: Duration = Duration::from_secs(5);
+const MAX_DELAY: Duration = Duration::from_secs(10 * 60);
+
+/// Exponential backoff with jitter, so that the whole herd doesn't come knocking at once when ras
+/// is back
+pub struct Backoff {
+    failures: u32,
+}
+
+impl Backoff {
+    pub fn new() -> Self {
+        Self { failures: 0 }
+    }
+
+    pub fn reset(&mut self) {
+        self.failures = 0;
+    }
+
+    /// Somewhere between half and all of the current delay, which doubles with every failure
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = INITIAL_DELAY
+            .saturating_mul(2u32.saturating_pow(self.failures))
+            .min(MAX_DELAY);
+        self.failures = self.failures.saturating_add(1);
+
+        delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doubles_the_delay_up_to_the_maximum() {
+        let mut backoff = Backoff::new();
+
+        for x in [5, 10, 20, 40, 80, 160, 320, 600, 600] {
+            let delay = backoff.next_delay();
+            let full = Duration::from_secs(x);
+
+            assert!(delay >= full / 2 && delay <= full, "{delay:?} for {full:?}");
+        }
+    }
+
+    #[test]
+    fn starts_over_after_a_reset() {
+        let mut backoff = Backoff::new();
+        for _ in 0..100 {
+            backoff.next_delay();
+        }
+
+        backoff.reset();
+
+        assert!(backoff.next_delay() <= INITIAL_DELAY);
+    }
+}
@@ -0,0 +1,180 @@
+use std::{num::ParseIntError, path::PathBuf, time::Duration};
+
+use serde::Deserialize;
+use thiserror::Error;
+
+const DEFAULT_SERVER_URL: &str = "http://hallewell:8438";
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to read the config file {0}: {1}")]
+    Read(PathBuf, std::io::Error),
+    #[error("The config file {0} is invalid: {1}")]
+    Parse(PathBuf, serde_json::Error),
+    #[error("RAD_INTERVAL has to be a number of seconds: {0}")]
+    InvalidInterval(ParseIntError),
+    #[error("The interval can't be zero")]
+    ZeroInterval,
+    #[error("There's no token, set RAS_TOKEN or put it in the config file")]
+    MissingToken,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct File {
+    server_url: Option<String>,
+    interval_seconds: Option<u64>,
+    token: Option<String>,
+    queue_file: Option<PathBuf>,
+}
+
+pub struct Config {
+    pub server_url: String,
+    pub interval: Duration,
+    // Needs the herd-report scope
+    pub token: String,
+    // Without one, queued reports are lost when rad stops
+    pub queue_file: Option<PathBuf>,
+}
+
+impl Config {
+    /// Reads the JSON file at `RAD_CONFIG` if it's set, then lets `RAD_SERVER_URL`,
+    /// `RAD_INTERVAL` (in seconds), `RAS_TOKEN` and `RAD_QUEUE_FILE` override what's in it. The
+    /// queue is kept in the `StateDirectory` systemd gives rad, unless it's set elsewhere.
+    pub async fn read() -> Result<Self, Error> {
+        let file = match std::env::var_os("RAD_CONFIG") {
+            Some(path) => {
+                let path = PathBuf::from(path);
+                let contents = tokio::fs::read_to_string(&path)
+                    .await
+                    .map_err(|e| Error::Read(path.clone(), e))?;
+
+                serde_json::from_str(&contents).map_err(|e| Error::Parse(path, e))?
+            }
+            None => File::default(),
+        };
+
+        Self::from_parts(file, |x| std::env::var(x).ok())
+    }
+
+    fn from_parts(file: File, env: impl Fn(&str) -> Option<String>) -> Result<Self, Error> {
+        let interval = match env("RAD_INTERVAL") {
+            Some(interval) => Some(interval.parse().map_err(Error::InvalidInterval)?),
+            None => file.interval_seconds,
+        }
+        .map_or(DEFAULT_INTERVAL, Duration::from_secs);
+
+        if interval.is_zero() {
+            return Err(Error::ZeroInterval);
+        }
+
+        let server_url = env("RAD_SERVER_URL")
+            .or(file.server_url)
+            .unwrap_or_else(|| DEFAULT_SERVER_URL.to_string());
+
+        Ok(Self {
+            server_url: server_url.trim_end_matches('/').to_string(),
+            interval,
+            token: env("RAS_TOKEN").or(file.token).ok_or(Error::MissingToken)?,
+            queue_file: env("RAD_QUEUE_FILE")
+                .map(PathBuf::from)
+                .or(file.queue_file)
+                .or_else(|| env("STATE_DIRECTORY").map(|x| PathBuf::from(x).join("queue.json"))),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        |name| {
+            vars.iter()
+                .find(|(x, _)| *x == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    fn file() -> File {
+        serde_json::from_str(
+            r#"{
+                "server_url": "http://file:8438/",
+                "interval_seconds": 30,
+                "token": "file-token",
+                "queue_file": "/file/queue.json"
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn uses_the_defaults_without_a_file() {
+        let config = Config::from_parts(File::default(), env(&[("RAS_TOKEN", "token")])).unwrap();
+
+        assert_eq!("http://hallewell:8438", config.server_url);
+        assert_eq!(DEFAULT_INTERVAL, config.interval);
+        assert_eq!("token", config.token);
+        assert_eq!(None, config.queue_file);
+    }
+
+    #[test]
+    fn uses_the_file() {
+        let config = Config::from_parts(file(), env(&[])).unwrap();
+
+        assert_eq!("http://file:8438", config.server_url);
+        assert_eq!(Duration::from_secs(30), config.interval);
+        assert_eq!("file-token", config.token);
+        assert_eq!(Some(PathBuf::from("/file/queue.json")), config.queue_file);
+    }
+
+    #[test]
+    fn lets_the_environment_override_the_file() {
+        let config = Config::from_parts(
+            file(),
+            env(&[
+                ("RAD_SERVER_URL", "http://env:8438"),
+                ("RAD_INTERVAL", "10"),
+                ("RAS_TOKEN", "env-token"),
+                ("RAD_QUEUE_FILE", "/env/queue.json"),
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!("http://env:8438", config.server_url);
+        assert_eq!(Duration::from_secs(10), config.interval);
+        assert_eq!("env-token", config.token);
+        assert_eq!(Some(PathBuf::from("/env/queue.json")), config.queue_file);
+    }
+
+    #[test]
+    fn keeps_the_queue_in_the_state_directory() {
+        let vars = [("RAS_TOKEN", "token"), ("STATE_DIRECTORY", "/var/lib/rad")];
+
+        let config = Config::from_parts(File::default(), env(&vars)).unwrap();
+        assert_eq!(
+            Some(PathBuf::from("/var/lib/rad/queue.json")),
+            config.queue_file
+        );
+
+        let config = Config::from_parts(file(), env(&vars)).unwrap();
+        assert_eq!(Some(PathBuf::from("/file/queue.json")), config.queue_file);
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert!(matches!(
+            Config::from_parts(File::default(), env(&[])),
+            Err(Error::MissingToken)
+        ));
+        assert!(matches!(
+            Config::from_parts(file(), env(&[("RAD_INTERVAL", "soon")])),
+            Err(Error::InvalidInterval(_))
+        ));
+        assert!(matches!(
+            Config::from_parts(file(), env(&[("RAD_INTERVAL", "0")])),
+            Err(Error::ZeroInterval)
+        ));
+    }
+}
@@ -1,29 +1,89 @@
-use std::{error::Error, time::Duration};
+mod backoff;
+mod config;
+mod queue;
+mod system;
 
-use ratlib::herd::PostHerdMachine;
-use tokio::time::sleep;
+use std::error::Error;
+
+use backoff::Backoff;
+use chrono::Utc;
+use config::Config;
+use queue::Queue;
+use ratlib::{
+    error::ErrorKind,
+    herd::PostHerdMachine,
+    todo::client::{Client, ClientError},
+};
+use tokio::time::{sleep, sleep_until, Instant};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    let client = reqwest::Client::new();
+    let config = Config::read().await?;
+    let client = Client::new(&config.server_url).with_token(&config.token)?;
+    let hostname = hostname::get()?.to_string_lossy().to_string();
+
+    let mut queue = Queue::load(config.queue_file.clone()).await;
+    let mut backoff = Backoff::new();
+    let mut next_report = Instant::now();
 
     loop {
-        let hostname = hostname::get()?.to_string_lossy().to_string();
-        let closure_path = tokio::fs::canonicalize("/nix/var/nix/profiles/system").await?;
-        let closure_path = closure_path.to_string_lossy();
-
-        let result: String = client
-            .post(format!("http://hallewell:8438/herd/machines/{hostname}"))
-            .json(&PostHerdMachine {
-                current_closure: closure_path.to_string(),
-            })
-            .send()
-            .await?
-            .json()
-            .await?;
-
-        println!("Updated host {hostname} with closure {closure_path}, response: {result}");
-
-        sleep(Duration::from_secs(60)).await;
+        if Instant::now() >= next_report {
+            match collect_report().await {
+                Ok(report) => queue.push(report),
+                Err(e) => eprintln!("Failed to find out the current closure: {e}"),
+            }
+
+            next_report = Instant::now() + config.interval;
+        }
+
+        let flushed = flush(&client, &hostname, &mut queue).await;
+        if let Err(e) = queue.save().await {
+            eprintln!("Failed to save the queued reports: {e}");
+        }
+
+        match flushed {
+            Ok(()) => {
+                backoff.reset();
+                sleep_until(next_report).await;
+            }
+            Err(e) => {
+                let delay = backoff.next_delay();
+                eprintln!(
+                    "Failed to send {} report(s) to ras, retrying in {}s: {e}",
+                    queue.len(),
+                    delay.as_secs()
+                );
+                sleep(delay).await;
+            }
+        }
+    }
+}
+
+async fn collect_report() -> std::io::Result<PostHerdMachine> {
+    Ok(PostHerdMachine {
+        current_closure: system::current_closure().await?,
+        reported_at: Some(Utc::now()),
+        system: system::collect().await,
+    })
+}
+
+// Oldest first, so that ras sees the closure switches in the order they happened
+async fn flush(client: &Client, hostname: &str, queue: &mut Queue) -> Result<(), ClientError> {
+    while let Some(report) = queue.front() {
+        match client.update_herd_machine(hostname, report).await {
+            Ok(()) => println!(
+                "Updated host {hostname} with closure {}",
+                report.current_closure
+            ),
+            // Sending it again won't change ras' mind, and it would hold up the rest
+            Err(ClientError::Api(e)) if e.kind == ErrorKind::InvalidRequest => {
+                eprintln!("ras rejected a report, dropping it: {e}");
+            }
+            Err(e) => return Err(e),
+        }
+
+        queue.pop_front();
     }
+
+    Ok(())
 }
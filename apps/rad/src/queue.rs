@@ -0,0 +1,110 @@
+use std::{collections::VecDeque, io::ErrorKind, path::PathBuf};
+
+use ratlib::herd::PostHerdMachine;
+use tokio::fs;
+
+// An hour's worth with the default interval, the oldest reports get dropped after that
+const MAX_QUEUED_REPORTS: usize = 60;
+
+/// Reports wait here until ras accepts them, so that nothing gets lost while it's unreachable.
+/// With a file, they survive restarts of rad as well.
+pub struct Queue {
+    reports: VecDeque<PostHerdMachine>,
+    file: Option<PathBuf>,
+}
+
+impl Queue {
+    /// Picks up the reports the last run left in `file`. A file that's missing or can't be read
+    /// only means there's nothing to pick up.
+    pub async fn load(file: Option<PathBuf>) -> Self {
+        let reports = match &file {
+            Some(path) => match fs::read_to_string(path).await {
+                Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                    eprintln!("Ignoring the queued reports in {}: {e}", path.display());
+                    VecDeque::new()
+                }),
+                Err(e) if e.kind() == ErrorKind::NotFound => VecDeque::new(),
+                Err(e) => {
+                    eprintln!(
+                        "Failed to read the queued reports in {}: {e}",
+                        path.display()
+                    );
+                    VecDeque::new()
+                }
+            },
+            None => VecDeque::new(),
+        };
+
+        Self { reports, file }
+    }
+
+    pub fn push(&mut self, report: PostHerdMachine) {
+        if self.reports.len() == MAX_QUEUED_REPORTS {
+            self.reports.pop_front();
+        }
+        self.reports.push_back(report);
+    }
+
+    pub fn front(&self) -> Option<&PostHerdMachine> {
+        self.reports.front()
+    }
+
+    pub fn pop_front(&mut self) {
+        self.reports.pop_front();
+    }
+
+    pub fn len(&self) -> usize {
+        self.reports.len()
+    }
+
+    /// Writes the reports to the file, if there is one. Goes through a temporary file, so that
+    /// being stopped halfway doesn't leave a broken one behind.
+    pub async fn save(&self) -> std::io::Result<()> {
+        let Some(path) = &self.file else {
+            return Ok(());
+        };
+
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, serde_json::to_vec(&self.reports)?).await?;
+        fs::rename(&temporary, path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(closure: &str) -> PostHerdMachine {
+        serde_json::from_str(&format!(r#"{{"current_closure": "{closure}"}}"#)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn drops_the_oldest_reports_when_full() {
+        let mut queue = Queue::load(None).await;
+
+        for x in 0..=MAX_QUEUED_REPORTS {
+            queue.push(report(&x.to_string()));
+        }
+
+        assert_eq!(MAX_QUEUED_REPORTS, queue.len());
+        assert_eq!("1", queue.front().unwrap().current_closure);
+    }
+
+    #[tokio::test]
+    async fn keeps_the_reports_in_the_file() {
+        let path = std::env::temp_dir().join(format!("rad-queue-{}.json", std::process::id()));
+
+        let mut queue = Queue::load(Some(path.clone())).await;
+        assert_eq!(0, queue.len());
+        queue.push(report("first"));
+        queue.push(report("second"));
+        queue.pop_front();
+        queue.save().await.unwrap();
+
+        let queue = Queue::load(Some(path.clone())).await;
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(1, queue.len());
+        assert_eq!("second", queue.front().unwrap().current_closure);
+    }
+}
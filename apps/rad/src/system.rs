@@ -0,0 +1,200 @@
+use std::time::Duration;
+
+use ratlib::herd::{DiskUsage, LoadAverage, SystemReport};
+use tokio::{fs, process::Command};
+
+const SYSTEM_PROFILE: &str = "/nix/var/nix/profiles/system";
+const MONITORED_MOUNTS: [&str; 2] = ["/", "/nix"];
+
+// Every part is collected on a best-effort basis - a machine with e.g. a broken systemctl should
+// still report the rest
+pub async fn collect() -> SystemReport {
+    SystemReport {
+        booted_closure: fs::canonicalize("/run/booted-system")
+            .await
+            .ok()
+            .map(|x| x.to_string_lossy().to_string()),
+        kernel_version: read_trimmed("/proc/sys/kernel/osrelease").await,
+        uptime: uptime().await,
+        generation: generation().await,
+        failed_units: failed_units().await,
+        disks: disks().await,
+        load_average: load_average().await,
+    }
+}
+
+pub async fn current_closure() -> std::io::Result<String> {
+    let closure = fs::canonicalize(SYSTEM_PROFILE).await?;
+
+    Ok(closure.to_string_lossy().to_string())
+}
+
+async fn read_trimmed(path: &str) -> Option<String> {
+    fs::read_to_string(path)
+        .await
+        .ok()
+        .map(|x| x.trim().to_string())
+}
+
+async fn run(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().await.ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    String::from_utf8(output.stdout).ok()
+}
+
+async fn uptime() -> Option<Duration> {
+    parse_uptime(&read_trimmed("/proc/uptime").await?)
+}
+
+async fn generation() -> Option<u32> {
+    let link = fs::read_link(SYSTEM_PROFILE).await.ok()?;
+
+    parse_generation(link.file_name()?.to_str()?)
+}
+
+async fn failed_units() -> Vec<String> {
+    run(
+        "systemctl",
+        &["list-units", "--state=failed", "--plain", "--no-legend"],
+    )
+    .await
+    .map_or_else(Vec::new, |x| parse_failed_units(&x))
+}
+
+async fn disks() -> Vec<DiskUsage> {
+    let mut args = vec!["-B1", "--output=target,used,size"];
+    args.extend(MONITORED_MOUNTS);
+
+    run("df", &args)
+        .await
+        .map_or_else(Vec::new, |x| parse_disks(&x))
+}
+
+async fn load_average() -> Option<LoadAverage> {
+    parse_load_average(&read_trimmed("/proc/loadavg").await?)
+}
+
+// The first number in /proc/uptime, e.g. "350735.47 234388.90"
+fn parse_uptime(uptime: &str) -> Option<Duration> {
+    let seconds: f64 = uptime.split_whitespace().next()?.parse().ok()?;
+
+    Duration::try_from_secs_f64(seconds).ok()
+}
+
+// The profile points at e.g. "system-123-link"
+fn parse_generation(link: &str) -> Option<u32> {
+    link.strip_prefix("system-")?
+        .strip_suffix("-link")?
+        .parse()
+        .ok()
+}
+
+fn parse_failed_units(output: &str) -> Vec<String> {
+    output
+        .lines()
+        .filter_map(|x| x.split_whitespace().next())
+        .map(ToString::to_string)
+        .collect()
+}
+
+fn parse_disks(output: &str) -> Vec<DiskUsage> {
+    let mut disks: Vec<DiskUsage> = vec![];
+    // The first line is the header
+    for line in output.lines().skip(1) {
+        let mut columns = line.split_whitespace();
+        let (Some(mount_point), Some(Ok(used_bytes)), Some(Ok(total_bytes))) = (
+            columns.next(),
+            columns.next().map(str::parse),
+            columns.next().map(str::parse),
+        ) else {
+            continue;
+        };
+
+        // If /nix isn't a separate filesystem, df lists / twice
+        if disks.iter().any(|x| x.mount_point == mount_point) {
+            continue;
+        }
+
+        disks.push(DiskUsage {
+            mount_point: mount_point.to_string(),
+            used_bytes,
+            total_bytes,
+        });
+    }
+
+    disks
+}
+
+// e.g. "0.52 0.58 0.59 1/1181 2739018"
+fn parse_load_average(loadavg: &str) -> Option<LoadAverage> {
+    let mut columns = loadavg.split_whitespace().map(str::parse);
+
+    Some(LoadAverage {
+        one: columns.next()?.ok()?,
+        five: columns.next()?.ok()?,
+        fifteen: columns.next()?.ok()?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_uptime() {
+        assert_eq!(
+            Some(Duration::from_millis(350_735_470)),
+            parse_uptime("350735.47 234388.90")
+        );
+        assert_eq!(None, parse_uptime(""));
+        assert_eq!(None, parse_uptime("-1 0"));
+    }
+
+    #[test]
+    fn parses_the_generation() {
+        assert_eq!(Some(123), parse_generation("system-123-link"));
+        assert_eq!(None, parse_generation("system"));
+        assert_eq!(None, parse_generation("system-latest-link"));
+    }
+
+    #[test]
+    fn parses_the_failed_units() {
+        let output = "nginx.service loaded failed failed A high performance web server\n\
+            restic-backups.timer loaded failed failed Backups\n";
+
+        assert_eq!(
+            vec!["nginx.service", "restic-backups.timer"],
+            parse_failed_units(output)
+        );
+        assert!(parse_failed_units("").is_empty());
+    }
+
+    #[test]
+    fn parses_the_disks_once_each() {
+        let output = "Mounted on         Used          Size\n\
+            /           52589420544  491173691392\n\
+            /           52589420544  491173691392\n\
+            /boot          broken\n";
+
+        let disks = parse_disks(output);
+
+        assert_eq!(1, disks.len());
+        assert_eq!("/", disks[0].mount_point);
+        assert_eq!(52_589_420_544, disks[0].used_bytes);
+        assert_eq!(491_173_691_392, disks[0].total_bytes);
+    }
+
+    #[test]
+    fn parses_the_load_average() {
+        let load_average = parse_load_average("0.52 0.58 0.59 1/1181 2739018").unwrap();
+
+        assert_eq!(0.52, load_average.one);
+        assert_eq!(0.58, load_average.five);
+        assert_eq!(0.59, load_average.fifteen);
+        assert!(parse_load_average("0.52 0.58").is_none());
+    }
+}
@@ -0,0 +1,30 @@
+-- Values that are trees of their own (requirements, recurrence rules) are kept as JSON, everything
+-- else gets a column so it can be queried and indexed.
+CREATE TABLE todos (
+    id BIGSERIAL NOT NULL PRIMARY KEY,
+    title TEXT NOT NULL,
+    -- 0 is low, 1 medium, 2 high, so that priorities can be compared
+    priority SMALLINT NOT NULL CONSTRAINT todos_priority CHECK (priority BETWEEN 0 AND 2),
+    status TEXT NOT NULL CONSTRAINT todos_status CHECK (status IN ('Todo', 'Doing', 'Done')),
+    estimate_seconds BIGINT NOT NULL,
+    deadline TIMESTAMPTZ,
+    deadline_timezone TEXT,
+    requirements JSONB NOT NULL,
+    recurrence JSONB,
+    next_instance BIGINT,
+    CONSTRAINT todos_deadline_timezone CHECK ((deadline IS NULL) = (deadline_timezone IS NULL))
+);
+
+CREATE INDEX todos_status ON todos(status);
+CREATE INDEX todos_deadline ON todos(deadline) WHERE deadline IS NOT NULL;
+CREATE INDEX todos_recurring ON todos(id) WHERE recurrence IS NOT NULL;
+
+CREATE TABLE events (
+    id SERIAL NOT NULL PRIMARY KEY,
+    title TEXT NOT NULL,
+    start TIMESTAMPTZ NOT NULL,
+    start_timezone TEXT NOT NULL,
+    duration_seconds BIGINT NOT NULL
+);
+
+CREATE INDEX events_start ON events(start);
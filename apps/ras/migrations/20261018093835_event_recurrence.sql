@@ -0,0 +1,7 @@
+ALTER TABLE events
+    -- An RRULE, e.g. `FREQ=WEEKLY;BYDAY=MO`
+    ADD COLUMN recurrence TEXT,
+    -- Local start times, in the timezone of the start
+    ADD COLUMN exdates TIMESTAMP[] NOT NULL DEFAULT '{}';
+
+ALTER TABLE events ALTER COLUMN exdates DROP DEFAULT;
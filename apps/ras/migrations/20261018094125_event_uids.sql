@@ -0,0 +1,5 @@
+-- The UID of events imported from other calendars
+ALTER TABLE events ADD COLUMN uid TEXT;
+
+-- Not unique, the same calendar can be imported more than once
+CREATE INDEX events_uid ON events(uid);
@@ -0,0 +1,4 @@
+-- Every status change of the todo, oldest first
+ALTER TABLE todos ADD COLUMN history JSONB NOT NULL DEFAULT '[]';
+
+ALTER TABLE todos ALTER COLUMN history DROP DEFAULT;
@@ -0,0 +1,2 @@
+-- Todos from before this was tracked don't have one
+ALTER TABLE todos ADD COLUMN created_at TIMESTAMPTZ;
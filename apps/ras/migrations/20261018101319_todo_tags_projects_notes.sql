@@ -0,0 +1,11 @@
+ALTER TABLE todos
+    ADD COLUMN tags TEXT[] NOT NULL DEFAULT '{}',
+    ADD COLUMN project TEXT,
+    ADD COLUMN notes TEXT NOT NULL DEFAULT '';
+
+ALTER TABLE todos
+    ALTER COLUMN tags DROP DEFAULT,
+    ALTER COLUMN notes DROP DEFAULT;
+
+CREATE INDEX todos_project ON todos(project);
+CREATE INDEX todos_tags ON todos USING GIN(tags);
@@ -0,0 +1,5 @@
+-- Like requirements, a checklist is a tree of its own, so it's kept as JSON
+ALTER TABLE todos
+    ADD COLUMN checklist JSONB NOT NULL DEFAULT '{"items": [], "completes_todo": false}';
+
+ALTER TABLE todos ALTER COLUMN checklist DROP DEFAULT;
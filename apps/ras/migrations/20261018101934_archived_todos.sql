@@ -0,0 +1,8 @@
+CREATE TABLE archived_todos (
+    id BIGINT NOT NULL PRIMARY KEY,
+    data JSONB NOT NULL,
+    archived_at TIMESTAMPTZ NOT NULL,
+    deleted BOOLEAN NOT NULL
+);
+
+CREATE INDEX archived_todos_archived_at ON archived_todos(archived_at);
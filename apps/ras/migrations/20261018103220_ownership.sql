@@ -0,0 +1,16 @@
+-- Todos and events from before there were users have no owner, and everyone can see them
+ALTER TABLE todos
+    ADD COLUMN owner TEXT,
+    ADD COLUMN shared BOOLEAN NOT NULL DEFAULT false;
+
+ALTER TABLE todos ALTER COLUMN shared DROP DEFAULT;
+
+CREATE INDEX todos_owner ON todos(owner);
+
+ALTER TABLE events
+    ADD COLUMN owner TEXT,
+    ADD COLUMN shared BOOLEAN NOT NULL DEFAULT false;
+
+ALTER TABLE events ALTER COLUMN shared DROP DEFAULT;
+
+CREATE INDEX events_owner ON events(owner);
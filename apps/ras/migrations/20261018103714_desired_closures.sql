@@ -0,0 +1,7 @@
+-- The closure each host should be running, as deployed by CI. Apart from hosts, as CI can deploy
+-- a host before it ever reported.
+CREATE TABLE desired_closures (
+    hostname TEXT NOT NULL PRIMARY KEY,
+    closure_path TEXT NOT NULL,
+    set_at TIMESTAMPTZ NOT NULL
+);
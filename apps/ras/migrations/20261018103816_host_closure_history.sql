@@ -0,0 +1,13 @@
+-- Every closure a host has run, appended to whenever it switches to another one
+CREATE TABLE host_closure_history (
+    id BIGSERIAL NOT NULL PRIMARY KEY,
+    hostname TEXT NOT NULL,
+    closure_path TEXT NOT NULL,
+    started_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX host_closure_history_hostname_started_at ON host_closure_history(hostname, started_at);
+
+-- What the hosts run now is all that's known from before
+INSERT INTO host_closure_history(hostname, closure_path, started_at)
+    SELECT hostname, running_closure_path, COALESCE(last_running_closure_change, last_seen) FROM hosts;
@@ -0,0 +1,18 @@
+-- What rad reports about the system besides the closure, all missing for hosts running older rad
+ALTER TABLE hosts ADD COLUMN booted_closure_path TEXT NULL;
+ALTER TABLE hosts ADD COLUMN kernel_version TEXT NULL;
+ALTER TABLE hosts ADD COLUMN uptime_seconds BIGINT NULL;
+ALTER TABLE hosts ADD COLUMN generation INTEGER NULL;
+ALTER TABLE hosts ADD COLUMN failed_units TEXT[] NOT NULL DEFAULT '{}';
+ALTER TABLE hosts ADD COLUMN load_average_1 DOUBLE PRECISION NULL;
+ALTER TABLE hosts ADD COLUMN load_average_5 DOUBLE PRECISION NULL;
+ALTER TABLE hosts ADD COLUMN load_average_15 DOUBLE PRECISION NULL;
+
+-- Replaced with every report
+CREATE TABLE host_disks (
+    hostname TEXT NOT NULL,
+    mount_point TEXT NOT NULL,
+    used_bytes BIGINT NOT NULL,
+    total_bytes BIGINT NOT NULL,
+    PRIMARY KEY (hostname, mount_point)
+);
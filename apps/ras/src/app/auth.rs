@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Query},
+    http::{header::AUTHORIZATION, request::Parts},
+};
+use ratlib::ownership::Ownership;
+use serde::Deserialize;
+
+use super::{error::Error, AppState};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Scope {
+    Read,
+    Write,
+    /// Everything else, along with the maintenance endpoints
+    Admin,
+    /// Only reporting the state of machines in the herd, which is what rad does
+    HerdReport,
+    /// Only setting the closures machines should run, which is what CI does
+    HerdDeploy,
+}
+
+/// Who made the request, as identified by their token
+#[derive(Debug, Clone, Deserialize)]
+pub struct User {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+}
+
+impl User {
+    pub fn has(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope) || self.scopes.contains(&Scope::Admin)
+    }
+
+    pub fn require(&self, scope: Scope) -> Result<(), Error> {
+        if self.has(scope) {
+            return Ok(());
+        }
+
+        Err(Error::forbidden(format!(
+            "{} is missing the {scope:?} scope",
+            self.name
+        )))
+    }
+
+    pub fn can_see(&self, ownership: &Ownership) -> bool {
+        ownership.is_visible_to(&self.name)
+    }
+}
+
+/// The users, by their token
+#[derive(Debug, Clone, Deserialize)]
+pub struct Tokens(HashMap<String, User>);
+
+impl Tokens {
+    /// Reads the tokens from the file at `RAS_TOKENS_FILE`, e.g.
+    /// `{"<token>": {"name": "ramona", "scopes": ["read", "write"]}}`. Without any tokens ras
+    /// would reject every request, so that's an error rather than something to find out later.
+    pub fn read() -> Result<Self, Box<dyn std::error::Error>> {
+        let path = std::env::var("RAS_TOKENS_FILE")
+            .map_err(|_| "RAS_TOKENS_FILE is not set, ras would reject every request")?;
+        let tokens: Self = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+
+        if tokens.0.is_empty() {
+            return Err(format!("{path} has no tokens, ras would reject every request").into());
+        }
+
+        Ok(tokens)
+    }
+
+    fn authenticate(&self, token: Option<String>) -> Result<User, Error> {
+        let token = token.ok_or_else(|| Error::unauthorized("A token is required"))?;
+
+        self.0
+            .get(token.trim())
+            .cloned()
+            .ok_or_else(|| Error::unauthorized("The token is not valid"))
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for User {
+    type Rejection = Error;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        state.tokens.authenticate(bearer_token(parts))
+    }
+}
+
+/// The user of a calendar subscription. Calendar apps subscribing to `/events.ics` can't set
+/// headers, so besides the header this also takes the token from `?token=`. Only for read-only
+/// routes, since URLs end up in logs and histories.
+pub struct Subscriber(pub User);
+
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for Subscriber {
+    type Rejection = Error;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let token = bearer_token(parts).or_else(|| {
+            Query::<TokenQuery>::try_from_uri(&parts.uri)
+                .ok()
+                .and_then(|x| x.0.token)
+        });
+
+        Ok(Subscriber(state.tokens.authenticate(token)?))
+    }
+}
+
+fn bearer_token(parts: &Parts) -> Option<String> {
+    parts
+        .headers
+        .get(AUTHORIZATION)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.strip_prefix("Bearer "))
+        .map(ToString::to_string)
+}
@@ -0,0 +1,87 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use ratlib::error::{ApiError, ErrorKind};
+use tracing::error;
+
+use crate::{calendar, todo};
+
+/// The error every handler returns. Internal errors are logged with a random correlation ID
+/// and only that ID is sent to the client, so we don't leak details of the storage.
+#[derive(Debug)]
+pub struct Error(ApiError);
+
+impl Error {
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self(ApiError::new(ErrorKind::NotFound, message))
+    }
+
+    pub fn invalid_request(message: impl Into<String>) -> Self {
+        Self(ApiError::new(ErrorKind::InvalidRequest, message))
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self(ApiError::new(ErrorKind::Conflict, message))
+    }
+
+    pub fn unauthorized(message: impl Into<String>) -> Self {
+        Self(ApiError::new(ErrorKind::Unauthorized, message))
+    }
+
+    pub fn forbidden(message: impl Into<String>) -> Self {
+        Self(ApiError::new(ErrorKind::Forbidden, message))
+    }
+
+    pub fn internal(error: impl std::fmt::Display) -> Self {
+        let correlation_id = format!("{:016x}", rand::random::<u64>());
+
+        error!("Request failed (correlation ID: {correlation_id}): {error}");
+
+        Self(ApiError {
+            kind: ErrorKind::Internal,
+            message: "Internal server error".to_string(),
+            correlation_id: Some(correlation_id),
+        })
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.0.kind.status_code())
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+
+        (status, Json(self.0)).into_response()
+    }
+}
+
+impl From<todo::store::Error> for Error {
+    fn from(value: todo::store::Error) -> Self {
+        match value {
+            todo::store::Error::NotFound(_)
+            | todo::store::Error::UnknownChecklistItem(_, _)
+            | todo::store::Error::NothingToUndo => Self::not_found(value.to_string()),
+            todo::store::Error::SelfDependency(_) | todo::store::Error::UnknownRequirement(_) => {
+                Self::invalid_request(value.to_string())
+            }
+            todo::store::Error::RequirementCycle(_, _)
+            | todo::store::Error::StillRequired(_, _)
+            | todo::store::Error::ChangedSinceUndoable(_, _)
+            | todo::store::Error::RequiredSinceUndoable(_, _, _) => {
+                Self::conflict(value.to_string())
+            }
+            todo::store::Error::Storage(e) => Self::internal(e),
+        }
+    }
+}
+
+impl From<calendar::store::Error> for Error {
+    fn from(value: calendar::store::Error) -> Self {
+        match value {
+            calendar::store::Error::NotFound(_) => Self::not_found(value.to_string()),
+            calendar::store::Error::InvalidRange(_, _) => Self::invalid_request(value.to_string()),
+            calendar::store::Error::Storage(e) => Self::internal(e),
+        }
+    }
+}
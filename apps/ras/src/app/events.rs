@@ -1,29 +1,78 @@
-use crate::app::AppState;
+use crate::{
+    app::{
+        auth::{Scope, Subscriber, User},
+        error::Error,
+        feed::Audience,
+        AppState,
+    },
+    calendar::store::{self, Store},
+};
 use axum::{
-    extract::{Query, State},
+    extract::{Path, Query, State},
+    http::header,
+    response::IntoResponse,
     Json,
 };
-use chrono::NaiveDate;
-use ratlib::{calendar::event::Event, PostEvent};
+use chrono::{NaiveDate, Utc};
+use chrono_tz::{Europe::Berlin, Tz};
+use ratlib::{
+    calendar::{
+        agenda::Day,
+        event::{Event, Id},
+        ical,
+    },
+    feed::Change,
+    ownership::Ownership,
+    todo::filter::Filter,
+    PostEvent, PostEventWithId,
+};
 use serde::Deserialize;
 use std::borrow::BorrowMut;
 
 #[derive(Deserialize)]
 pub struct EventQuery {
-    date: NaiveDate,
+    // A shorthand for a range of just one day
+    date: Option<NaiveDate>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    tz: Option<Tz>,
+}
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    // For the times in the calendar that don't come with a timezone
+    tz: Option<Tz>,
 }
 
 pub async fn get(
     State(state): State<AppState>,
+    user: User,
     Query(query): Query<EventQuery>,
-) -> Json<Vec<Event>> {
-    let mut event_store_guard = state.event_store.lock().await;
-    let event_store = event_store_guard.borrow_mut();
+) -> Result<Json<Vec<Day>>, Error> {
+    user.require(Scope::Read)?;
+
+    let from = query
+        .from
+        .or(query.date)
+        .ok_or_else(|| Error::invalid_request("Either date or from is required"))?;
+    let to = query.to.or(query.date).unwrap_or(from);
+
+    let event_store = state.event_store.lock().await;
 
-    Json((event_store).find_by_date(query.date))
+    Ok(Json(
+        event_store
+            .find_between(&user.name, from, to, query.tz.unwrap_or(Berlin))
+            .await?,
+    ))
 }
 
-pub async fn post(State(state): State<AppState>, Json(request): Json<PostEvent>) -> Json<String> {
+pub async fn post(
+    State(state): State<AppState>,
+    user: User,
+    Json(request): Json<PostEvent>,
+) -> Result<Json<Id>, Error> {
+    user.require(Scope::Write)?;
+
     let mut event_store_guard = state.event_store.lock().await;
     let event_store = event_store_guard.borrow_mut();
 
@@ -32,10 +81,141 @@ pub async fn post(State(state): State<AppState>, Json(request): Json<PostEvent>)
             date,
             duration,
             title,
+            recurrence,
+            shared,
         } => {
-            event_store.create(date, duration, title);
+            let ownership = Ownership::new(&user.name, shared);
+            let id = event_store
+                .create(date, duration, title, recurrence, ownership.clone())
+                .await?;
+            state
+                .feed
+                .publish(Change::EventAdded(id), Audience::Visible(vec![ownership]));
+
+            Ok(Json(id))
         }
     }
+}
+
+pub async fn get_with_id(
+    State(state): State<AppState>,
+    user: User,
+    Path(id): Path<u32>,
+) -> Result<Json<Event>, Error> {
+    user.require(Scope::Read)?;
+
+    let event_store = state.event_store.lock().await;
+
+    Ok(Json(find_visible(&event_store, &user, Id(id)).await?))
+}
+
+// Events the user can't see are treated as if they didn't exist
+async fn find_visible(event_store: &Store, user: &User, id: Id) -> Result<Event, Error> {
+    let event = event_store.find_by_id(id).await?;
+
+    if !user.can_see(event.ownership()) {
+        return Err(store::Error::NotFound(id).into());
+    }
+
+    Ok(event)
+}
+
+pub async fn post_with_id(
+    State(state): State<AppState>,
+    user: User,
+    Path(id): Path<u32>,
+    Json(request): Json<PostEventWithId>,
+) -> Result<Json<String>, Error> {
+    user.require(Scope::Write)?;
+
+    let event_store = state.event_store.lock().await;
+    let ownership = find_visible(&event_store, &user, Id(id))
+        .await?
+        .ownership()
+        .clone();
+
+    let change = match request {
+        PostEventWithId::Edit {
+            set_title,
+            set_start,
+            set_duration,
+            set_recurrence,
+            add_exdates,
+        } => {
+            event_store
+                .edit(
+                    Id(id),
+                    set_title,
+                    set_start,
+                    set_duration,
+                    set_recurrence,
+                    add_exdates,
+                )
+                .await?;
+
+            Change::EventEdited(Id(id))
+        }
+        PostEventWithId::Delete => {
+            event_store.delete(Id(id)).await?;
+
+            Change::EventDeleted(Id(id))
+        }
+    };
+    state
+        .feed
+        .publish(change, Audience::Visible(vec![ownership]));
+
+    Ok(Json("ok".to_string()))
+}
+
+/// The whole calendar as an iCalendar feed, including todos with deadlines
+pub async fn get_ics(
+    State(state): State<AppState>,
+    Subscriber(user): Subscriber,
+) -> Result<impl IntoResponse, Error> {
+    user.require(Scope::Read)?;
+
+    let mut events = state.event_store.lock().await.find_all().await?;
+    events.retain(|x| user.can_see(x.ownership()));
+
+    let mut todos = state
+        .todo_store
+        .lock()
+        .await
+        .find(&Filter {
+            has_deadline: Some(true),
+            ..Default::default()
+        })
+        .await?;
+    todos.retain(|x| user.can_see(x.ownership()));
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+        ical::export(&events, &todos, Utc::now()),
+    ))
+}
+
+pub async fn post_import(
+    State(state): State<AppState>,
+    user: User,
+    Query(query): Query<ImportQuery>,
+    body: String,
+) -> Result<Json<Vec<Id>>, Error> {
+    user.require(Scope::Write)?;
+
+    let events = ical::parse(&body, query.tz.unwrap_or(Berlin))
+        .map_err(|e| Error::invalid_request(e.to_string()))?;
+
+    let event_store = state.event_store.lock().await;
+    let (ids, ownerships): (Vec<_>, Vec<_>) = event_store
+        .import(&user.name, events)
+        .await?
+        .into_iter()
+        .unzip();
+    // Imports can update events others see as well
+    state
+        .feed
+        .publish(Change::EventsImported, Audience::Visible(ownerships));
 
-    Json("ok".to_string())
+    Ok(Json(ids))
 }
@@ -0,0 +1,81 @@
+use std::convert::Infallible;
+
+use axum::{
+    extract::State,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use ratlib::{feed::Change, ownership::Ownership};
+use tokio::sync::broadcast::{self, Receiver, Sender};
+use tokio_stream::{wrappers::BroadcastStream, Stream, StreamExt as _};
+
+use super::{
+    auth::{Scope, User},
+    error::Error,
+    AppState,
+};
+
+// How many changes a subscriber can fall behind before it misses some
+const CAPACITY: usize = 256;
+
+/// Who gets to hear about a change
+#[derive(Debug, Clone)]
+pub enum Audience {
+    /// Whoever can see one of the todos or events that changed
+    Visible(Vec<Ownership>),
+    /// Tokens that can report or deploy machines in the herd
+    Herd,
+}
+
+impl Audience {
+    fn includes(&self, user: &User) -> bool {
+        match self {
+            Audience::Visible(ownerships) => ownerships.iter().any(|x| user.can_see(x)),
+            Audience::Herd => user.has(Scope::HerdReport) || user.has(Scope::HerdDeploy),
+        }
+    }
+}
+
+/// Passes changes on to everyone subscribed to `GET /events/stream` who may know about them
+#[derive(Clone)]
+pub struct Feed {
+    sender: Sender<(Change, Audience)>,
+}
+
+impl Feed {
+    pub fn new() -> Self {
+        Self {
+            sender: broadcast::channel(CAPACITY).0,
+        }
+    }
+
+    pub fn publish(&self, change: Change, audience: Audience) {
+        // Nobody listening is fine
+        let _ = self.sender.send((change, audience));
+    }
+
+    fn subscribe(&self) -> Receiver<(Change, Audience)> {
+        self.sender.subscribe()
+    }
+}
+
+pub async fn get_stream(
+    State(state): State<AppState>,
+    user: User,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, Error> {
+    user.require(Scope::Read)?;
+
+    let changes = BroadcastStream::new(state.feed.subscribe()).filter_map(move |published| {
+        let change = match published {
+            Ok((change, audience)) if audience.includes(&user) => change,
+            Ok(_) => return None,
+            // The subscriber was too slow to keep up, and some changes were dropped
+            Err(_) => Change::Missed,
+        };
+
+        Some(Ok(Event::default()
+            .json_data(change)
+            .expect("Changes can always be serialized")))
+    });
+
+    Ok(Sse::new(changes).keep_alive(KeepAlive::default()))
+}
@@ -1,21 +1,109 @@
 use axum::{
     extract::{Path, State},
-    http::StatusCode,
     Json,
 };
-use ratlib::herd::PostHerdMachine;
+use ratlib::{
+    feed::Change,
+    herd::{ClosurePeriod, Machine, PostDesiredClosure, PostHerdMachine},
+};
 
-use super::AppState;
+use super::{
+    auth::{Scope, User},
+    error::Error,
+    feed::Audience,
+    AppState,
+};
 
 pub async fn post_herd_machine(
     State(state): State<AppState>,
+    user: User,
     Path(hostname): Path<String>,
     Json(request): Json<PostHerdMachine>,
-) -> Result<Json<String>, StatusCode> {
+) -> Result<Json<String>, Error> {
+    user.require(Scope::HerdReport)?;
+
     state
         .herd_store
-        .update_host(hostname, request.current_closure)
-        .await;
+        .update_host(hostname.clone(), &request)
+        .await
+        .map_err(Error::internal)?;
+    state
+        .feed
+        .publish(Change::HostUpdated(hostname), Audience::Herd);
 
     Ok(Json("OK".to_string()))
 }
+
+pub async fn post_desired_closure(
+    State(state): State<AppState>,
+    user: User,
+    Path(hostname): Path<String>,
+    Json(request): Json<PostDesiredClosure>,
+) -> Result<Json<String>, Error> {
+    user.require(Scope::HerdDeploy)?;
+
+    state
+        .herd_store
+        .set_desired_closure(&hostname, &request.closure)
+        .await
+        .map_err(Error::internal)?;
+    state
+        .feed
+        .publish(Change::HostUpdated(hostname), Audience::Herd);
+
+    Ok(Json("OK".to_string()))
+}
+
+pub async fn get_herd_machines(
+    State(state): State<AppState>,
+    user: User,
+) -> Result<Json<Vec<Machine>>, Error> {
+    user.require(Scope::Read)?;
+
+    Ok(Json(
+        state
+            .herd_store
+            .find_machines()
+            .await
+            .map_err(Error::internal)?,
+    ))
+}
+
+pub async fn get_herd_machine(
+    State(state): State<AppState>,
+    user: User,
+    Path(hostname): Path<String>,
+) -> Result<Json<Machine>, Error> {
+    user.require(Scope::Read)?;
+
+    state
+        .herd_store
+        .find_machine(&hostname)
+        .await
+        .map_err(Error::internal)?
+        .map(Json)
+        .ok_or_else(|| Error::not_found(format!("Machine {hostname} never reported")))
+}
+
+pub async fn get_herd_history(
+    State(state): State<AppState>,
+    user: User,
+    Path(hostname): Path<String>,
+) -> Result<Json<Vec<ClosurePeriod>>, Error> {
+    user.require(Scope::Read)?;
+
+    let history = state
+        .herd_store
+        .find_history(&hostname)
+        .await
+        .map_err(Error::internal)?;
+
+    // Every machine that reported has at least the closure it's running now
+    if history.is_empty() {
+        return Err(Error::not_found(format!(
+            "Machine {hostname} never reported"
+        )));
+    }
+
+    Ok(Json(history))
+}
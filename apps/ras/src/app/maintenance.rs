@@ -1,13 +1,21 @@
-use axum::{extract::State, http::StatusCode, Json};
-use tracing::error;
+use axum::{extract::State, Json};
 
-use super::AppState;
+use super::{
+    auth::{Scope, User},
+    error::Error,
+    AppState,
+};
 
-pub async fn post_monitoring(State(state): State<AppState>) -> Result<Json<String>, StatusCode> {
-    if let Err(e) = state.monitoring_maintainer.execute().await {
-        error!("Monitoring maintenance failed: {e}");
+pub async fn post_monitoring(
+    State(state): State<AppState>,
+    user: User,
+) -> Result<Json<String>, Error> {
+    user.require(Scope::Admin)?;
 
-        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    if let Err(e) = state.monitoring_maintainer.execute().await {
+        return Err(Error::internal(format!(
+            "Monitoring maintenance failed: {e}"
+        )));
     }
 
     Ok(Json("OK".to_string()))
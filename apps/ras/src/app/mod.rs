@@ -5,9 +5,13 @@ use tokio::sync::Mutex;
 
 use crate::maintenance::MonitoringMaintainer;
 
+pub mod auth;
+pub mod error;
 pub mod events;
+pub mod feed;
 pub mod herd;
 pub mod maintenance;
+pub mod plan;
 pub mod todos;
 
 #[derive(Clone)]
@@ -16,6 +20,8 @@ pub struct AppState {
     pub event_store: Arc<Mutex<crate::calendar::store::Store>>,
     pub monitoring_maintainer: Arc<MonitoringMaintainer>,
     pub herd_store: Arc<crate::herd::Store>,
+    pub feed: feed::Feed,
+    pub tokens: Arc<auth::Tokens>,
 }
 
 pub async fn index() -> Json<String> {
@@ -0,0 +1,83 @@
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::{NaiveDate, NaiveTime, Utc};
+use chrono_tz::{Europe::Berlin, Tz};
+use ratlib::{
+    plan::{self, Plan},
+    todo::{filter::Filter, Status},
+};
+use serde::Deserialize;
+
+use super::{
+    auth::{Scope, User},
+    error::Error,
+    AppState,
+};
+
+#[derive(Deserialize)]
+pub struct PlanQuery {
+    date: Option<NaiveDate>,
+    tz: Option<Tz>,
+    day_start: Option<NaiveTime>,
+    day_end: Option<NaiveTime>,
+}
+
+/// Proposes when to work on the todos that are ready or being worked on, around the events of the
+/// day. Defaults to today, from 09:00 to 18:00.
+pub async fn get(
+    State(state): State<AppState>,
+    user: User,
+    Query(query): Query<PlanQuery>,
+) -> Result<Json<Plan>, Error> {
+    user.require(Scope::Read)?;
+
+    let timezone = query.tz.unwrap_or(Berlin);
+    let now = Utc::now();
+    let date = query
+        .date
+        .unwrap_or_else(|| now.with_timezone(&timezone).date_naive());
+    let working_hours = (
+        query
+            .day_start
+            .unwrap_or(NaiveTime::from_hms_opt(9, 0, 0).unwrap()),
+        query
+            .day_end
+            .unwrap_or(NaiveTime::from_hms_opt(18, 0, 0).unwrap()),
+    );
+
+    let todos = {
+        let todo_store = state.todo_store.lock().await;
+
+        let mut todos = todo_store
+            .find(&Filter {
+                status: Some(Status::Doing),
+                ..Default::default()
+            })
+            .await?;
+        todos.extend(todo_store.find_ready_to_do().await?);
+        todos.retain(|x| user.can_see(x.ownership()));
+
+        todos
+    };
+
+    let events = state
+        .event_store
+        .lock()
+        .await
+        .find_between(&user.name, date, date, timezone)
+        .await?
+        .into_iter()
+        .flat_map(|x| x.events)
+        .collect::<Vec<_>>();
+
+    Ok(Json(plan::plan(
+        date,
+        working_hours,
+        timezone,
+        &events,
+        &todos,
+        now,
+    )))
+}
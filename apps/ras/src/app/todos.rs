@@ -1,11 +1,21 @@
-use crate::app::AppState;
+use crate::app::{
+    auth::{Scope, User},
+    error::Error,
+    feed::Audience,
+    AppState,
+};
 use axum::{
     extract::{Path, Query, State},
     Json,
 };
-use chrono::NaiveDate;
+use chrono::{NaiveDate, TimeDelta, Utc};
 use ratlib::{
-    todo::{Id, Status, Todo},
+    feed::Change,
+    ownership::Ownership,
+    todo::{
+        archive::Page, filter::Filter, graph::Graph, report::Report, rules::Blocker,
+        urgency::ScoredTodo, Id, Todo,
+    },
     PostTodo, PostTodoWithId,
 };
 use serde::Deserialize;
@@ -13,45 +23,56 @@ use std::borrow::BorrowMut;
 
 #[derive(Deserialize)]
 enum SavedQuery {
-    AroundDeadline,
+    // Used to be the name of this query, which included every todo with a future deadline
+    #[serde(alias = "AroundDeadline")]
+    AtRisk,
 }
 
 #[derive(Deserialize)]
 pub struct TodosQuery {
     becoming_ready_on: Option<NaiveDate>,
-    status: Option<Status>,
     query: Option<SavedQuery>,
 }
 
 pub async fn get_todos(
     State(app_state): State<AppState>,
+    user: User,
     Query(query): Query<TodosQuery>,
-) -> Json<Vec<Todo>> {
+    Query(filter): Query<Filter>,
+) -> Result<Json<Vec<ScoredTodo>>, Error> {
+    user.require(Scope::Read)?;
+
     let mut store_mutex_guard = app_state.todo_store.lock().await;
     let store = store_mutex_guard.borrow_mut();
 
-    let result;
-
-    if query.status.is_some() {
-        // FIXME this should really be find_by_status...
-        result = store.find_doing();
-    } else if let Some(saved_query) = query.query {
-        result = match saved_query {
-            SavedQuery::AroundDeadline => store.find_around_deadline(),
-        };
-    } else if let Some(becoming_ready_on) = query.becoming_ready_on {
-        result = store.find_becoming_valid_on(becoming_ready_on);
-    } else {
-        result = store.find_ready_to_do();
-    }
+    // Only one way of selecting todos at a time, a filter combined with a saved query wouldn't
+    // mean anything sensible. Without any of them, we list what's ready to do.
+    let mut result = match (filter.is_empty(), query.query, query.becoming_ready_on) {
+        (false, None, None) => store.find(&filter).await?,
+        (true, Some(SavedQuery::AtRisk), None) => store.find_at_risk().await?,
+        (true, None, Some(becoming_ready_on)) => {
+            store.find_becoming_valid_on(becoming_ready_on).await?
+        }
+        (true, None, None) => store.find_ready_to_do().await?,
+        _ => {
+            return Err(Error::invalid_request(
+                "Filter fields, query and becoming_ready_on can't be combined",
+            ))
+        }
+    };
+
+    result.retain(|x| user.can_see(x.ownership()));
 
-    Json(result)
+    Ok(Json(store.score(result)))
 }
 
 pub async fn post_todos(
     State(app_state): State<AppState>,
+    user: User,
     Json(request): Json<PostTodo>,
-) -> Json<Id> {
+) -> Result<Json<Id>, Error> {
+    user.require(Scope::Write)?;
+
     let mut store_mutex_guard = app_state.todo_store.lock().await;
     let store = store_mutex_guard.borrow_mut();
 
@@ -62,55 +83,229 @@ pub async fn post_todos(
             estimate,
             requirements,
             deadline,
+            recurrence,
         } => {
-            let id = store.create(title, priority, estimate, requirements, deadline);
+            let id = store
+                .create(
+                    &user.name,
+                    title,
+                    priority,
+                    estimate,
+                    requirements,
+                    deadline,
+                    recurrence,
+                )
+                .await?;
+            app_state.feed.publish(
+                Change::TodoCreated(id),
+                Audience::Visible(vec![Ownership::new(&user.name, false)]),
+            );
 
-            Json(id)
+            Ok(Json(id))
         }
     }
 }
 
+#[derive(Deserialize)]
+pub struct GraphQuery {
+    #[serde(default)]
+    include_done: bool,
+}
+
+pub async fn get_graph(
+    State(app_state): State<AppState>,
+    user: User,
+    Query(query): Query<GraphQuery>,
+) -> Result<Json<Graph>, Error> {
+    user.require(Scope::Read)?;
+
+    let graph = app_state
+        .todo_store
+        .lock()
+        .await
+        .find_graph(&user.name)
+        .await?;
+
+    Ok(Json(if query.include_done {
+        graph
+    } else {
+        graph.without_done()
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct ReportQuery {
+    #[serde(default = "default_report_days")]
+    days: u32,
+}
+
+fn default_report_days() -> u32 {
+    30
+}
+
+/// Estimates compared to the time actually spent, for the todos done in the last `days` days
+pub async fn get_report(
+    State(app_state): State<AppState>,
+    user: User,
+    Query(query): Query<ReportQuery>,
+) -> Result<Json<Report>, Error> {
+    user.require(Scope::Read)?;
+
+    let to = Utc::now();
+    let from = to
+        - TimeDelta::try_days(query.days.into())
+            .ok_or_else(|| Error::invalid_request("Too many days"))?;
+
+    Ok(Json(
+        app_state
+            .todo_store
+            .lock()
+            .await
+            .find_report(&user.name, from, to)
+            .await?,
+    ))
+}
+
+#[derive(Deserialize)]
+pub struct ArchiveQuery {
+    #[serde(default)]
+    offset: usize,
+    #[serde(default = "default_archive_limit")]
+    limit: usize,
+    #[serde(default)]
+    include_deleted: bool,
+}
+
+fn default_archive_limit() -> usize {
+    50
+}
+
+pub async fn get_archive(
+    State(app_state): State<AppState>,
+    user: User,
+    Query(query): Query<ArchiveQuery>,
+) -> Result<Json<Page>, Error> {
+    user.require(Scope::Read)?;
+
+    let store = app_state.todo_store.lock().await;
+
+    Ok(Json(
+        store
+            .find_archived(&user.name, query.include_deleted, query.offset, query.limit)
+            .await?,
+    ))
+}
+
+/// Reverts the most recent change the user made to the todos, and tells what it was. The changes
+/// that can be undone are only kept in memory, so restarting ras forgets them.
+pub async fn post_undo(
+    State(app_state): State<AppState>,
+    user: User,
+) -> Result<Json<String>, Error> {
+    user.require(Scope::Write)?;
+
+    let mut store = app_state.todo_store.lock().await;
+    let undone = store.undo(&user.name).await?;
+    // Own changes can still touch todos others see
+    app_state
+        .feed
+        .publish(Change::TodosReverted, Audience::Visible(undone.ownerships));
+
+    Ok(Json(undone.description))
+}
+
+pub async fn get_todo(
+    State(app_state): State<AppState>,
+    user: User,
+    Path(id): Path<Id>,
+) -> Result<Json<Todo>, Error> {
+    user.require(Scope::Read)?;
+
+    let store = app_state.todo_store.lock().await;
+
+    store
+        .find_by_id(id)
+        .await?
+        .filter(|x| user.can_see(x.ownership()))
+        .map(Json)
+        .ok_or_else(|| Error::not_found(format!("Todo {id} does not exist")))
+}
+
+pub async fn get_blockers(
+    State(app_state): State<AppState>,
+    user: User,
+    Path(id): Path<Id>,
+) -> Result<Json<Vec<Blocker>>, Error> {
+    user.require(Scope::Read)?;
+
+    let store = app_state.todo_store.lock().await;
+
+    Ok(Json(store.find_blockers(id, &user.name).await?))
+}
+
 pub async fn post_todos_with_id(
     State(app_state): State<AppState>,
+    user: User,
     Path(id): Path<Id>,
     Json(request): Json<PostTodoWithId>,
-) -> Json<String> {
+) -> Result<Json<String>, Error> {
+    user.require(Scope::Write)?;
+
     let mut store_mutex_guard = app_state.todo_store.lock().await;
     let store = store_mutex_guard.borrow_mut();
 
-    match request {
+    // Whoever could see the todo before should hear about it, even once it's gone
+    let mut ownership = store
+        .find_by_id(id)
+        .await?
+        .filter(|x| user.can_see(x.ownership()))
+        .map(|x| x.ownership().clone())
+        .ok_or_else(|| Error::not_found(format!("Todo {id} does not exist")))?;
+
+    let change = match request {
         PostTodoWithId::MoveToStatus(new_status) => {
-            let mut todo = store.find_by_id(id).unwrap();
-            todo.transition_to(new_status);
-            store.save(todo);
-        }
-        PostTodoWithId::Edit {
-            set_title,
-            set_estimate,
-            add_requirements,
-            set_priority,
-        } => {
-            let mut todo = store.find_by_id(id).unwrap();
+            store.move_to_status(id, &user.name, new_status).await?;
 
-            if let Some(title) = set_title {
-                todo.set_title(title);
+            Change::TodoStatusChanged(id, new_status)
+        }
+        PostTodoWithId::Edit(edit) => {
+            // And whoever can see it after
+            if edit.set_shared == Some(true) {
+                ownership.set_shared(true);
             }
+            store.edit(id, &user.name, edit).await?;
 
-            if let Some(estimate) = set_estimate {
-                todo.set_estimate(estimate);
-            }
+            Change::TodoEdited(id)
+        }
+        PostTodoWithId::AddChecklistItem(title) => {
+            store.add_checklist_item(id, &user.name, title).await?;
 
-            for requirement in add_requirements {
-                todo.add_requirement(requirement);
-            }
+            Change::TodoEdited(id)
+        }
+        PostTodoWithId::ToggleChecklistItem(index) => {
+            store.toggle_checklist_item(id, &user.name, index).await?;
 
-            if let Some(priority) = set_priority {
-                todo.set_priority(priority);
-            }
+            Change::TodoEdited(id)
+        }
+        PostTodoWithId::RemoveChecklistItem(index) => {
+            store.remove_checklist_item(id, &user.name, index).await?;
 
-            store.save(todo);
+            Change::TodoEdited(id)
         }
-    }
+        PostTodoWithId::Archive => {
+            store.archive(id, &user.name).await?;
+
+            Change::TodoArchived(id)
+        }
+        PostTodoWithId::Delete => {
+            store.delete(id, &user.name).await?;
+
+            Change::TodoDeleted(id)
+        }
+    };
+    app_state
+        .feed
+        .publish(change, Audience::Visible(vec![ownership]));
 
-    Json("ok".to_string())
+    Ok(Json("ok".to_string()))
 }
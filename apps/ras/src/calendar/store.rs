@@ -1,48 +1,542 @@
-use std::{sync::Arc, time::Duration};
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
-use chrono::{DateTime, TimeZone};
-use chrono_tz::{Europe::Berlin, Tz};
-use ratlib::calendar::event::{Event, Id};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
+use ratlib::{
+    calendar::{
+        agenda::Day,
+        event::{Event, Id},
+        ical::ImportedEvent,
+        rrule::RRule,
+    },
+    ownership::Ownership,
+};
+use thiserror::Error;
 
-use crate::datafile::DataFileReader;
+use crate::storage::{self, EventRepository};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Event {0} does not exist")]
+    NotFound(Id),
+    #[error("Invalid range {0} - {1}")]
+    InvalidRange(NaiveDate, NaiveDate),
+    #[error("Storage: {0}")]
+    Storage(#[from] storage::Error),
+}
+
+// Keeps a single request from expanding recurring events over centuries
+const MAX_RANGE_DAYS: i64 = 366;
 
 pub struct Store {
-    data_file_reader: Arc<dyn DataFileReader + Send + Sync>,
+    repository: Arc<dyn EventRepository + Send + Sync>,
 }
 
 impl Store {
-    pub fn new(data_file_reader: Arc<dyn DataFileReader + Send + Sync>) -> Self {
-        Self { data_file_reader }
+    pub fn new(repository: Arc<dyn EventRepository + Send + Sync>) -> Self {
+        Self { repository }
     }
 
-    pub fn create(&self, start: DateTime<Tz>, duration: Duration, title: String) -> Id {
-        let mut datafile = self.data_file_reader.read();
+    pub async fn create(
+        &self,
+        start: DateTime<Tz>,
+        duration: Duration,
+        title: String,
+        recurrence: Option<RRule>,
+        ownership: Ownership,
+    ) -> Result<Id, Error> {
+        let id = self.repository.next_id().await?;
 
-        let id = Id(datafile.events.keys().map(|x| x.0).min().unwrap_or(0) + 1);
+        let mut event = Event::new(id, start, duration, title);
+        event.set_recurrence(recurrence);
+        event.set_ownership(ownership);
 
-        datafile
-            .events
-            .insert(id, Event::new(id, start, duration, title));
+        self.repository.save(&event).await?;
 
-        self.data_file_reader.save(datafile);
+        Ok(id)
+    }
 
-        id
+    pub async fn find_by_id(&self, id: Id) -> Result<Event, Error> {
+        self.repository
+            .find_by_id(id)
+            .await?
+            .ok_or(Error::NotFound(id))
     }
 
-    pub fn find_by_date(&self, day: chrono::prelude::NaiveDate) -> Vec<Event> {
-        let datafile = self.data_file_reader.read();
-        let mut today: Vec<_> = datafile
-            .events
-            .into_values()
-            .filter(|x| {
-                Berlin
-                    .from_utc_datetime(&x.start().to_utc().naive_utc())
-                    .date_naive()
-                    == day
-            })
+    pub async fn edit(
+        &self,
+        id: Id,
+        title: Option<String>,
+        start: Option<DateTime<Tz>>,
+        duration: Option<Duration>,
+        recurrence: Option<Option<RRule>>,
+        exdates: Vec<NaiveDateTime>,
+    ) -> Result<(), Error> {
+        let mut event = self.find_by_id(id).await?;
+
+        if let Some(title) = title {
+            event.set_title(title);
+        }
+
+        if let Some(start) = start {
+            event.set_start(start);
+        }
+
+        if let Some(duration) = duration {
+            event.set_duration(duration);
+        }
+
+        if let Some(recurrence) = recurrence {
+            event.set_recurrence(recurrence);
+        }
+
+        for exdate in exdates {
+            event.add_exdate(exdate);
+        }
+
+        Ok(self.repository.save(&event).await?)
+    }
+
+    pub async fn delete(&self, id: Id) -> Result<(), Error> {
+        // Make sure we report unknown IDs instead of silently doing nothing
+        self.find_by_id(id).await?;
+
+        Ok(self.repository.delete(id).await?)
+    }
+
+    pub async fn find_all(&self) -> Result<Vec<Event>, Error> {
+        let mut events = self.repository.find_all().await?;
+
+        events.sort_by_key(|x| (x.start(), x.id().0));
+        Ok(events)
+    }
+
+    /// Stores events from another calendar for `user`. Events whose UID they already know are
+    /// updated in place, the rest are created as their private events.
+    /// Returns the IDs the events were stored under, along with who can see them
+    pub async fn import(
+        &self,
+        user: &str,
+        events: Vec<ImportedEvent>,
+    ) -> Result<Vec<(Id, Ownership)>, Error> {
+        // Also remembers the ones created by this import, in case the UID repeats
+        let mut known: HashMap<String, (Id, Ownership)> = HashMap::new();
+        let mut imported_events = vec![];
+
+        for imported in events {
+            let existing = match known.get(&imported.uid) {
+                Some(existing) => Some(existing.clone()),
+                None => self
+                    .repository
+                    .find_by_uid(&imported.uid)
+                    .await?
+                    .into_iter()
+                    .filter(|x| x.ownership().is_visible_to(user))
+                    .min_by_key(|x| x.id().0)
+                    .map(|x| (x.id(), x.ownership().clone())),
+            };
+            let (id, ownership) = match existing {
+                Some(existing) => existing,
+                None => (
+                    self.repository.next_id().await?,
+                    Ownership::new(user, false),
+                ),
+            };
+
+            known.insert(imported.uid.clone(), (id, ownership.clone()));
+
+            let mut event = imported.into_event(id);
+            event.set_ownership(ownership.clone());
+            self.repository.save(&event).await?;
+            imported_events.push((id, ownership));
+        }
+
+        Ok(imported_events)
+    }
+
+    /// Finds everything `user` can see happening on the days from `from` to `to` (inclusive),
+    /// with days starting at midnight in `timezone`. Recurring events are expanded into their
+    /// occurrences.
+    pub async fn find_between(
+        &self,
+        user: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+        timezone: Tz,
+    ) -> Result<Vec<Day>, Error> {
+        if to < from || (to - from).num_days() >= MAX_RANGE_DAYS {
+            return Err(Error::InvalidRange(from, to));
+        }
+
+        let events: Vec<_> = self
+            .repository
+            .find_overlapping(
+                start_of_day(from, timezone),
+                start_of_day(to + TimeDelta::try_days(1).unwrap(), timezone),
+            )
+            .await?
+            .into_iter()
+            .filter(|x| x.ownership().is_visible_to(user))
             .collect();
 
-        today.sort_by_key(Event::start);
-        today
+        Ok(from
+            .iter_days()
+            .take_while(|date| *date <= to)
+            .map(|date| {
+                let start = start_of_day(date, timezone);
+                let end = start_of_day(date + TimeDelta::try_days(1).unwrap(), timezone);
+
+                let mut events: Vec<_> = events
+                    .iter()
+                    .flat_map(|x| x.occurrences_overlapping(start, end))
+                    .collect();
+                events.sort_by_key(|x| (x.start(), x.id().0));
+
+                Day { date, events }
+            })
+            .collect())
+    }
+}
+
+// In some timezones midnight doesn't exist on the day DST starts, the day then begins an hour
+// later
+fn start_of_day(date: NaiveDate, timezone: Tz) -> DateTime<Utc> {
+    let midnight = date.and_time(NaiveTime::MIN);
+
+    timezone
+        .from_local_datetime(&midnight)
+        .earliest()
+        .or_else(|| {
+            timezone
+                .from_local_datetime(&(midnight + TimeDelta::try_hours(1).unwrap()))
+                .earliest()
+        })
+        .unwrap()
+        .to_utc()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use chrono::{NaiveDate, TimeZone};
+    use chrono_tz::Europe::{Berlin, London};
+    use ratlib::{
+        calendar::{
+            event::{Event, Id},
+            ical::ImportedEvent,
+        },
+        ownership::Ownership,
+    };
+
+    use crate::{
+        calendar::store::{Error, Store},
+        datafile::{DataFile, DataFileReader},
+        storage::datafile::DataFileRepository,
+    };
+
+    struct MockStore(pub Mutex<Vec<Event>>);
+
+    impl DataFileReader for MockStore {
+        fn read(&self) -> Result<DataFile, crate::datafile::Error> {
+            Ok(DataFile {
+                todos: HashMap::new(),
+                events: self
+                    .0
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .map(|x| (x.id(), x.clone()))
+                    .collect(),
+                archived_todos: HashMap::new(),
+            })
+        }
+
+        fn save(&self, data: DataFile) -> Result<(), crate::datafile::Error> {
+            *self.0.lock().unwrap() = data.events.into_values().collect();
+
+            Ok(())
+        }
+    }
+
+    fn create_store(events: Vec<Event>) -> Store {
+        Store::new(Arc::new(DataFileRepository::new(Arc::new(MockStore(
+            Mutex::new(events),
+        )))))
+    }
+
+    async fn on(store: &Store, date: NaiveDate) -> Vec<Event> {
+        store
+            .find_between("ramona", date, date, Berlin)
+            .await
+            .unwrap()
+            .remove(0)
+            .events
+    }
+
+    fn event(id: u32, title: &str) -> Event {
+        Event::new(
+            Id(id),
+            Berlin.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap(),
+            Duration::from_secs(3600),
+            title.to_string(),
+        )
+    }
+
+    #[tokio::test]
+    pub async fn create_does_not_overwrite_existing_events() {
+        let store = create_store(vec![event(1, "first"), event(3, "third")]);
+
+        let id = store
+            .create(
+                Berlin.with_ymd_and_hms(2024, 5, 2, 10, 0, 0).unwrap(),
+                Duration::from_secs(60),
+                "new".to_string(),
+                None,
+                Ownership::new("ramona", false),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(Id(4), id);
+        assert_eq!("first", store.find_by_id(Id(1)).await.unwrap().title());
+        assert_eq!("third", store.find_by_id(Id(3)).await.unwrap().title());
+    }
+
+    #[tokio::test]
+    pub async fn can_edit_and_delete() {
+        let store = create_store(vec![event(1, "first")]);
+
+        store
+            .edit(
+                Id(1),
+                Some("renamed".to_string()),
+                None,
+                Some(Duration::from_secs(60)),
+                None,
+                vec![],
+            )
+            .await
+            .unwrap();
+
+        let edited = store.find_by_id(Id(1)).await.unwrap();
+        assert_eq!("renamed", edited.title());
+        assert_eq!(Duration::from_secs(60), edited.duration());
+        assert_eq!(
+            Berlin.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap(),
+            edited.start()
+        );
+
+        store.delete(Id(1)).await.unwrap();
+
+        assert!(matches!(
+            store.find_by_id(Id(1)).await,
+            Err(Error::NotFound(Id(1)))
+        ));
+        assert!(matches!(
+            store.delete(Id(1)).await,
+            Err(Error::NotFound(Id(1)))
+        ));
+    }
+
+    #[tokio::test]
+    pub async fn expands_recurring_events() {
+        let store = create_store(vec![event(1, "once")]);
+
+        let id = store
+            .create(
+                Berlin.with_ymd_and_hms(2024, 3, 25, 9, 0, 0).unwrap(),
+                Duration::from_secs(900),
+                "standup".to_string(),
+                Some("FREQ=WEEKLY;BYDAY=MO,WE".parse().unwrap()),
+                Ownership::new("ramona", false),
+            )
+            .await
+            .unwrap();
+
+        // The first Monday after the DST change
+        let monday = on(&store, NaiveDate::from_ymd_opt(2024, 4, 1).unwrap()).await;
+        assert_eq!(1, monday.len());
+        assert_eq!(id, monday[0].id());
+        assert_eq!(
+            Berlin.with_ymd_and_hms(2024, 4, 1, 9, 0, 0).unwrap(),
+            monday[0].start()
+        );
+
+        store
+            .edit(
+                id,
+                None,
+                None,
+                None,
+                None,
+                vec![NaiveDate::from_ymd_opt(2024, 4, 3)
+                    .unwrap()
+                    .and_hms_opt(9, 0, 0)
+                    .unwrap()],
+            )
+            .await
+            .unwrap();
+
+        assert!(on(&store, NaiveDate::from_ymd_opt(2024, 4, 3).unwrap())
+            .await
+            .is_empty());
+
+        let non_recurring = on(&store, NaiveDate::from_ymd_opt(2024, 5, 1).unwrap()).await;
+        assert_eq!(
+            vec!["standup", "once"],
+            non_recurring.iter().map(Event::title).collect::<Vec<_>>()
+        );
+
+        store
+            .edit(id, None, None, None, Some(None), vec![])
+            .await
+            .unwrap();
+
+        assert!(store.find_by_id(id).await.unwrap().recurrence().is_none());
+        assert!(on(&store, NaiveDate::from_ymd_opt(2024, 4, 1).unwrap())
+            .await
+            .is_empty());
+    }
+
+    #[tokio::test]
+    pub async fn import_deduplicates_by_uid() {
+        let store = create_store(vec![event(1, "local")]);
+
+        let imported = |uid: &str, title: &str| ImportedEvent {
+            uid: uid.to_string(),
+            start: Berlin.with_ymd_and_hms(2024, 5, 2, 10, 0, 0).unwrap(),
+            duration: Duration::from_secs(60),
+            title: title.to_string(),
+            recurrence: None,
+            exdates: vec![],
+        };
+
+        let ids = store
+            .import(
+                "ramona",
+                vec![
+                    imported("remote@example.com", "remote"),
+                    imported("event-1@ras", "exported and changed"),
+                ],
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            vec![Id(2), Id(1)],
+            ids.iter().map(|x| x.0).collect::<Vec<_>>()
+        );
+
+        let ids = store
+            .import(
+                "ramona",
+                vec![imported("remote@example.com", "remote, renamed")],
+            )
+            .await
+            .unwrap();
+        assert_eq!(vec![(Id(2), Ownership::new("ramona", false))], ids);
+
+        assert_eq!(
+            vec!["exported and changed", "remote, renamed"],
+            store
+                .find_all()
+                .await
+                .unwrap()
+                .iter()
+                .map(Event::title)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[tokio::test]
+    pub async fn events_show_up_on_every_day_they_overlap() {
+        let store = create_store(vec![Event::new(
+            Id(1),
+            Berlin.with_ymd_and_hms(2024, 5, 1, 22, 0, 0).unwrap(),
+            Duration::from_secs(150 * 60),
+            "late movie".to_string(),
+        )]);
+
+        let days = store
+            .find_between(
+                "ramona",
+                NaiveDate::from_ymd_opt(2024, 4, 30).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 5, 3).unwrap(),
+                Berlin,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            vec![0, 1, 1, 0],
+            days.iter().map(|x| x.events.len()).collect::<Vec<_>>()
+        );
+
+        // 22:00 in Berlin is 21:00 in London, so it's over before midnight there
+        let days = store
+            .find_between(
+                "ramona",
+                NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(),
+                NaiveDate::from_ymd_opt(2024, 5, 2).unwrap(),
+                London,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            vec![1, 0],
+            days.iter().map(|x| x.events.len()).collect::<Vec<_>>()
+        );
+
+        assert!(matches!(
+            store
+                .find_between(
+                    "ramona",
+                    NaiveDate::from_ymd_opt(2024, 5, 2).unwrap(),
+                    NaiveDate::from_ymd_opt(2024, 5, 1).unwrap(),
+                    Berlin,
+                )
+                .await,
+            Err(Error::InvalidRange(_, _))
+        ));
+    }
+
+    #[tokio::test]
+    pub async fn private_events_are_only_visible_to_their_owner() {
+        let store = create_store(vec![event(1, "everyone's")]);
+        let start = Berlin.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+
+        for (title, shared) in [("private", false), ("shared", true)] {
+            store
+                .create(
+                    start,
+                    Duration::from_secs(60),
+                    title.to_string(),
+                    None,
+                    Ownership::new("ramona", shared),
+                )
+                .await
+                .unwrap();
+        }
+
+        let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+
+        for (user, expected) in [
+            ("ramona", vec!["everyone's", "private", "shared"]),
+            ("someone", vec!["everyone's", "shared"]),
+        ] {
+            let day = store
+                .find_between(user, date, date, Berlin)
+                .await
+                .unwrap()
+                .remove(0);
+
+            assert_eq!(
+                expected,
+                day.events.iter().map(Event::title).collect::<Vec<_>>()
+            );
+        }
     }
 }
@@ -1,44 +1,165 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    fs::{File, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+};
 
+use fs4::FileExt;
 use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("IO: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("The data file {0} is not valid: {1}")]
+    Invalid(PathBuf, serde_json::Error),
+}
 
 pub trait DataFileReader {
-    fn read(&self) -> DataFile;
-    fn save(&self, data: DataFile);
+    fn read(&self) -> Result<DataFile, Error>;
+    fn save(&self, data: DataFile) -> Result<(), Error>;
+
+    /// Reads the data file, lets `modify` change it and saves it back. Implementations should
+    /// make sure nobody else writes to the file in between.
+    fn update(&self, modify: &mut dyn FnMut(&mut DataFile)) -> Result<(), Error> {
+        let mut data = self.read()?;
+
+        modify(&mut data);
+
+        self.save(data)
+    }
 }
 
+/// Reads and writes the JSON data file. Writes go to a temporary file that is then renamed over
+/// the original, so a crash mid-write leaves the previous version intact. An advisory lock on a
+/// `.lock` file next to the data file keeps several ras instances from interleaving their
+/// updates, and the last `backup_count` versions are kept as `<name>.1` (newest) to `<name>.N`.
 pub struct DefaultDataFileReader {
     path: PathBuf,
+    backup_count: usize,
 }
 
 impl DefaultDataFileReader {
-    pub fn new(path: PathBuf) -> Self {
-        Self { path }
+    pub fn new(path: PathBuf, backup_count: usize) -> Self {
+        Self { path, backup_count }
     }
-}
 
-impl DataFileReader for DefaultDataFileReader {
-    fn read(&self) -> DataFile {
-        let contents = std::fs::read_to_string(&self.path).unwrap();
-
-        if let Ok(datafile) = serde_json::from_str(&contents) {
-            datafile
-        } else if let Ok(todos) =
-            serde_json::from_str::<HashMap<ratlib::todo::Id, ratlib::todo::Todo>>(&contents)
-        {
-            // FIXME Legacy data format - todos only. Remove this - we don't have the legacy data
-            // format anywhere anymore.
-            DataFile {
-                todos,
-                events: HashMap::new(),
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(suffix);
+
+        self.path.with_file_name(name)
+    }
+
+    fn open_lock_file(&self) -> Result<File, Error> {
+        Ok(OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(self.sibling(".lock"))?)
+    }
+
+    fn read_unlocked(&self) -> Result<DataFile, Error> {
+        let contents = std::fs::read_to_string(&self.path)?;
+
+        match serde_json::from_str(&contents) {
+            Ok(datafile) => Ok(datafile),
+            Err(error) => {
+                if let Ok(todos) =
+                    serde_json::from_str::<HashMap<ratlib::todo::Id, ratlib::todo::Todo>>(&contents)
+                {
+                    // FIXME Legacy data format - todos only. Remove this - we don't have the
+                    // legacy data format anywhere anymore.
+                    Ok(DataFile {
+                        todos,
+                        events: HashMap::new(),
+                        archived_todos: HashMap::new(),
+                    })
+                } else {
+                    Err(Error::Invalid(self.path.clone(), error))
+                }
+            }
+        }
+    }
+
+    fn rotate_backups(&self) -> Result<(), Error> {
+        if self.backup_count == 0 || !self.path.exists() {
+            return Ok(());
+        }
+
+        for i in (1..self.backup_count).rev() {
+            let from = self.sibling(&format!(".{i}"));
+
+            if from.exists() {
+                std::fs::rename(from, self.sibling(&format!(".{}", i + 1)))?;
             }
-        } else {
-            panic!("Failed to read the data file!");
         }
+
+        std::fs::copy(&self.path, self.sibling(".1"))?;
+
+        Ok(())
+    }
+
+    fn save_unlocked(&self, data: &DataFile) -> Result<(), Error> {
+        let temporary_path = self.sibling(".tmp");
+        let contents =
+            serde_json::to_string_pretty(data).map_err(|e| Error::Invalid(self.path.clone(), e))?;
+
+        let mut temporary = File::create(&temporary_path)?;
+        temporary.write_all(contents.as_bytes())?;
+        temporary.sync_all()?;
+        drop(temporary);
+
+        self.rotate_backups()?;
+        std::fs::rename(&temporary_path, &self.path)?;
+
+        // The rename itself only becomes durable once the directory is synced
+        if let Some(directory) = self.path.parent().filter(|x| x != &Path::new("")) {
+            File::open(directory)?.sync_all()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl DataFileReader for DefaultDataFileReader {
+    fn read(&self) -> Result<DataFile, Error> {
+        let lock = self.open_lock_file()?;
+        lock.lock_shared()?;
+
+        let result = self.read_unlocked();
+
+        lock.unlock()?;
+
+        result
     }
 
-    fn save(&self, data: DataFile) {
-        std::fs::write(&self.path, serde_json::to_string_pretty(&data).unwrap()).unwrap();
+    fn save(&self, data: DataFile) -> Result<(), Error> {
+        let lock = self.open_lock_file()?;
+        lock.lock_exclusive()?;
+
+        let result = self.save_unlocked(&data);
+
+        lock.unlock()?;
+
+        result
+    }
+
+    fn update(&self, modify: &mut dyn FnMut(&mut DataFile)) -> Result<(), Error> {
+        let lock = self.open_lock_file()?;
+        lock.lock_exclusive()?;
+
+        let result = self.read_unlocked().and_then(|mut data| {
+            modify(&mut data);
+
+            self.save_unlocked(&data)
+        });
+
+        lock.unlock()?;
+
+        result
     }
 }
 
@@ -47,4 +168,78 @@ pub struct DataFile {
     pub todos: HashMap<ratlib::todo::Id, ratlib::todo::Todo>,
     #[serde(default)]
     pub events: HashMap<ratlib::calendar::event::Id, ratlib::calendar::event::Event>,
+    #[serde(default)]
+    pub archived_todos: HashMap<ratlib::todo::Id, ratlib::todo::archive::ArchivedTodo>,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::PathBuf, time::Duration};
+
+    use ratlib::todo::{Id, Priority, Todo};
+
+    use super::{DataFile, DataFileReader, DefaultDataFileReader, Error};
+
+    fn temporary_directory(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("ras-datafile-{name}-{}", std::process::id()));
+
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+
+        path
+    }
+
+    fn todo(id: usize) -> Todo {
+        Todo::new(
+            Id(id),
+            format!("todo {id}"),
+            Priority::Medium,
+            vec![],
+            Duration::from_secs(60),
+            None,
+        )
+    }
+
+    #[test]
+    pub fn keeps_rotating_backups() {
+        let directory = temporary_directory("backups");
+        let reader = DefaultDataFileReader::new(directory.join("todos.json"), 2);
+
+        reader
+            .save(DataFile {
+                todos: HashMap::new(),
+                events: HashMap::new(),
+                archived_todos: HashMap::new(),
+            })
+            .unwrap();
+
+        for id in 1..=4 {
+            reader
+                .update(&mut |data| {
+                    data.todos.insert(Id(id), todo(id));
+                })
+                .unwrap();
+        }
+
+        assert_eq!(4, reader.read().unwrap().todos.len());
+
+        let newest_backup = DefaultDataFileReader::new(directory.join("todos.json.1"), 0);
+        assert_eq!(3, newest_backup.read().unwrap().todos.len());
+
+        let oldest_backup = DefaultDataFileReader::new(directory.join("todos.json.2"), 0);
+        assert_eq!(2, oldest_backup.read().unwrap().todos.len());
+
+        assert!(!directory.join("todos.json.3").exists());
+        assert!(!directory.join("todos.json.tmp").exists());
+    }
+
+    #[test]
+    pub fn truncated_file_is_an_error() {
+        let directory = temporary_directory("truncated");
+        std::fs::write(directory.join("todos.json"), "{\"todos\": {\"1\": {\"id\"").unwrap();
+
+        let reader = DefaultDataFileReader::new(directory.join("todos.json"), 2);
+
+        assert!(matches!(reader.read(), Err(Error::Invalid(_, _))));
+    }
 }
@@ -1,6 +1,13 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc, time::Duration};
 
-use sqlx::{query, Pool, Postgres};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use ratlib::herd::{
+    ClosurePeriod, DesiredClosure, DiskUsage, LoadAverage, Machine, PostHerdMachine, SystemReport,
+};
+use sqlx::{query, query_as, Pool, Postgres};
+
+use crate::storage::{self, HostRepository};
 
 pub struct Store {
     pool: Arc<Pool<Postgres>>,
@@ -11,26 +18,316 @@ impl Store {
         Self { pool }
     }
 
-    pub async fn update_host(&self, hostname: String, running_closure: String) {
-        let mut transaction = self.pool.begin().await.unwrap();
+    pub async fn update_host(
+        &self,
+        hostname: String,
+        report: &PostHerdMachine,
+    ) -> Result<(), storage::Error> {
+        let running_closure = &report.current_closure;
+        // Reports rad had to queue are sent late, they should count from when they were made.
+        // A clock that's ahead shouldn't make a machine look seen in the future though.
+        let now = Utc::now();
+        let reported_at = report.reported_at.map_or(now, |x| x.min(now));
+        let mut transaction = self.pool.begin().await?;
 
         query!("
                 INSERT INTO 
                     hosts AS h(hostname, last_seen, running_closure_path, last_running_closure_change) 
-                VALUES($1, NOW(), $2, NOW())
+                VALUES($1, $3, $2, $3)
                 ON CONFLICT(hostname) DO UPDATE 
                     SET 
-                        last_seen = NOW(), 
+                        last_seen = GREATEST(h.last_seen, $3), 
                         running_closure_path = $2, 
                         last_running_closure_change = CASE 
-                            WHEN h.running_closure_path != $2 THEN NOW() 
+                            WHEN h.running_closure_path != $2 THEN $3 
                             ELSE h.last_running_closure_change 
                         END
-              ", &hostname, &running_closure)
+              ", &hostname, running_closure, reported_at)
+            .execute(&mut *transaction)
+            .await?;
+
+        // Only when the closure differs from the last one in the history, so the history has
+        // one entry for every switch
+        query!(
+            "INSERT INTO host_closure_history(hostname, closure_path, started_at)
+                SELECT $1, $2, $3
+                WHERE $2 IS DISTINCT FROM (
+                    SELECT closure_path FROM host_closure_history
+                        WHERE hostname = $1
+                        ORDER BY started_at DESC, id DESC
+                        LIMIT 1
+                )",
+            &hostname,
+            running_closure,
+            reported_at
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        let system = &report.system;
+        let load_average = system.load_average.as_ref();
+        query!(
+            "UPDATE hosts
+                SET
+                    booted_closure_path = $2,
+                    kernel_version = $3,
+                    uptime_seconds = $4,
+                    generation = $5,
+                    failed_units = $6,
+                    load_average_1 = $7,
+                    load_average_5 = $8,
+                    load_average_15 = $9
+                WHERE hostname = $1",
+            &hostname,
+            system.booted_closure,
+            system.kernel_version,
+            system.uptime.map(|x| to_i64(x.as_secs())),
+            system.generation.and_then(|x| i32::try_from(x).ok()),
+            &system.failed_units,
+            load_average.map(|x| x.one),
+            load_average.map(|x| x.five),
+            load_average.map(|x| x.fifteen)
+        )
+        .execute(&mut *transaction)
+        .await?;
+
+        query!("DELETE FROM host_disks WHERE hostname = $1", &hostname)
             .execute(&mut *transaction)
-            .await
-            .unwrap();
+            .await?;
+
+        for disk in &system.disks {
+            query!(
+                "INSERT INTO host_disks(hostname, mount_point, used_bytes, total_bytes)
+                    VALUES($1, $2, $3, $4)",
+                &hostname,
+                disk.mount_point,
+                to_i64(disk.used_bytes),
+                to_i64(disk.total_bytes)
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    /// Hosts that never reported can have one too, it shows once they do
+    pub async fn set_desired_closure(
+        &self,
+        hostname: &str,
+        closure: &str,
+    ) -> Result<(), storage::Error> {
+        // Setting the same closure again (e.g. CI rebuilding without changes) doesn't restart
+        // the drift
+        query!(
+            "INSERT INTO desired_closures AS d(hostname, closure_path, set_at)
+                VALUES($1, $2, NOW())
+                ON CONFLICT(hostname) DO UPDATE
+                    SET
+                        closure_path = $2,
+                        set_at = CASE
+                            WHEN d.closure_path != $2 THEN NOW()
+                            ELSE d.set_at
+                        END",
+            hostname,
+            closure
+        )
+        .execute(&*self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Every closure the host ran, the current one first
+    pub async fn find_history(&self, hostname: &str) -> Result<Vec<ClosurePeriod>, storage::Error> {
+        let rows = query!(
+            r#"SELECT closure_path, started_at,
+                    LEAD(started_at) OVER (ORDER BY started_at, id) AS ended_at
+                FROM host_closure_history
+                WHERE hostname = $1
+                ORDER BY started_at DESC, id DESC"#,
+            hostname
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|x| ClosurePeriod {
+                closure: x.closure_path,
+                started_at: x.started_at,
+                ended_at: x.ended_at,
+            })
+            .collect())
+    }
+
+    /// Every machine that ever reported, by hostname
+    pub async fn find_machines(&self) -> Result<Vec<Machine>, storage::Error> {
+        let now = Utc::now();
+        let rows = query_as!(
+            HostRow,
+            r#"SELECT h.hostname, last_seen, running_closure_path, last_running_closure_change,
+                    d.closure_path AS "desired_closure_path?",
+                    d.set_at AS "desired_closure_set_at?", booted_closure_path, kernel_version,
+                    uptime_seconds, generation, failed_units, load_average_1, load_average_5,
+                    load_average_15
+                FROM hosts h LEFT JOIN desired_closures d ON d.hostname = h.hostname
+                ORDER BY h.hostname"#
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        let mut disks: HashMap<String, Vec<DiskUsage>> = HashMap::new();
+        for row in query_as!(
+            DiskRow,
+            "SELECT hostname, mount_point, used_bytes, total_bytes FROM host_disks
+                ORDER BY hostname, mount_point"
+        )
+        .fetch_all(&*self.pool)
+        .await?
+        {
+            disks
+                .entry(row.hostname.clone())
+                .or_default()
+                .push(row.into_disk_usage());
+        }
+
+        Ok(rows
+            .into_iter()
+            .map(|x| {
+                let disks = disks.remove(&x.hostname).unwrap_or_default();
+
+                x.into_machine(disks, now)
+            })
+            .collect())
+    }
+
+    pub async fn find_machine(&self, hostname: &str) -> Result<Option<Machine>, storage::Error> {
+        let Some(row) = query_as!(
+            HostRow,
+            r#"SELECT h.hostname, last_seen, running_closure_path, last_running_closure_change,
+                    d.closure_path AS "desired_closure_path?",
+                    d.set_at AS "desired_closure_set_at?", booted_closure_path, kernel_version,
+                    uptime_seconds, generation, failed_units, load_average_1, load_average_5,
+                    load_average_15
+                FROM hosts h LEFT JOIN desired_closures d ON d.hostname = h.hostname
+                WHERE h.hostname = $1"#,
+            hostname
+        )
+        .fetch_optional(&*self.pool)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let disks = query_as!(
+            DiskRow,
+            "SELECT hostname, mount_point, used_bytes, total_bytes FROM host_disks
+                WHERE hostname = $1 ORDER BY mount_point",
+            hostname
+        )
+        .fetch_all(&*self.pool)
+        .await?
+        .into_iter()
+        .map(DiskRow::into_disk_usage)
+        .collect();
+
+        Ok(Some(row.into_machine(disks, Utc::now())))
+    }
+}
+
+struct HostRow {
+    hostname: String,
+    last_seen: DateTime<Utc>,
+    running_closure_path: String,
+    last_running_closure_change: Option<DateTime<Utc>>,
+    desired_closure_path: Option<String>,
+    desired_closure_set_at: Option<DateTime<Utc>>,
+    booted_closure_path: Option<String>,
+    kernel_version: Option<String>,
+    uptime_seconds: Option<i64>,
+    generation: Option<i32>,
+    failed_units: Vec<String>,
+    load_average_1: Option<f64>,
+    load_average_5: Option<f64>,
+    load_average_15: Option<f64>,
+}
+
+impl HostRow {
+    fn into_machine(self, disks: Vec<DiskUsage>, now: DateTime<Utc>) -> Machine {
+        let desired_closure = self
+            .desired_closure_path
+            .zip(self.desired_closure_set_at)
+            .map(|(closure, set_at)| DesiredClosure { closure, set_at });
+
+        let load_average = match (
+            self.load_average_1,
+            self.load_average_5,
+            self.load_average_15,
+        ) {
+            (Some(one), Some(five), Some(fifteen)) => Some(LoadAverage { one, five, fifteen }),
+            _ => None,
+        };
+
+        let system = SystemReport {
+            booted_closure: self.booted_closure_path,
+            kernel_version: self.kernel_version,
+            uptime: self
+                .uptime_seconds
+                .and_then(|x| u64::try_from(x).ok())
+                .map(Duration::from_secs),
+            generation: self.generation.and_then(|x| u32::try_from(x).ok()),
+            failed_units: self.failed_units,
+            disks,
+            load_average,
+        };
+
+        Machine::new(
+            self.hostname,
+            self.last_seen,
+            self.running_closure_path,
+            self.last_running_closure_change,
+            desired_closure,
+            system,
+            now,
+        )
+    }
+}
+
+struct DiskRow {
+    hostname: String,
+    mount_point: String,
+    used_bytes: i64,
+    total_bytes: i64,
+}
+
+impl DiskRow {
+    fn into_disk_usage(self) -> DiskUsage {
+        DiskUsage {
+            mount_point: self.mount_point,
+            used_bytes: u64::try_from(self.used_bytes).unwrap_or_default(),
+            total_bytes: u64::try_from(self.total_bytes).unwrap_or_default(),
+        }
+    }
+}
+
+// Postgres has no unsigned integers, and nothing reported gets anywhere near i64::MAX
+fn to_i64(value: u64) -> i64 {
+    i64::try_from(value).unwrap_or(i64::MAX)
+}
+
+#[async_trait]
+impl HostRepository for Store {
+    async fn find_last_seen(&self) -> Result<HashMap<String, DateTime<Utc>>, storage::Error> {
+        let rows = query!("SELECT hostname, last_seen FROM hosts")
+            .fetch_all(&*self.pool)
+            .await?;
 
-        transaction.commit().await.unwrap();
+        Ok(rows
+            .into_iter()
+            .map(|x| (x.hostname, x.last_seen))
+            .collect())
     }
 }
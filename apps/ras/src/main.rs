@@ -3,9 +3,10 @@ mod calendar;
 mod datafile;
 mod herd;
 mod maintenance;
+mod storage;
 mod todo;
 
-use std::{error::Error, net::SocketAddr, path::PathBuf, sync::Arc};
+use std::{error::Error, net::SocketAddr, sync::Arc};
 
 use app::AppState;
 use axum::{
@@ -13,17 +14,22 @@ use axum::{
     Router,
 };
 use axum_tracing_opentelemetry::middleware::{OtelAxumLayer, OtelInResponseLayer};
-use datafile::DefaultDataFileReader;
+use datafile::{DataFileReader, DefaultDataFileReader};
 use maintenance::MonitoringMaintainer;
 use opentelemetry::KeyValue;
 use opentelemetry_otlp::WithExportConfig;
 use opentelemetry_sdk::{runtime::Tokio, Resource};
 use sqlx::postgres::PgPoolOptions;
+use storage::{
+    datafile::DataFileRepository, postgres::PostgresRepository, EventRepository, TodoRepository,
+};
 use tokio::sync::Mutex;
 use tokio_postgres::NoTls;
-use tracing::error;
+use tracing::{error, info};
 use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Registry};
 
+const DATAFILE_BACKUP_COUNT: usize = 5;
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
     let tracer = opentelemetry_otlp::new_pipeline()
@@ -57,7 +63,42 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     sqlx::migrate!("./migrations/").run(&pool).await?;
 
-    let datafile_path: PathBuf = std::env::args().nth(1).unwrap().into();
+    let pool = Arc::new(pool);
+    let postgres_repository = Arc::new(PostgresRepository::new(pool.clone()));
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    let (todo_repository, event_repository): (
+        Arc<dyn TodoRepository + Send + Sync>,
+        Arc<dyn EventRepository + Send + Sync>,
+    ) = match args.as_slice() {
+        [] => (postgres_repository.clone(), postgres_repository),
+        // `ras import <datafile>` copies an existing datafile into the database and exits
+        ["import", datafile_path] => {
+            return import(&postgres_repository, datafile_path).await;
+        }
+        // Only imports into a database that has nothing in it yet, so it can run on every start
+        // without overwriting newer changes with what's in the datafile
+        ["import", "--if-empty", datafile_path] => {
+            if postgres_repository.is_empty().await? {
+                return import(&postgres_repository, datafile_path).await;
+            }
+
+            info!("The database already has data, not importing {datafile_path}");
+
+            return Ok(());
+        }
+        // `ras <datafile>` keeps everything in the datafile instead of the database
+        [datafile_path] => {
+            let repository = Arc::new(DataFileRepository::new(Arc::new(
+                DefaultDataFileReader::new(datafile_path.into(), DATAFILE_BACKUP_COUNT),
+            )));
+
+            (repository.clone(), repository)
+        }
+        _ => return Err("Usage: ras [datafile] | ras import [--if-empty] <datafile>".into()),
+    };
+
     let postgres_password = ratlib::secrets::read("telegraf-database")
         .unwrap()
         .trim()
@@ -77,29 +118,60 @@ async fn main() -> Result<(), Box<dyn Error>> {
         }
     });
 
-    let data_file_reader = Arc::new(DefaultDataFileReader::new(datafile_path));
+    let herd_store = Arc::new(herd::Store::new(pool));
+
+    let mut todo_store = todo::store::Store::new(todo_repository, herd_store.clone());
+    // e.g. `{"priority": 1.0, "deadline": 2.0, "age": 0.25}`, missing weights keep their defaults
+    if let Ok(weights) = std::env::var("URGENCY_WEIGHTS") {
+        todo_store.set_urgency_weights(serde_json::from_str(&weights)?);
+    }
 
     let router = Router::new()
         .route("/", get(app::index))
         .route("/todos", get(app::todos::get_todos))
         .route("/todos", post(app::todos::post_todos))
-        .route("/todos/:id", post(app::todos::post_todos_with_id))
+        .route("/todos/graph", get(app::todos::get_graph))
+        .route("/todos/report", get(app::todos::get_report))
+        .route("/todos/archive", get(app::todos::get_archive))
+        .route("/todos/undo", post(app::todos::post_undo))
+        .route(
+            "/todos/:id",
+            get(app::todos::get_todo).post(app::todos::post_todos_with_id),
+        )
+        .route("/todos/:id/blockers", get(app::todos::get_blockers))
+        .route("/herd/machines", get(app::herd::get_herd_machines))
         .route(
             "/herd/machines/:hostname",
-            post(app::herd::post_herd_machine),
+            get(app::herd::get_herd_machine).post(app::herd::post_herd_machine),
+        )
+        .route(
+            "/herd/machines/:hostname/desired",
+            post(app::herd::post_desired_closure),
+        )
+        .route(
+            "/herd/machines/:hostname/history",
+            get(app::herd::get_herd_history),
         )
         .route(
             "/maintenance/monitoring",
             post(app::maintenance::post_monitoring),
         )
+        .route("/plan", get(app::plan::get))
         .route("/events", get(app::events::get).post(app::events::post))
+        .route("/events.ics", get(app::events::get_ics))
+        .route("/events/import", post(app::events::post_import))
+        .route("/events/stream", get(app::feed::get_stream))
+        .route(
+            "/events/:id",
+            get(app::events::get_with_id).post(app::events::post_with_id),
+        )
         .with_state(AppState {
-            todo_store: Arc::new(Mutex::new(todo::store::Store::new(
-                data_file_reader.clone(),
-            ))),
-            event_store: Arc::new(Mutex::new(calendar::store::Store::new(data_file_reader))),
+            todo_store: Arc::new(Mutex::new(todo_store)),
+            event_store: Arc::new(Mutex::new(calendar::store::Store::new(event_repository))),
             monitoring_maintainer: Arc::new(MonitoringMaintainer::new(Arc::new(postgres_client))),
-            herd_store: Arc::new(herd::Store::new(Arc::new(pool))),
+            herd_store,
+            feed: app::feed::Feed::new(),
+            tokens: Arc::new(app::auth::Tokens::read()?),
         })
         .layer(OtelInResponseLayer)
         .layer(OtelAxumLayer::default());
@@ -111,3 +183,15 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+async fn import(
+    repository: &PostgresRepository,
+    datafile_path: &str,
+) -> Result<(), Box<dyn Error>> {
+    let datafile = DefaultDataFileReader::new(datafile_path.into(), 0).read()?;
+    repository.import(datafile).await?;
+
+    info!("Imported {datafile_path}");
+
+    Ok(())
+}
@@ -0,0 +1,174 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use ratlib::{
+    calendar::event::{self, Event},
+    todo::{
+        self,
+        archive::{ArchivedTodo, Page},
+        filter::Filter,
+        IdGenerator, Todo,
+    },
+};
+
+use crate::datafile::DataFileReader;
+
+use super::{Error, EventRepository, TodoRepository};
+
+/// Keeps everything in a single JSON file. Every call reads (and possibly rewrites) the whole
+/// file, so this is only meant for local development and tests.
+pub struct DataFileRepository {
+    reader: Arc<dyn DataFileReader + Send + Sync>,
+}
+
+impl DataFileRepository {
+    pub fn new(reader: Arc<dyn DataFileReader + Send + Sync>) -> Self {
+        Self { reader }
+    }
+}
+
+#[async_trait]
+impl TodoRepository for DataFileRepository {
+    async fn next_id(&self) -> Result<todo::Id, Error> {
+        let datafile = self.reader.read()?;
+        let mut id_generator = IdGenerator::new(
+            datafile
+                .todos
+                .keys()
+                .chain(datafile.archived_todos.keys())
+                .map(|x| x.0)
+                .max()
+                .unwrap_or(0),
+        );
+
+        Ok(id_generator.next())
+    }
+
+    async fn find_all(&self) -> Result<Vec<Todo>, Error> {
+        Ok(self.reader.read()?.todos.into_values().collect())
+    }
+
+    async fn find_by_id(&self, id: todo::Id) -> Result<Option<Todo>, Error> {
+        Ok(self.reader.read()?.todos.remove(&id))
+    }
+
+    async fn find(&self, filter: &Filter) -> Result<Vec<Todo>, Error> {
+        Ok(self
+            .reader
+            .read()?
+            .todos
+            .into_values()
+            .filter(|x| filter.matches(x))
+            .collect())
+    }
+
+    async fn find_visible_to(&self, user: &str) -> Result<Vec<Todo>, Error> {
+        Ok(self
+            .reader
+            .read()?
+            .todos
+            .into_values()
+            .filter(|x| x.ownership().is_visible_to(user))
+            .collect())
+    }
+
+    async fn save(&self, todo: &Todo) -> Result<(), Error> {
+        Ok(self.reader.update(&mut |datafile| {
+            datafile.todos.insert(todo.id(), todo.clone());
+        })?)
+    }
+
+    async fn archive(&self, todo: &ArchivedTodo) -> Result<(), Error> {
+        Ok(self.reader.update(&mut |datafile| {
+            datafile.todos.remove(&todo.todo.id());
+            datafile.archived_todos.insert(todo.todo.id(), todo.clone());
+        })?)
+    }
+
+    async fn find_archived(
+        &self,
+        include_deleted: bool,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Page, Error> {
+        let mut todos: Vec<_> = self
+            .reader
+            .read()?
+            .archived_todos
+            .into_values()
+            .filter(|x| include_deleted || !x.deleted)
+            .collect();
+        todos.sort_by_key(|x| (std::cmp::Reverse(x.archived_at), x.todo.id().0));
+
+        Ok(Page {
+            total: todos.len(),
+            todos: todos.into_iter().skip(offset).take(limit).collect(),
+            offset,
+        })
+    }
+
+    async fn remove(&self, id: todo::Id) -> Result<(), Error> {
+        Ok(self.reader.update(&mut |datafile| {
+            datafile.todos.remove(&id);
+            datafile.archived_todos.remove(&id);
+        })?)
+    }
+}
+
+#[async_trait]
+impl EventRepository for DataFileRepository {
+    async fn next_id(&self) -> Result<event::Id, Error> {
+        let datafile = self.reader.read()?;
+
+        Ok(event::Id(
+            datafile.events.keys().map(|x| x.0).max().unwrap_or(0) + 1,
+        ))
+    }
+
+    async fn find_all(&self) -> Result<Vec<Event>, Error> {
+        Ok(self.reader.read()?.events.into_values().collect())
+    }
+
+    async fn find_overlapping(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Event>, Error> {
+        Ok(self
+            .reader
+            .read()?
+            .events
+            .into_values()
+            .filter(|x| {
+                x.start() < to && (x.recurrence().is_some() || x.start() + x.duration() >= from)
+            })
+            .collect())
+    }
+
+    async fn find_by_id(&self, id: event::Id) -> Result<Option<Event>, Error> {
+        Ok(self.reader.read()?.events.remove(&id))
+    }
+
+    async fn find_by_uid(&self, uid: &str) -> Result<Vec<Event>, Error> {
+        Ok(self
+            .reader
+            .read()?
+            .events
+            .into_values()
+            .filter(|x| x.uid() == uid)
+            .collect())
+    }
+
+    async fn save(&self, event: &Event) -> Result<(), Error> {
+        Ok(self.reader.update(&mut |datafile| {
+            datafile.events.insert(event.id(), event.clone());
+        })?)
+    }
+
+    async fn delete(&self, id: event::Id) -> Result<(), Error> {
+        Ok(self.reader.update(&mut |datafile| {
+            datafile.events.remove(&id);
+        })?)
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use ratlib::{
+    calendar::event::{self, Event},
+    todo::{
+        self,
+        archive::{ArchivedTodo, Page},
+        filter::Filter,
+        Todo,
+    },
+};
+use thiserror::Error;
+
+pub mod datafile;
+pub mod postgres;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("DB: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("Data file: {0}")]
+    DataFile(#[from] crate::datafile::Error),
+    #[error("Invalid value in storage: {0}")]
+    InvalidValue(String),
+}
+
+#[async_trait]
+pub trait TodoRepository {
+    async fn next_id(&self) -> Result<todo::Id, Error>;
+    async fn find_all(&self) -> Result<Vec<Todo>, Error>;
+    async fn find_by_id(&self, id: todo::Id) -> Result<Option<Todo>, Error>;
+    /// In no particular order
+    async fn find(&self, filter: &Filter) -> Result<Vec<Todo>, Error>;
+    async fn find_visible_to(&self, user: &str) -> Result<Vec<Todo>, Error>;
+    async fn save(&self, todo: &Todo) -> Result<(), Error>;
+    /// Moves the todo out of the way of the other methods, into the archive
+    async fn archive(&self, todo: &ArchivedTodo) -> Result<(), Error>;
+    /// Most recently archived first
+    async fn find_archived(
+        &self,
+        include_deleted: bool,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Page, Error>;
+    /// Removes the todo for good, whether it's archived or not
+    async fn remove(&self, id: todo::Id) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait EventRepository {
+    async fn next_id(&self) -> Result<event::Id, Error>;
+    async fn find_all(&self) -> Result<Vec<Event>, Error>;
+    /// Events that can have an occurrence overlapping the range from `from` to `to`. Recurring
+    /// events that start before `to` are all included, as only expanding them tells.
+    async fn find_overlapping(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Event>, Error>;
+    async fn find_by_id(&self, id: event::Id) -> Result<Option<Event>, Error>;
+    /// Every user's copy of the event with this iCalendar UID
+    async fn find_by_uid(&self, uid: &str) -> Result<Vec<Event>, Error>;
+    async fn save(&self, event: &Event) -> Result<(), Error>;
+    async fn delete(&self, id: event::Id) -> Result<(), Error>;
+}
+
+#[async_trait]
+pub trait HostRepository {
+    /// When each host last reported to ras, by hostname
+    async fn find_last_seen(&self) -> Result<HashMap<String, DateTime<Utc>>, Error>;
+}
@@ -0,0 +1,568 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use ratlib::{
+    calendar::event::{self, Event},
+    ownership::Ownership,
+    todo::{
+        self,
+        archive::{ArchivedTodo, Page},
+        checklist::Checklist,
+        filter::Filter,
+        recurrence::Recurrence,
+        Priority, Requirement, Status, Todo, Transition,
+    },
+};
+use sqlx::{query, query_as, types::Json, PgExecutor, Pool, Postgres};
+
+use crate::datafile::DataFile;
+
+use super::{Error, EventRepository, TodoRepository};
+
+pub struct PostgresRepository {
+    pool: Arc<Pool<Postgres>>,
+}
+
+impl PostgresRepository {
+    pub fn new(pool: Arc<Pool<Postgres>>) -> Self {
+        Self { pool }
+    }
+
+    /// Copies everything from a datafile into the database, keeping the existing IDs. Rows that
+    /// already exist get overwritten, so it's safe to run more than once.
+    pub async fn import(&self, datafile: DataFile) -> Result<(), Error> {
+        let mut transaction = self.pool.begin().await?;
+
+        for todo in datafile.todos.into_values() {
+            save_todo(&mut *transaction, &todo).await?;
+        }
+
+        for todo in datafile.archived_todos.into_values() {
+            save_archived_todo(&mut *transaction, &todo).await?;
+        }
+
+        for event in datafile.events.into_values() {
+            save_event(&mut *transaction, &event).await?;
+        }
+
+        // The sequences don't know about the explicitly inserted IDs, move them past those.
+        // Archived todos keep their IDs, so new todos must not get those either.
+        query!(
+            "SELECT setval(
+                'todos_id_seq',
+                GREATEST(
+                    (SELECT COALESCE(MAX(id), 0) FROM todos),
+                    (SELECT COALESCE(MAX(id), 0) FROM archived_todos)
+                ) + 1,
+                false
+            )"
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        query!(
+            "SELECT setval('events_id_seq', (SELECT COALESCE(MAX(id), 0) + 1 FROM events), false)"
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    /// Whether there are no todos, archived todos or events at all
+    pub async fn is_empty(&self) -> Result<bool, Error> {
+        let row = query!(
+            r#"SELECT NOT (
+                EXISTS(SELECT 1 FROM todos)
+                OR EXISTS(SELECT 1 FROM archived_todos)
+                OR EXISTS(SELECT 1 FROM events)
+            ) AS "empty!""#
+        )
+        .fetch_one(&*self.pool)
+        .await?;
+
+        Ok(row.empty)
+    }
+
+    // Every way of looking todos up in one query. The criteria that are `None` match everything.
+    async fn select_todos(
+        &self,
+        id: Option<todo::Id>,
+        filter: &Filter,
+        visible_to: Option<&str>,
+    ) -> Result<Vec<Todo>, Error> {
+        let rows = query_as!(
+            TodoRow,
+            r#"SELECT
+                    id, title, priority, status, estimate_seconds, deadline, deadline_timezone,
+                    requirements AS "requirements: Json<Vec<Requirement>>",
+                    recurrence AS "recurrence: Json<Recurrence>",
+                    next_instance, tags, project, notes,
+                    checklist AS "checklist: Json<Checklist>",
+                    created_at,
+                    history AS "history: Json<Vec<Transition>>",
+                    owner, shared
+                FROM todos
+                WHERE ($1::BIGINT IS NULL OR id = $1)
+                    AND ($2::TEXT IS NULL OR status = $2)
+                    AND ($3::SMALLINT IS NULL OR priority >= $3)
+                    AND ($4::SMALLINT IS NULL OR priority <= $4)
+                    AND ($5::BOOLEAN IS NULL OR (deadline IS NOT NULL) = $5)
+                    AND ($6::TIMESTAMPTZ IS NULL OR deadline < $6)
+                    AND ($7::TIMESTAMPTZ IS NULL OR deadline > $7)
+                    AND ($8::TEXT IS NULL OR strpos(lower(title), lower($8)) > 0)
+                    -- Requirements nest, so this looks at every level
+                    AND ($9::BIGINT IS NULL OR jsonb_path_exists(
+                        requirements,
+                        '$.** ? (@.TodoDone == $id)',
+                        jsonb_build_object('id', $9::BIGINT)
+                    ))
+                    AND ($10::TEXT IS NULL OR $10 = ANY(tags))
+                    AND ($11::TEXT IS NULL OR project = $11)
+                    AND ($12::TEXT IS NULL OR owner IS NULL OR shared OR owner = $12)"#,
+            id.map(|x| x.0 as i64),
+            filter.status.map(status_to_db),
+            filter.min_priority.map(priority_to_db),
+            filter.max_priority.map(priority_to_db),
+            filter.has_deadline,
+            filter.deadline_before,
+            filter.deadline_after,
+            filter.title_contains,
+            filter.requires.map(|x| x.0 as i64),
+            filter.tag.as_deref().map(|x| x.trim_start_matches('#')),
+            filter.project,
+            visible_to
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        rows.into_iter().map(TodoRow::into_todo).collect()
+    }
+
+    // Like `select_todos`, the criteria that are `None` match everything
+    async fn select_events(
+        &self,
+        id: Option<event::Id>,
+        overlapping: Option<(DateTime<Utc>, DateTime<Utc>)>,
+        uid: Option<&str>,
+    ) -> Result<Vec<Event>, Error> {
+        let rows = query_as!(
+            EventRow,
+            r#"SELECT id, title, start, start_timezone, duration_seconds, uid, recurrence, exdates,
+                    owner, shared
+                FROM events
+                WHERE ($1::INTEGER IS NULL OR id = $1)
+                    AND ($2::TIMESTAMPTZ IS NULL OR start < $2)
+                    AND (
+                        $3::TIMESTAMPTZ IS NULL
+                        OR recurrence IS NOT NULL
+                        OR start + make_interval(secs => duration_seconds::DOUBLE PRECISION) >= $3
+                    )
+                    -- Events created here have a UID based on their ID, see `Event::uid`
+                    AND ($4::TEXT IS NULL OR uid = $4 OR (uid IS NULL AND 'event-' || id || '@ras' = $4))"#,
+            id.map(|x| x.0 as i32),
+            overlapping.map(|(_, to)| to),
+            overlapping.map(|(from, _)| from),
+            uid
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+
+        rows.into_iter().map(EventRow::into_event).collect()
+    }
+}
+
+#[async_trait]
+impl TodoRepository for PostgresRepository {
+    async fn next_id(&self) -> Result<todo::Id, Error> {
+        let row = query!(r#"SELECT nextval('todos_id_seq') AS "id!""#)
+            .fetch_one(&*self.pool)
+            .await?;
+
+        Ok(todo::Id(row.id as usize))
+    }
+
+    async fn find_all(&self) -> Result<Vec<Todo>, Error> {
+        self.select_todos(None, &Filter::default(), None).await
+    }
+
+    async fn find_by_id(&self, id: todo::Id) -> Result<Option<Todo>, Error> {
+        Ok(self
+            .select_todos(Some(id), &Filter::default(), None)
+            .await?
+            .pop())
+    }
+
+    async fn find(&self, filter: &Filter) -> Result<Vec<Todo>, Error> {
+        self.select_todos(None, filter, None).await
+    }
+
+    async fn find_visible_to(&self, user: &str) -> Result<Vec<Todo>, Error> {
+        self.select_todos(None, &Filter::default(), Some(user))
+            .await
+    }
+
+    async fn save(&self, todo: &Todo) -> Result<(), Error> {
+        save_todo(&*self.pool, todo).await
+    }
+
+    async fn archive(&self, todo: &ArchivedTodo) -> Result<(), Error> {
+        let mut transaction = self.pool.begin().await?;
+
+        query!("DELETE FROM todos WHERE id = $1", todo.todo.id().0 as i64)
+            .execute(&mut *transaction)
+            .await?;
+        save_archived_todo(&mut *transaction, todo).await?;
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    async fn find_archived(
+        &self,
+        include_deleted: bool,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Page, Error> {
+        let rows = query!(
+            r#"SELECT data AS "data: Json<Todo>", archived_at, deleted FROM archived_todos
+                WHERE $1 OR NOT deleted ORDER BY archived_at DESC, id OFFSET $2 LIMIT $3"#,
+            include_deleted,
+            offset as i64,
+            limit as i64
+        )
+        .fetch_all(&*self.pool)
+        .await?;
+        let total = query!(
+            r#"SELECT COUNT(*) AS "count!" FROM archived_todos WHERE $1 OR NOT deleted"#,
+            include_deleted
+        )
+        .fetch_one(&*self.pool)
+        .await?;
+
+        Ok(Page {
+            todos: rows
+                .into_iter()
+                .map(|x| ArchivedTodo {
+                    todo: x.data.0,
+                    archived_at: x.archived_at,
+                    deleted: x.deleted,
+                })
+                .collect(),
+            offset,
+            total: total.count as usize,
+        })
+    }
+
+    async fn remove(&self, id: todo::Id) -> Result<(), Error> {
+        let mut transaction = self.pool.begin().await?;
+
+        query!("DELETE FROM todos WHERE id = $1", id.0 as i64)
+            .execute(&mut *transaction)
+            .await?;
+        query!("DELETE FROM archived_todos WHERE id = $1", id.0 as i64)
+            .execute(&mut *transaction)
+            .await?;
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl EventRepository for PostgresRepository {
+    async fn next_id(&self) -> Result<event::Id, Error> {
+        let row = query!(r#"SELECT nextval('events_id_seq') AS "id!""#)
+            .fetch_one(&*self.pool)
+            .await?;
+
+        Ok(event::Id(row.id as u32))
+    }
+
+    async fn find_all(&self) -> Result<Vec<Event>, Error> {
+        self.select_events(None, None, None).await
+    }
+
+    async fn find_overlapping(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Vec<Event>, Error> {
+        self.select_events(None, Some((from, to)), None).await
+    }
+
+    async fn find_by_id(&self, id: event::Id) -> Result<Option<Event>, Error> {
+        Ok(self.select_events(Some(id), None, None).await?.pop())
+    }
+
+    async fn find_by_uid(&self, uid: &str) -> Result<Vec<Event>, Error> {
+        self.select_events(None, None, Some(uid)).await
+    }
+
+    async fn save(&self, event: &Event) -> Result<(), Error> {
+        save_event(&*self.pool, event).await
+    }
+
+    async fn delete(&self, id: event::Id) -> Result<(), Error> {
+        query!("DELETE FROM events WHERE id = $1", id.0 as i32)
+            .execute(&*self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+async fn save_todo<'e>(executor: impl PgExecutor<'e>, todo: &Todo) -> Result<(), Error> {
+    query!(
+        "INSERT INTO todos(
+                id, title, priority, status, estimate_seconds, deadline, deadline_timezone,
+                requirements, recurrence, next_instance, tags, project, notes, checklist,
+                created_at, history, owner, shared
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
+            ON CONFLICT(id) DO UPDATE SET
+                title = EXCLUDED.title,
+                priority = EXCLUDED.priority,
+                status = EXCLUDED.status,
+                estimate_seconds = EXCLUDED.estimate_seconds,
+                deadline = EXCLUDED.deadline,
+                deadline_timezone = EXCLUDED.deadline_timezone,
+                requirements = EXCLUDED.requirements,
+                recurrence = EXCLUDED.recurrence,
+                next_instance = EXCLUDED.next_instance,
+                tags = EXCLUDED.tags,
+                project = EXCLUDED.project,
+                notes = EXCLUDED.notes,
+                checklist = EXCLUDED.checklist,
+                created_at = EXCLUDED.created_at,
+                history = EXCLUDED.history,
+                owner = EXCLUDED.owner,
+                shared = EXCLUDED.shared",
+        todo.id().0 as i64,
+        todo.title(),
+        priority_to_db(todo.priority()),
+        status_to_db(todo.status()),
+        todo.estimate().as_secs() as i64,
+        todo.deadline().map(|x| x.to_utc()),
+        todo.deadline().map(|x| x.timezone().name()),
+        Json(todo.requirements()) as _,
+        todo.recurrence().map(Json) as _,
+        todo.next_instance().map(|x| x.0 as i64),
+        todo.tags(),
+        todo.project(),
+        todo.notes(),
+        Json(todo.checklist()) as _,
+        todo.created_at(),
+        Json(todo.history()) as _,
+        todo.ownership().owner(),
+        todo.ownership().is_shared()
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+async fn save_archived_todo<'e>(
+    executor: impl PgExecutor<'e>,
+    todo: &ArchivedTodo,
+) -> Result<(), Error> {
+    query!(
+        "INSERT INTO archived_todos(id, data, archived_at, deleted) VALUES ($1, $2, $3, $4)
+            ON CONFLICT(id) DO UPDATE SET data = $2, archived_at = $3, deleted = $4",
+        todo.todo.id().0 as i64,
+        Json(&todo.todo) as _,
+        todo.archived_at,
+        todo.deleted
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+async fn save_event<'e>(executor: impl PgExecutor<'e>, event: &Event) -> Result<(), Error> {
+    query!(
+        "INSERT INTO events(
+                id, title, start, start_timezone, duration_seconds, uid, recurrence, exdates,
+                owner, shared
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            ON CONFLICT(id) DO UPDATE SET
+                title = EXCLUDED.title,
+                start = EXCLUDED.start,
+                start_timezone = EXCLUDED.start_timezone,
+                duration_seconds = EXCLUDED.duration_seconds,
+                uid = EXCLUDED.uid,
+                recurrence = EXCLUDED.recurrence,
+                exdates = EXCLUDED.exdates,
+                owner = EXCLUDED.owner,
+                shared = EXCLUDED.shared",
+        event.id().0 as i32,
+        event.title(),
+        event.start().to_utc(),
+        event.start().timezone().name(),
+        event.duration().as_secs() as i64,
+        event.external_uid(),
+        event.recurrence().map(ToString::to_string),
+        event.exdates(),
+        event.ownership().owner(),
+        event.ownership().is_shared()
+    )
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+struct TodoRow {
+    id: i64,
+    title: String,
+    priority: i16,
+    status: String,
+    estimate_seconds: i64,
+    deadline: Option<DateTime<Utc>>,
+    deadline_timezone: Option<String>,
+    requirements: Json<Vec<Requirement>>,
+    recurrence: Option<Json<Recurrence>>,
+    next_instance: Option<i64>,
+    tags: Vec<String>,
+    project: Option<String>,
+    notes: String,
+    checklist: Json<Checklist>,
+    created_at: Option<DateTime<Utc>>,
+    history: Json<Vec<Transition>>,
+    owner: Option<String>,
+    shared: bool,
+}
+
+impl TodoRow {
+    fn into_todo(self) -> Result<Todo, Error> {
+        let deadline = match (self.deadline, self.deadline_timezone) {
+            (Some(deadline), Some(timezone)) => {
+                Some(deadline.with_timezone(&parse_timezone(&timezone)?))
+            }
+            _ => None,
+        };
+
+        let mut todo = Todo::new(
+            todo::Id(self.id as usize),
+            self.title,
+            priority_from_db(self.priority)?,
+            self.requirements.0,
+            Duration::from_secs(self.estimate_seconds as u64),
+            deadline,
+        );
+        todo.restore_status(status_from_db(&self.status)?, self.history.0);
+        todo.set_recurrence(self.recurrence.map(|x| x.0));
+        if let Some(next_instance) = self.next_instance {
+            todo.set_next_instance(todo::Id(next_instance as usize));
+        }
+        for tag in &self.tags {
+            todo.add_tag(tag);
+        }
+        todo.set_project(self.project);
+        todo.set_notes(self.notes);
+        *todo.checklist_mut() = self.checklist.0;
+        if let Some(created_at) = self.created_at {
+            todo.set_created_at(created_at);
+        }
+        todo.set_ownership(ownership(self.owner, self.shared));
+
+        Ok(todo)
+    }
+}
+
+struct EventRow {
+    id: i32,
+    title: String,
+    start: DateTime<Utc>,
+    start_timezone: String,
+    duration_seconds: i64,
+    uid: Option<String>,
+    recurrence: Option<String>,
+    exdates: Vec<NaiveDateTime>,
+    owner: Option<String>,
+    shared: bool,
+}
+
+impl EventRow {
+    fn into_event(self) -> Result<Event, Error> {
+        let mut event = Event::new(
+            event::Id(self.id as u32),
+            self.start
+                .with_timezone(&parse_timezone(&self.start_timezone)?),
+            Duration::from_secs(self.duration_seconds as u64),
+            self.title,
+        );
+        if let Some(uid) = self.uid {
+            event.set_uid(uid);
+        }
+        event.set_recurrence(
+            self.recurrence
+                .map(|x| x.parse())
+                .transpose()
+                .map_err(|e| {
+                    Error::InvalidValue(format!("recurrence of event {}: {e}", self.id))
+                })?,
+        );
+        for exdate in self.exdates {
+            event.add_exdate(exdate);
+        }
+        event.set_ownership(ownership(self.owner, self.shared));
+
+        Ok(event)
+    }
+}
+
+fn ownership(owner: Option<String>, shared: bool) -> Ownership {
+    let mut ownership = owner.map_or_else(Ownership::default, |x| Ownership::new(x, shared));
+    ownership.set_shared(shared);
+
+    ownership
+}
+
+fn parse_timezone(name: &str) -> Result<Tz, Error> {
+    name.parse()
+        .map_err(|_| Error::InvalidValue(format!("timezone {name}")))
+}
+
+fn status_to_db(status: Status) -> &'static str {
+    match status {
+        Status::Todo => "Todo",
+        Status::Doing => "Doing",
+        Status::Done => "Done",
+    }
+}
+
+fn status_from_db(status: &str) -> Result<Status, Error> {
+    match status {
+        "Todo" => Ok(Status::Todo),
+        "Doing" => Ok(Status::Doing),
+        "Done" => Ok(Status::Done),
+        _ => Err(Error::InvalidValue(format!("status {status}"))),
+    }
+}
+
+// Numbers rather than names, so that the database can compare them
+fn priority_to_db(priority: Priority) -> i16 {
+    match priority {
+        Priority::Low => 0,
+        Priority::Medium => 1,
+        Priority::High => 2,
+    }
+}
+
+fn priority_from_db(priority: i16) -> Result<Priority, Error> {
+    match priority {
+        0 => Ok(Priority::Low),
+        1 => Ok(Priority::Medium),
+        2 => Ok(Priority::High),
+        _ => Err(Error::InvalidValue(format!("priority {priority}"))),
+    }
+}
@@ -1,240 +1,854 @@
 use std::{collections::HashMap, ops::Add, sync::Arc, time::Duration};
 
-use crate::datafile::DataFileReader;
+use crate::storage::{self, HostRepository, TodoRepository};
 use chrono::{DateTime, NaiveTime, TimeDelta, TimeZone, Utc};
 use chrono_tz::{Europe::Berlin, Tz};
-use ratlib::todo::{Id, IdGenerator, Priority, Requirement, Status, Todo};
+use ratlib::{
+    ownership::Ownership,
+    todo::{
+        archive::{ArchivedTodo, Page},
+        filter::Filter,
+        graph::Graph,
+        recurrence::Recurrence,
+        report::Report,
+        rules::{Blocker, Evaluator},
+        urgency::{is_at_risk, ScoredTodo, Weights},
+        Id, Priority, Requirement, Status, Todo,
+    },
+    TodoEdit,
+};
 use thiserror::Error;
 
+// How many changes can be undone, older ones are forgotten
+const UNDO_LOG_LENGTH: usize = 100;
+
+// What a todo looked like before a change
+enum Snapshot {
+    Saved(Box<Todo>),
+    // The change created the todo
+    Missing,
+}
+
+// A change to the todos, along with everything needed to revert it
+struct Change {
+    // Everyone can only undo their own changes
+    user: String,
+    description: String,
+    before: Vec<(Id, Snapshot)>,
+    // What the change left behind, as read back from the repository. Archived todos are `None`,
+    // like todos that don't exist.
+    after: Vec<(Id, Option<Todo>)>,
+}
+
+impl Change {
+    fn new(user: &str, description: String) -> Self {
+        Self {
+            user: user.to_string(),
+            description,
+            before: vec![],
+            after: vec![],
+        }
+    }
+
+    fn saved(&mut self, todo: &Todo) {
+        self.before
+            .push((todo.id(), Snapshot::Saved(Box::new(todo.clone()))));
+    }
+
+    fn created(&mut self, id: Id) {
+        self.before.push((id, Snapshot::Missing));
+    }
+}
+
+/// What undoing a change did
+pub struct Undone {
+    pub description: String,
+    /// Of the todos the change touched
+    pub ownerships: Vec<Ownership>,
+}
+
 pub struct Store {
-    datafile_reader: Arc<dyn DataFileReader + Send + Sync>,
+    repository: Arc<dyn TodoRepository + Send + Sync>,
+    hosts: Arc<dyn HostRepository + Send + Sync>,
+    urgency_weights: Weights,
+    // Only kept in memory, restarting ras forgets what can be undone. `POST /undo` says so too.
+    undo_log: Vec<Change>,
 }
 
 #[derive(Debug, Error)]
-pub enum Error {}
+pub enum Error {
+    #[error("Todo {0} does not exist")]
+    NotFound(Id),
+    #[error("Todo {0} can't require itself")]
+    SelfDependency(Id),
+    #[error("The required todo {0} does not exist")]
+    UnknownRequirement(Id),
+    #[error("Todo {0} can't require todo {1}, which already depends on it")]
+    RequirementCycle(Id, Id),
+    #[error("Todo {0} has no checklist item {1}")]
+    UnknownChecklistItem(Id, usize),
+    #[error("Todo {0} is still required by todo {1}, which isn't done")]
+    StillRequired(Id, Id),
+    #[error("There is nothing to undo")]
+    NothingToUndo,
+    #[error("Can't undo \"{0}\", todo {1} has changed since")]
+    ChangedSinceUndoable(String, Id),
+    #[error("Can't undo \"{0}\", todo {1} is required by todo {2} now")]
+    RequiredSinceUndoable(String, Id, Id),
+    #[error("Storage: {0}")]
+    Storage(#[from] storage::Error),
+}
 
 impl Store {
-    pub fn new(datafile_reader: Arc<dyn DataFileReader + Send + Sync>) -> Self {
-        Self { datafile_reader }
+    pub fn new(
+        repository: Arc<dyn TodoRepository + Send + Sync>,
+        hosts: Arc<dyn HostRepository + Send + Sync>,
+    ) -> Self {
+        Self {
+            repository,
+            hosts,
+            urgency_weights: Weights::default(),
+            undo_log: vec![],
+        }
     }
 
-    pub fn create(
+    pub fn set_urgency_weights(&mut self, weights: Weights) {
+        self.urgency_weights = weights;
+    }
+
+    /// Pairs the todos with their current urgency
+    pub fn score(&self, todos: Vec<Todo>) -> Vec<ScoredTodo> {
+        let now = Utc::now();
+
+        todos
+            .into_iter()
+            .map(|todo| ScoredTodo {
+                urgency: self.urgency_weights.urgency(&todo, now),
+                todo,
+            })
+            .collect()
+    }
+
+    /// New todos are private to `user`, until they get shared with an edit
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create(
         &mut self,
+        user: &str,
         title: String,
         priority: Priority,
         estimate: Duration,
         requirements: Vec<Requirement>,
         deadline: Option<DateTime<Tz>>,
-    ) -> Id {
-        let mut datafile = self.datafile_reader.read();
-        let mut id_generator =
-            IdGenerator::new(datafile.todos.values().map(|x| x.id().0).max().unwrap_or(0));
+        recurrence: Option<Recurrence>,
+    ) -> Result<Id, Error> {
+        let id = self.repository.next_id().await?;
 
-        let id = id_generator.next();
+        self.validate_requirements(id, user, &requirements).await?;
 
-        let new_todo = Todo::new(id, title, priority, requirements, estimate, deadline);
-        datafile.todos.insert(id, new_todo);
+        let mut new_todo = Todo::new(id, title, priority, requirements, estimate, deadline);
+        new_todo.set_recurrence(recurrence);
+        new_todo.set_created_at(Utc::now());
+        new_todo.set_ownership(Ownership::new(user, false));
 
-        self.datafile_reader.save(datafile);
+        self.repository.save(&new_todo).await?;
 
-        id
+        let mut change = Change::new(user, format!("create #{id}"));
+        change.created(id);
+        self.record(change).await?;
+
+        Ok(id)
     }
 
-    // TODO: This should really be its own struct...
-    fn evaluate_requirements(
-        all_todos: &HashMap<Id, Todo>,
-        requirements: &[Requirement],
-        as_of: DateTime<Utc>,
-    ) -> bool {
-        for requirement in requirements {
-            match requirement {
-                Requirement::TodoDone(id) => {
-                    if !all_todos
-                        .get(id)
-                        .is_some_and(|x| x.status() == Status::Done)
-                    {
-                        return false;
-                    }
-                }
-                Requirement::AfterDate(when) => {
-                    if as_of <= *when {
-                        return false;
-                    }
+    // Remembers a change that has been stored, along with what it left behind
+    async fn record(&mut self, mut change: Change) -> Result<(), Error> {
+        for (id, _) in &change.before {
+            change
+                .after
+                .push((*id, self.repository.find_by_id(*id).await?));
+        }
+
+        self.undo_log.push(change);
+
+        if self.undo_log.len() > UNDO_LOG_LENGTH {
+            self.undo_log.remove(0);
+        }
+
+        Ok(())
+    }
+
+    /// Reverts the most recent change `user` made, returns what it was. Refuses to if the todos
+    /// have been changed since, or others have come to require a todo the change created, as
+    /// reverting it would silently throw that work away.
+    pub async fn undo(&mut self, user: &str) -> Result<Undone, Error> {
+        let index = self
+            .undo_log
+            .iter()
+            .rposition(|x| x.user == user)
+            .ok_or(Error::NothingToUndo)?;
+        let change = &self.undo_log[index];
+
+        for (id, after) in &change.after {
+            if self.repository.find_by_id(*id).await? != *after {
+                return Err(Error::ChangedSinceUndoable(change.description.clone(), *id));
+            }
+        }
+
+        for (id, snapshot) in &change.before {
+            if matches!(snapshot, Snapshot::Missing) {
+                let dependents = self
+                    .repository
+                    .find(&Filter {
+                        requires: Some(*id),
+                        ..Default::default()
+                    })
+                    .await?;
+
+                if let Some(dependent) = dependents.iter().map(Todo::id).min_by_key(|x| x.0) {
+                    return Err(Error::RequiredSinceUndoable(
+                        change.description.clone(),
+                        *id,
+                        dependent,
+                    ));
                 }
             }
         }
 
-        true
+        let change = self.undo_log.remove(index);
+        // Whoever could see the todos before or after the change, it might have been shared since
+        let mut ownerships: Vec<_> = change
+            .after
+            .iter()
+            .filter_map(|(_, todo)| Some(todo.as_ref()?.ownership().clone()))
+            .collect();
+
+        for (id, snapshot) in change.before.into_iter().rev() {
+            // Takes the todo out of the archive too, if the change put it there
+            self.repository.remove(id).await?;
+
+            if let Snapshot::Saved(todo) = snapshot {
+                self.repository.save(&todo).await?;
+                ownerships.push(todo.ownership().clone());
+            }
+        }
+
+        Ok(Undone {
+            description: change.description,
+            ownerships,
+        })
+    }
+
+    pub async fn archive(&mut self, id: Id, user: &str) -> Result<(), Error> {
+        self.move_to_archive(id, user, false).await
     }
 
-    pub fn find_ready_to_do(&self) -> Vec<Todo> {
-        let datafile = self.datafile_reader.read();
-        let mut todos_to_consider = datafile
+    /// Deleted todos are kept in the archive, so this can be undone
+    pub async fn delete(&mut self, id: Id, user: &str) -> Result<(), Error> {
+        self.move_to_archive(id, user, true).await
+    }
+
+    // The todos requiring the archived one would never be ready again, as it's gone. If it's done,
+    // their requirements on it are met for good, so they are dropped. Otherwise it can't be
+    // archived until they are done.
+    async fn move_to_archive(&mut self, id: Id, user: &str, deleted: bool) -> Result<(), Error> {
+        let todo = self.find_visible(id, user).await?;
+
+        let mut dependents = self
+            .repository
+            .find(&Filter {
+                requires: Some(id),
+                ..Default::default()
+            })
+            .await?;
+        dependents.sort_by_key(|x| x.id().0);
+
+        if todo.status() != Status::Done {
+            if let Some(dependent) = dependents.iter().find(|x| x.status() != Status::Done) {
+                return Err(Error::StillRequired(id, dependent.id()));
+            }
+        }
+
+        let mut change = Change::new(
+            user,
+            format!("{} #{id}", if deleted { "delete" } else { "archive" }),
+        );
+        change.saved(&todo);
+
+        for mut dependent in dependents {
+            change.saved(&dependent);
+            dependent.assume_done(id);
+            self.repository.save(&dependent).await?;
+        }
+
+        self.repository
+            .archive(&ArchivedTodo {
+                todo,
+                archived_at: Utc::now(),
+                deleted,
+            })
+            .await?;
+        self.record(change).await
+    }
+
+    /// The part of the archive `user` can see
+    pub async fn find_archived(
+        &self,
+        user: &str,
+        include_deleted: bool,
+        offset: usize,
+        limit: usize,
+    ) -> Result<Page, Error> {
+        // Which todos are visible isn't known to the repository, so the pages are cut here. The
+        // limit has to fit into the i64 Postgres takes.
+        let todos: Vec<_> = self
+            .repository
+            .find_archived(include_deleted, 0, i64::MAX as usize)
+            .await?
             .todos
+            .into_iter()
+            .filter(|x| x.todo.ownership().is_visible_to(user))
+            .collect();
+
+        Ok(Page {
+            total: todos.len(),
+            todos: todos.into_iter().skip(offset).take(limit).collect(),
+            offset,
+        })
+    }
+
+    pub async fn edit(&mut self, id: Id, user: &str, edit: TodoEdit) -> Result<(), Error> {
+        let mut todo = self.find_visible(id, user).await?;
+
+        self.validate_requirements(id, user, &edit.add_requirements)
+            .await?;
+
+        let mut change = Change::new(user, format!("edit #{id}"));
+        change.saved(&todo);
+
+        if let Some(title) = edit.set_title {
+            todo.set_title(title);
+        }
+
+        if let Some(estimate) = edit.set_estimate {
+            todo.set_estimate(estimate);
+        }
+
+        for requirement in edit.add_requirements {
+            todo.add_requirement(requirement);
+        }
+
+        if let Some(priority) = edit.set_priority {
+            todo.set_priority(priority);
+        }
+
+        for tag in &edit.add_tags {
+            todo.add_tag(tag);
+        }
+
+        for tag in &edit.remove_tags {
+            todo.remove_tag(tag);
+        }
+
+        if let Some(project) = edit.set_project {
+            todo.set_project(Some(project));
+        }
+
+        if let Some(notes) = edit.set_notes {
+            todo.set_notes(notes);
+        }
+
+        if let Some(completes_todo) = edit.set_completes_with_checklist {
+            todo.checklist_mut().set_completes_todo(completes_todo);
+        }
+
+        if let Some(shared) = edit.set_shared {
+            let mut ownership = todo.ownership().clone();
+            ownership.set_shared(shared);
+            todo.set_ownership(ownership);
+        }
+
+        self.save_checklist_change(todo, change).await
+    }
+
+    pub async fn add_checklist_item(
+        &mut self,
+        id: Id,
+        user: &str,
+        title: String,
+    ) -> Result<(), Error> {
+        let mut todo = self.find_visible(id, user).await?;
+
+        let mut change = Change::new(user, format!("add a checklist item to #{id}"));
+        change.saved(&todo);
+
+        todo.checklist_mut().add(title);
+        self.save(&todo, change).await
+    }
+
+    pub async fn toggle_checklist_item(
+        &mut self,
+        id: Id,
+        user: &str,
+        index: usize,
+    ) -> Result<(), Error> {
+        let mut todo = self.find_visible(id, user).await?;
+
+        let mut change = Change::new(user, format!("toggle checklist item {index} of #{id}"));
+        change.saved(&todo);
+
+        if !todo.checklist_mut().toggle(index) {
+            return Err(Error::UnknownChecklistItem(id, index));
+        }
+
+        self.save_checklist_change(todo, change).await
+    }
+
+    pub async fn remove_checklist_item(
+        &mut self,
+        id: Id,
+        user: &str,
+        index: usize,
+    ) -> Result<(), Error> {
+        let mut todo = self.find_visible(id, user).await?;
+
+        let mut change = Change::new(user, format!("remove checklist item {index} of #{id}"));
+        change.saved(&todo);
+
+        if !todo.checklist_mut().remove(index) {
+            return Err(Error::UnknownChecklistItem(id, index));
+        }
+
+        self.save_checklist_change(todo, change).await
+    }
+
+    // Saves a todo whose checklist might have just been completed, and moves it to done if it
+    // asked for that. Going through `transition` makes sure recurring todos still recur.
+    async fn save_checklist_change(
+        &mut self,
+        mut todo: Todo,
+        mut change: Change,
+    ) -> Result<(), Error> {
+        if todo.status() != Status::Done
+            && todo.checklist().completes_todo()
+            && todo.checklist().is_complete()
+        {
+            if let Some(next_id) = self.transition(&mut todo, Status::Done, Utc::now()).await? {
+                change.created(next_id);
+            }
+        }
+
+        self.save(&todo, change).await
+    }
+
+    // Makes sure that the todo with the given id can require all of `requirements` - they have
+    // to exist and be visible to `user`, and must not (even indirectly) require the todo
+    // themselves.
+    async fn validate_requirements(
+        &self,
+        id: Id,
+        user: &str,
+        requirements: &[Requirement],
+    ) -> Result<(), Error> {
+        let requirements: Vec<_> = requirements.iter().flat_map(Requirement::flatten).collect();
+
+        if !requirements
+            .iter()
+            .any(|x| matches!(x, Requirement::TodoDone(_)))
+        {
+            return Ok(());
+        }
+
+        let todos = self.repository.find_all().await?;
+        let graph = Graph::new(&todos);
+
+        for requirement in requirements {
+            let Requirement::TodoDone(required) = requirement else {
+                continue;
+            };
+
+            if *required == id {
+                return Err(Error::SelfDependency(id));
+            }
+
+            if !todos
+                .iter()
+                .any(|x| x.id() == *required && x.ownership().is_visible_to(user))
+            {
+                return Err(Error::UnknownRequirement(*required));
+            }
+
+            if graph.requires(*required, id) {
+                return Err(Error::RequirementCycle(id, *required));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub async fn move_to_status(
+        &mut self,
+        id: Id,
+        user: &str,
+        status: Status,
+    ) -> Result<(), Error> {
+        self.move_to_status_as_of(id, user, status, Utc::now())
+            .await
+    }
+
+    async fn move_to_status_as_of(
+        &mut self,
+        id: Id,
+        user: &str,
+        status: Status,
+        as_of: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        let mut todo = self.find_visible(id, user).await?;
+
+        let mut change = Change::new(user, format!("move #{id} to {status:?}"));
+        change.saved(&todo);
+
+        if let Some(next_id) = self.transition(&mut todo, status, as_of).await? {
+            change.created(next_id);
+        }
+
+        self.save(&todo, change).await
+    }
+
+    // Changes the status of the todo without saving it. Returns the id of the next instance, if
+    // this created one.
+    async fn transition(
+        &mut self,
+        todo: &mut Todo,
+        status: Status,
+        as_of: DateTime<Utc>,
+    ) -> Result<Option<Id>, Error> {
+        todo.transition_to(status, as_of);
+
+        if status == Status::Done && todo.next_instance().is_none() {
+            if let Some(next_after) = todo.recurrence().and_then(|x| x.next_after(as_of)) {
+                let next_id = self.repository.next_id().await?;
+
+                // Where this instance started, so the deadline keeps its distance from it
+                let started_at = todo
+                    .requirements()
+                    .iter()
+                    .filter_map(|x| match x {
+                        Requirement::AfterDate(date) => Some(*date),
+                        _ => None,
+                    })
+                    .max()
+                    .or(todo.created_at())
+                    .unwrap_or(as_of);
+                let deadline = todo
+                    .deadline()
+                    .and_then(|x| shift_deadline(x, started_at, next_after.to_utc()));
+
+                let mut requirements: Vec<_> = todo
+                    .requirements()
+                    .iter()
+                    .filter(|x| !matches!(x, Requirement::AfterDate(_)))
+                    .cloned()
+                    .collect();
+                requirements.push(Requirement::AfterDate(next_after.to_utc()));
+
+                let mut next = Todo::new(
+                    next_id,
+                    todo.title().to_string(),
+                    todo.priority(),
+                    requirements,
+                    todo.estimate(),
+                    deadline,
+                );
+                next.set_recurrence(todo.recurrence().cloned());
+                next.set_created_at(as_of);
+                next.set_ownership(todo.ownership().clone());
+
+                self.repository.save(&next).await?;
+                todo.set_next_instance(next_id);
+
+                return Ok(Some(next_id));
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn find_all(&self) -> Result<HashMap<Id, Todo>, Error> {
+        Ok(self
+            .repository
+            .find_all()
+            .await?
+            .into_iter()
+            .map(|x| (x.id(), x))
+            .collect())
+    }
+
+    /// The todos that can be worked on, most urgent first
+    pub async fn find_ready_to_do(&self) -> Result<Vec<Todo>, Error> {
+        let todos = self.find_all().await?;
+        let hosts = self.hosts.find_last_seen().await?;
+        let now = Utc::now();
+        let evaluator = Evaluator::new(now, &todos, &hosts);
+
+        let mut todos_to_consider = todos
             .values()
             .filter(|v| v.status() == Status::Todo)
-            .filter(|v| Self::evaluate_requirements(&datafile.todos, v.requirements(), Utc::now()))
-            .cloned()
+            .filter(|v| evaluator.is_met(v.requirements()))
+            .map(|v| (self.urgency_weights.urgency(v, now), v))
             .collect::<Vec<_>>();
 
-        todos_to_consider.sort_by_key(|b| std::cmp::Reverse(b.priority()));
+        todos_to_consider.sort_by(|(a_urgency, a), (b_urgency, b)| {
+            b_urgency.total_cmp(a_urgency).then(a.id().0.cmp(&b.id().0))
+        });
 
-        todos_to_consider
-    }
-
-    pub fn find_becoming_valid_on(&self, day: chrono::prelude::NaiveDate) -> Vec<Todo> {
-        let datafile = self.datafile_reader.read();
-        let mut todos_to_consider =
-            datafile
-                .todos
-                .values()
-                .filter(|v| v.status() == Status::Todo)
-                .filter(|v| {
-                    Self::evaluate_requirements(
-                        &datafile.todos,
-                        v.requirements(),
-                        Berlin
-                            .from_utc_datetime(&day.add(TimeDelta::try_days(1).unwrap()).and_time(
-                                NaiveTime::from_num_seconds_from_midnight_opt(0, 0).unwrap(),
-                            ))
-                            .to_utc(),
-                    )
-                })
-                .filter(|v| {
-                    let mut should_display = false;
-
-                    for req in v.requirements() {
-                        match req {
-                            Requirement::TodoDone(_) => {}
-                            Requirement::AfterDate(d) => {
-                                if d.to_utc().date_naive() == day {
-                                    should_display = true;
-                                }
-                            }
-                        }
-                    }
+        Ok(todos_to_consider
+            .into_iter()
+            .map(|(_, todo)| todo.clone())
+            .collect())
+    }
 
-                    should_display
-                })
-                .cloned()
-                .collect::<Vec<_>>();
+    pub async fn find_becoming_valid_on(
+        &self,
+        day: chrono::prelude::NaiveDate,
+    ) -> Result<Vec<Todo>, Error> {
+        let todos = self.find_all().await?;
+        let hosts = self.hosts.find_last_seen().await?;
+        let evaluator = Evaluator::new(
+            Berlin
+                .from_utc_datetime(
+                    &day.add(TimeDelta::try_days(1).unwrap())
+                        .and_time(NaiveTime::from_num_seconds_from_midnight_opt(0, 0).unwrap()),
+                )
+                .to_utc(),
+            &todos,
+            &hosts,
+        );
+
+        let mut todos_to_consider = todos
+            .values()
+            .filter(|v| v.status() == Status::Todo)
+            .filter(|v| evaluator.is_met(v.requirements()))
+            .filter(|v| {
+                v.requirements()
+                    .iter()
+                    .flat_map(Requirement::flatten)
+                    .any(|x| matches!(x, Requirement::AfterDate(d) if d.date_naive() == day))
+            })
+            .cloned()
+            .collect::<Vec<_>>();
 
         todos_to_consider.sort_by_key(|b| std::cmp::Reverse(b.priority()));
 
-        todos_to_consider
+        Ok(todos_to_consider)
     }
 
-    pub fn find_doing(&self) -> Vec<Todo> {
-        let datafile = self.datafile_reader.read();
+    pub async fn find(&self, filter: &Filter) -> Result<Vec<Todo>, Error> {
+        let mut todos = self.repository.find(filter).await?;
 
-        datafile
-            .todos
-            .into_values()
-            .filter(|x| x.status() == Status::Doing)
-            .collect()
+        todos.sort_by_key(|x| (std::cmp::Reverse(x.priority()), x.id().0));
+
+        Ok(todos)
     }
 
-    pub fn find_around_deadline(&self) -> Vec<Todo> {
-        let datafile = self.datafile_reader.read();
+    /// The todos that might not get done before their deadline, see [`is_at_risk`], the closest
+    /// deadline first
+    pub async fn find_at_risk(&self) -> Result<Vec<Todo>, Error> {
+        let now = Utc::now();
 
-        datafile
-            .todos
-            .into_values()
-            .filter(|x| x.status() != Status::Done)
-            .filter(|x| match x.deadline() {
-                Some(deadline) => {
-                    deadline.signed_duration_since(Utc::now()).abs().num_days() <= 1
-                        || deadline > Utc::now()
-                }
-                None => false,
+        let mut todos: Vec<_> = self
+            .repository
+            .find(&Filter {
+                has_deadline: Some(true),
+                ..Default::default()
             })
-            .collect()
+            .await?
+            .into_iter()
+            .filter(|x| is_at_risk(x, now))
+            .collect();
+
+        todos.sort_by_key(|x| (x.deadline(), x.id().0));
+
+        Ok(todos)
     }
 
-    pub fn find_by_id(&self, id: Id) -> Option<Todo> {
-        let datafile = self.datafile_reader.read();
+    /// Why the todo isn't ready to be done yet, empty if it is
+    pub async fn find_blockers(&self, id: Id, user: &str) -> Result<Vec<Blocker>, Error> {
+        let todos = self.find_all().await?;
+        let todo = todos
+            .get(&id)
+            .filter(|x| x.ownership().is_visible_to(user))
+            .ok_or(Error::NotFound(id))?;
+        let hosts = self.hosts.find_last_seen().await?;
+
+        Ok(Evaluator::new(Utc::now(), &todos, &hosts).blockers(todo.requirements()))
+    }
 
-        datafile.todos.get(&id).cloned()
+    pub async fn find_report(
+        &self,
+        user: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Report, Error> {
+        Ok(Report::new(&self.find_visible_to(user).await?, from, to))
     }
 
-    pub fn save(&mut self, todo: Todo) {
-        let mut datafile = self.datafile_reader.read();
+    pub async fn find_graph(&self, user: &str) -> Result<Graph, Error> {
+        Ok(Graph::new(&self.find_visible_to(user).await?))
+    }
 
-        datafile.todos.insert(todo.id(), todo);
+    pub async fn find_by_id(&self, id: Id) -> Result<Option<Todo>, Error> {
+        Ok(self.repository.find_by_id(id).await?)
+    }
+
+    // Todos that aren't visible to the user are treated as if they didn't exist
+    async fn find_visible(&self, id: Id, user: &str) -> Result<Todo, Error> {
+        self.repository
+            .find_by_id(id)
+            .await?
+            .filter(|x| x.ownership().is_visible_to(user))
+            .ok_or(Error::NotFound(id))
+    }
 
-        self.datafile_reader.save(datafile);
+    async fn find_visible_to(&self, user: &str) -> Result<Vec<Todo>, Error> {
+        Ok(self.repository.find_visible_to(user).await?)
     }
+
+    // Saves the todo, and remembers how to undo the change that was made to it
+    async fn save(&mut self, todo: &Todo, change: Change) -> Result<(), Error> {
+        self.repository.save(todo).await?;
+        self.record(change).await
+    }
+}
+
+// Moves the deadline by as much as the instance moved. The difference is taken in the deadline's
+// timezone, so a deadline at 18:00 stays at 18:00 across DST changes.
+fn shift_deadline(
+    deadline: DateTime<Tz>,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Option<DateTime<Tz>> {
+    let timezone = deadline.timezone();
+    let shift =
+        to.with_timezone(&timezone).naive_local() - from.with_timezone(&timezone).naive_local();
+
+    timezone
+        .from_local_datetime(&(deadline.naive_local() + shift))
+        .earliest()
 }
 
 #[cfg(test)]
 mod tests {
     use std::{
+        collections::HashMap,
         sync::{Arc, Mutex},
         time::Duration,
     };
 
-    use chrono::{NaiveDate, TimeZone, Utc};
+    use async_trait::async_trait;
+    use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, TimeZone, Utc};
+    use chrono_tz::Europe::Berlin;
     use ratlib::{
         calendar::event::Event,
-        todo::{Id, Priority, Requirement, Todo},
+        ownership::Ownership,
+        todo::{
+            archive::ArchivedTodo,
+            filter::Filter,
+            recurrence::{Recurrence, Rule},
+            rules::Blocker,
+            Id, Priority, Requirement, Status, Todo,
+        },
+        TodoEdit,
     };
 
     use crate::{
         datafile::{DataFile, DataFileReader},
-        todo::store::Store,
+        storage::{self, datafile::DataFileRepository, HostRepository},
+        todo::store::{Change, Error, Store},
     };
 
-    struct MockStore(pub Mutex<(Vec<Todo>, Vec<Event>)>);
+    struct MockStore(
+        pub Mutex<(Vec<Todo>, Vec<Event>)>,
+        pub Mutex<HashMap<Id, ArchivedTodo>>,
+    );
+
+    // Only hallewell is online
+    struct MockHosts;
+
+    #[async_trait]
+    impl HostRepository for MockHosts {
+        async fn find_last_seen(&self) -> Result<HashMap<String, DateTime<Utc>>, storage::Error> {
+            Ok(HashMap::from([("hallewell".to_string(), Utc::now())]))
+        }
+    }
+
+    fn create_store(data_file_reader: Arc<MockStore>) -> Store {
+        Store::new(
+            Arc::new(DataFileRepository::new(data_file_reader)),
+            Arc::new(MockHosts),
+        )
+    }
 
     impl DataFileReader for MockStore {
-        fn read(&self) -> crate::datafile::DataFile {
+        fn read(&self) -> Result<crate::datafile::DataFile, crate::datafile::Error> {
             let guard = self.0.lock().unwrap();
             let (todos, events) = (guard.0.clone(), guard.1.clone());
 
-            DataFile {
+            Ok(DataFile {
                 todos: todos.into_iter().map(|x| (x.id(), x)).collect(),
                 events: events.into_iter().map(|x| (x.id(), x)).collect(),
-            }
+                archived_todos: self.1.lock().unwrap().clone(),
+            })
         }
 
-        fn save(&self, data: crate::datafile::DataFile) {
+        fn save(&self, data: crate::datafile::DataFile) -> Result<(), crate::datafile::Error> {
             let mut guard = self.0.lock().unwrap();
 
             *guard = (
                 data.todos.into_values().collect(),
                 data.events.into_values().collect(),
             );
+            *self.1.lock().unwrap() = data.archived_todos;
+
+            Ok(())
         }
     }
 
-    #[test]
-    pub fn can_create() {
-        let data_file_reader = MockStore(Mutex::new((vec![], vec![])));
+    #[tokio::test]
+    pub async fn can_create() {
+        let data_file_reader = MockStore(Mutex::new((vec![], vec![])), Mutex::default());
+
+        let mut store = create_store(Arc::new(data_file_reader));
+        let before = Utc::now();
+        let id = store
+            .create(
+                "ramona",
+                "This is a todo".to_string(),
+                Priority::Low,
+                Duration::from_secs(15),
+                vec![],
+                None,
+                None,
+            )
+            .await
+            .unwrap();
 
-        let mut store = Store::new(Arc::new(data_file_reader));
-        let id = store.create(
+        let created = store.find_by_id(id).await.unwrap().unwrap();
+        let mut expected = Todo::new(
+            id,
             "This is a todo".to_string(),
             Priority::Low,
-            Duration::from_secs(15),
             vec![],
+            Duration::from_secs(15),
             None,
         );
+        expected.set_created_at(created.created_at().unwrap());
+        expected.set_ownership(Ownership::new("ramona", false));
 
-        assert_eq!(
-            Todo::new(
-                id,
-                "This is a todo".to_string(),
-                Priority::Low,
-                vec![],
-                Duration::from_secs(15),
-                None
-            ),
-            store.find_by_id(id).unwrap()
-        );
+        assert_eq!(expected, created);
+        assert!(created.created_at().unwrap() >= before);
     }
 
-    #[test]
-    pub fn can_find_by_id() {
+    #[tokio::test]
+    pub async fn can_find_by_id() {
         let findme = Todo::new(
             Id(2),
             "asdf".to_string(),
@@ -243,28 +857,31 @@ mod tests {
             Duration::from_secs(1024),
             None,
         );
-        let data_file_reader = MockStore(Mutex::new((
-            vec![
-                Todo::new(
-                    Id(1),
-                    "asdf".to_string(),
-                    ratlib::todo::Priority::Medium,
-                    vec![],
-                    Duration::from_secs(1024),
-                    None,
-                ),
-                findme.clone(),
-            ],
-            vec![],
-        )));
+        let data_file_reader = MockStore(
+            Mutex::new((
+                vec![
+                    Todo::new(
+                        Id(1),
+                        "asdf".to_string(),
+                        ratlib::todo::Priority::Medium,
+                        vec![],
+                        Duration::from_secs(1024),
+                        None,
+                    ),
+                    findme.clone(),
+                ],
+                vec![],
+            )),
+            Mutex::default(),
+        );
 
-        let store = Store::new(Arc::new(data_file_reader));
+        let store = create_store(Arc::new(data_file_reader));
 
-        assert_eq!(Some(findme), store.find_by_id(Id(2)));
+        assert_eq!(Some(findme), store.find_by_id(Id(2)).await.unwrap());
     }
 
-    #[test]
-    pub fn test_can_find_ready_to_do() {
+    #[tokio::test]
+    pub async fn test_can_find_ready_to_do() {
         let findme = Todo::new(
             Id(1),
             "aaa".to_string(),
@@ -273,28 +890,82 @@ mod tests {
             Duration::from_secs(120),
             None,
         );
-        let data_file_reader = MockStore(Mutex::new((
+        let data_file_reader = MockStore(
+            Mutex::new((
+                vec![
+                    findme.clone(),
+                    Todo::new(
+                        Id(2),
+                        "basdf".to_string(),
+                        Priority::High,
+                        vec![Requirement::TodoDone(Id(1))],
+                        Duration::from_secs(15),
+                        None,
+                    ),
+                ],
+                vec![],
+            )),
+            Mutex::default(),
+        );
+
+        let store = create_store(Arc::new(data_file_reader));
+
+        assert_eq!(vec![findme], store.find_ready_to_do().await.unwrap());
+    }
+
+    #[tokio::test]
+    pub async fn urgent_todos_come_first() {
+        let in_hours =
+            |hours| Some(Utc::now().with_timezone(&Berlin) + TimeDelta::try_hours(hours).unwrap());
+        let todo = |id, priority, deadline| {
+            Todo::new(
+                Id(id),
+                format!("todo {id}"),
+                priority,
+                vec![],
+                Duration::from_secs(3600),
+                deadline,
+            )
+        };
+        let mut done = todo(4, Priority::Low, in_hours(-2));
+        done.transition_to(Status::Done, Utc::now());
+
+        let data = Mutex::new((
             vec![
-                findme.clone(),
-                Todo::new(
-                    Id(2),
-                    "basdf".to_string(),
-                    Priority::High,
-                    vec![Requirement::TodoDone(Id(1))],
-                    Duration::from_secs(15),
-                    None,
-                ),
+                todo(1, Priority::High, None),
+                todo(2, Priority::Low, in_hours(2)),
+                todo(3, Priority::Medium, in_hours(24 * 10)),
+                done,
+                todo(5, Priority::Low, in_hours(-1)),
             ],
             vec![],
-        )));
+        ));
+        let store = create_store(Arc::new(MockStore(data, Mutex::default())));
 
-        let store = Store::new(Arc::new(data_file_reader));
-
-        assert_eq!(vec![findme], store.find_ready_to_do());
+        assert_eq!(
+            vec![Id(5), Id(2), Id(1), Id(3)],
+            store
+                .find_ready_to_do()
+                .await
+                .unwrap()
+                .iter()
+                .map(Todo::id)
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![Id(5), Id(2)],
+            store
+                .find_at_risk()
+                .await
+                .unwrap()
+                .iter()
+                .map(Todo::id)
+                .collect::<Vec<_>>()
+        );
     }
 
-    #[test]
-    pub fn can_find_becoming_valid_on() {
+    #[tokio::test]
+    pub async fn can_find_becoming_valid_on() {
         let todo = Todo::new(
             Id(1234),
             "aaa".to_string(),
@@ -315,34 +986,91 @@ mod tests {
             Duration::from_secs(12),
             None,
         );
-        todo_done.transition_to(ratlib::todo::Status::Done);
+        todo_done.transition_to(ratlib::todo::Status::Done, Utc::now());
+
+        let data_file_reader = MockStore(
+            Mutex::new((
+                vec![
+                    todo.clone(),
+                    todo_done,
+                    Todo::new(
+                        Id(2),
+                        "basdf".to_string(),
+                        Priority::High,
+                        vec![],
+                        Duration::from_secs(15),
+                        None,
+                    ),
+                ],
+                vec![],
+            )),
+            Mutex::default(),
+        );
+
+        let store = create_store(Arc::new(data_file_reader));
+
+        let becoming_valid = store
+            .find_becoming_valid_on(NaiveDate::from_ymd_opt(2022, 1, 1).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(vec![todo], becoming_valid);
+    }
+
+    #[tokio::test]
+    pub async fn explains_why_todos_are_blocked() {
+        let todo = |id, requirements| {
+            Todo::new(
+                Id(id),
+                format!("todo {id}"),
+                Priority::Medium,
+                requirements,
+                Duration::from_secs(60),
+                None,
+            )
+        };
+        let ready = todo(1, vec![Requirement::HostOnline("hallewell".to_string())]);
 
-        let data_file_reader = MockStore(Mutex::new((
+        let data = Mutex::new((
             vec![
-                todo.clone(),
-                todo_done,
-                Todo::new(
-                    Id(2),
-                    "basdf".to_string(),
-                    Priority::High,
-                    vec![],
-                    Duration::from_secs(15),
-                    None,
+                ready.clone(),
+                todo(
+                    2,
+                    vec![
+                        Requirement::TodoDone(Id(1)),
+                        Requirement::AnyOf(vec![
+                            Requirement::HostOnline("hallewell".to_string()),
+                            Requirement::HostOnline("moonfall".to_string()),
+                        ]),
+                        Requirement::HostOnline("moonfall".to_string()),
+                    ],
                 ),
             ],
             vec![],
-        )));
-
-        let store = Store::new(Arc::new(data_file_reader));
-
-        let becoming_valid =
-            store.find_becoming_valid_on(NaiveDate::from_ymd_opt(2022, 1, 1).unwrap());
+        ));
+        let store = create_store(Arc::new(MockStore(data, Mutex::default())));
 
-        assert_eq!(vec![todo], becoming_valid);
+        assert_eq!(vec![ready], store.find_ready_to_do().await.unwrap());
+        assert_eq!(
+            vec![
+                Blocker::TodoNotDone(Id(1)),
+                Blocker::HostOffline("moonfall".to_string())
+            ],
+            store.find_blockers(Id(2), "ramona").await.unwrap()
+        );
+        assert!(store
+            .find_blockers(Id(1), "ramona")
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            store.find_blockers(Id(3), "ramona").await,
+            Err(Error::NotFound(Id(3)))
+        ));
     }
 
-    #[test]
-    pub fn can_find_doing() {
+    #[tokio::test]
+    pub async fn can_find_doing() {
         let mut todo = Todo::new(
             Id(1234),
             "aaa".to_string(),
@@ -351,32 +1079,41 @@ mod tests {
             Duration::from_secs(12),
             None,
         );
-        todo.transition_to(ratlib::todo::Status::Doing);
+        todo.transition_to(ratlib::todo::Status::Doing, Utc::now());
 
-        let data_file_reader = MockStore(Mutex::new((
-            vec![
-                todo.clone(),
-                Todo::new(
-                    Id(2),
-                    "basdf".to_string(),
-                    Priority::High,
-                    vec![],
-                    Duration::from_secs(15),
-                    None,
-                ),
-            ],
-            vec![],
-        )));
+        let data_file_reader = MockStore(
+            Mutex::new((
+                vec![
+                    todo.clone(),
+                    Todo::new(
+                        Id(2),
+                        "basdf".to_string(),
+                        Priority::High,
+                        vec![],
+                        Duration::from_secs(15),
+                        None,
+                    ),
+                ],
+                vec![],
+            )),
+            Mutex::default(),
+        );
 
-        let store = Store::new(Arc::new(data_file_reader));
+        let store = create_store(Arc::new(data_file_reader));
 
-        let doing = store.find_doing();
+        let doing = store
+            .find(&Filter {
+                status: Some(Status::Doing),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
 
         assert_eq!(vec![todo], doing);
     }
 
-    #[test]
-    pub fn can_save() {
+    #[tokio::test]
+    pub async fn can_save() {
         let todo = Todo::new(
             Id(1234),
             "aaa".to_string(),
@@ -387,12 +1124,650 @@ mod tests {
         );
 
         let data = Mutex::new((vec![todo.clone()], vec![]));
-        let data_file_reader = Arc::new(MockStore(data));
+        let data_file_reader = Arc::new(MockStore(data, Mutex::default()));
+
+        let mut store = create_store(data_file_reader.clone());
+        store
+            .save(&todo, Change::new("ramona", "save #1234".to_string()))
+            .await
+            .unwrap();
+
+        let store = create_store(data_file_reader);
+        assert_eq!(vec![todo], store.find_ready_to_do().await.unwrap());
+    }
+
+    #[tokio::test]
+    pub async fn can_tag_and_annotate() {
+        let todo = Todo::new(
+            Id(1),
+            "Renew the passport".to_string(),
+            Priority::High,
+            vec![],
+            Duration::from_secs(3600),
+            None,
+        );
+
+        let data_file_reader = MockStore(Mutex::new((vec![todo], vec![])), Mutex::default());
+        let mut store = create_store(Arc::new(data_file_reader));
+
+        store
+            .edit(
+                Id(1),
+                "ramona",
+                TodoEdit {
+                    add_tags: vec!["#errands".to_string(), "paperwork".to_string()],
+                    set_project: Some("travel".to_string()),
+                    set_notes: Some("Bring the old one".to_string()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        store
+            .edit(
+                Id(1),
+                "ramona",
+                TodoEdit {
+                    remove_tags: vec!["errands".to_string()],
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        let found = store
+            .find(&Filter {
+                tag: Some("paperwork".to_string()),
+                project: Some("travel".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(1, found.len());
+        assert_eq!(&["paperwork".to_string()], found[0].tags());
+        assert_eq!("Bring the old one", found[0].notes());
+
+        store
+            .edit(
+                Id(1),
+                "ramona",
+                TodoEdit {
+                    set_project: Some(String::new()),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            None,
+            store.find_by_id(Id(1)).await.unwrap().unwrap().project()
+        );
+    }
+
+    #[tokio::test]
+    pub async fn completes_todos_with_their_checklist() {
+        let todo = Todo::new(
+            Id(1),
+            "Paint the hallway".to_string(),
+            Priority::Medium,
+            vec![],
+            Duration::from_secs(3 * 3600),
+            None,
+        );
+
+        let data_file_reader = MockStore(Mutex::new((vec![todo], vec![])), Mutex::default());
+        let mut store = create_store(Arc::new(data_file_reader));
+
+        for item in ["Buy paint", "Tape the edges", "Paint"] {
+            store
+                .add_checklist_item(Id(1), "ramona", item.to_string())
+                .await
+                .unwrap();
+        }
+        store
+            .toggle_checklist_item(Id(1), "ramona", 0)
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.toggle_checklist_item(Id(1), "ramona", 3).await,
+            Err(Error::UnknownChecklistItem(Id(1), 3))
+        ));
+
+        store
+            .edit(
+                Id(1),
+                "ramona",
+                TodoEdit {
+                    set_completes_with_checklist: Some(true),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        store
+            .remove_checklist_item(Id(1), "ramona", 1)
+            .await
+            .unwrap();
+
+        let todo = store.find_by_id(Id(1)).await.unwrap().unwrap();
+        assert_eq!((1, 2), todo.checklist().progress());
+        assert_eq!(Status::Todo, todo.status());
+
+        store
+            .toggle_checklist_item(Id(1), "ramona", 1)
+            .await
+            .unwrap();
+
+        let todo = store.find_by_id(Id(1)).await.unwrap().unwrap();
+        assert_eq!(Status::Done, todo.status());
+    }
+
+    #[tokio::test]
+    pub async fn archives_and_undoes() {
+        let todo = |id, requirements| {
+            Todo::new(
+                Id(id),
+                format!("todo {id}"),
+                Priority::Medium,
+                requirements,
+                Duration::from_secs(60),
+                None,
+            )
+        };
+        let mut done = todo(1, vec![]);
+        done.transition_to(Status::Done, Utc::now());
+
+        let data = Mutex::new((
+            vec![
+                done,
+                todo(2, vec![Requirement::TodoDone(Id(1))]),
+                todo(3, vec![]),
+                todo(4, vec![Requirement::TodoDone(Id(3))]),
+            ],
+            vec![],
+        ));
+        let mut store = create_store(Arc::new(MockStore(data, Mutex::default())));
+
+        assert!(matches!(
+            store.delete(Id(3), "ramona").await,
+            Err(Error::StillRequired(Id(3), Id(4)))
+        ));
+
+        store.archive(Id(1), "ramona").await.unwrap();
+        store.delete(Id(4), "ramona").await.unwrap();
+        store
+            .move_to_status(Id(2), "ramona", Status::Doing)
+            .await
+            .unwrap();
+
+        assert_eq!(None, store.find_by_id(Id(1)).await.unwrap());
+        assert!(store
+            .find_by_id(Id(2))
+            .await
+            .unwrap()
+            .unwrap()
+            .requirements()
+            .is_empty());
+        assert_eq!(
+            1,
+            store
+                .find_archived("ramona", false, 0, 10)
+                .await
+                .unwrap()
+                .total
+        );
+
+        let page = store.find_archived("ramona", true, 1, 10).await.unwrap();
+        assert_eq!(2, page.total);
+        assert_eq!(1, page.todos.len());
+
+        assert_eq!(
+            "move #2 to Doing",
+            store.undo("ramona").await.unwrap().description
+        );
+        assert_eq!("delete #4", store.undo("ramona").await.unwrap().description);
+        assert_eq!(
+            "archive #1",
+            store.undo("ramona").await.unwrap().description
+        );
+        assert!(matches!(
+            store.undo("ramona").await,
+            Err(Error::NothingToUndo)
+        ));
+
+        let todo_2 = store.find_by_id(Id(2)).await.unwrap().unwrap();
+        assert_eq!(Status::Todo, todo_2.status());
+        assert_eq!(&[Requirement::TodoDone(Id(1))], todo_2.requirements());
+        assert!(store.find_by_id(Id(4)).await.unwrap().is_some());
+        assert_eq!(
+            0,
+            store
+                .find_archived("ramona", true, 0, 10)
+                .await
+                .unwrap()
+                .total
+        );
+    }
+
+    #[tokio::test]
+    pub async fn rejects_invalid_requirements() {
+        let todo = |id, requirements| {
+            Todo::new(
+                Id(id),
+                format!("todo {id}"),
+                Priority::Medium,
+                requirements,
+                Duration::from_secs(60),
+                None,
+            )
+        };
+
+        let data = Mutex::new((
+            vec![
+                todo(1, vec![]),
+                todo(2, vec![Requirement::TodoDone(Id(1))]),
+                todo(3, vec![Requirement::TodoDone(Id(2))]),
+            ],
+            vec![],
+        ));
+        let mut store = create_store(Arc::new(MockStore(data, Mutex::default())));
+
+        assert!(matches!(
+            store
+                .edit(
+                    Id(1),
+                    "ramona",
+                    TodoEdit {
+                        add_requirements: vec![Requirement::TodoDone(Id(1))],
+                        ..Default::default()
+                    },
+                )
+                .await,
+            Err(Error::SelfDependency(Id(1)))
+        ));
+        assert!(matches!(
+            store
+                .edit(
+                    Id(1),
+                    "ramona",
+                    TodoEdit {
+                        add_requirements: vec![Requirement::TodoDone(Id(4))],
+                        ..Default::default()
+                    },
+                )
+                .await,
+            Err(Error::UnknownRequirement(Id(4)))
+        ));
+        assert!(matches!(
+            store
+                .edit(
+                    Id(1),
+                    "ramona",
+                    TodoEdit {
+                        add_requirements: vec![Requirement::TodoDone(Id(3))],
+                        ..Default::default()
+                    },
+                )
+                .await,
+            Err(Error::RequirementCycle(Id(1), Id(3)))
+        ));
+        assert!(matches!(
+            store
+                .edit(
+                    Id(5),
+                    "ramona",
+                    TodoEdit {
+                        set_title: Some("nope".to_string()),
+                        ..Default::default()
+                    },
+                )
+                .await,
+            Err(Error::NotFound(Id(5)))
+        ));
+
+        store
+            .edit(
+                Id(3),
+                "ramona",
+                TodoEdit {
+                    add_requirements: vec![Requirement::TodoDone(Id(1))],
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            &[Requirement::TodoDone(Id(2)), Requirement::TodoDone(Id(1))],
+            store
+                .find_by_id(Id(3))
+                .await
+                .unwrap()
+                .unwrap()
+                .requirements()
+        );
+    }
+
+    #[tokio::test]
+    pub async fn done_recurring_todo_spawns_next_instance() {
+        let mut todo = Todo::new(
+            Id(1),
+            "review backups".to_string(),
+            Priority::High,
+            vec![
+                Requirement::TodoDone(Id(5)),
+                Requirement::AfterDate(Utc.with_ymd_and_hms(2024, 5, 1, 7, 0, 0).unwrap()),
+            ],
+            Duration::from_secs(1800),
+            Some(Berlin.with_ymd_and_hms(2024, 5, 2, 18, 0, 0).unwrap()),
+        );
+        todo.set_recurrence(Some(Recurrence::new(
+            Rule::Daily {
+                every: 7,
+                at: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            },
+            Berlin,
+        )));
+
+        let data_file_reader = Arc::new(MockStore(
+            Mutex::new((vec![todo], vec![])),
+            Mutex::default(),
+        ));
+        let mut store = create_store(data_file_reader);
+
+        let done_at = Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap();
+        store
+            .move_to_status_as_of(Id(1), "ramona", Status::Done, done_at)
+            .await
+            .unwrap();
+        // Going through done again must not create another copy
+        store
+            .move_to_status_as_of(Id(1), "ramona", Status::Todo, done_at)
+            .await
+            .unwrap();
+        store
+            .move_to_status_as_of(Id(1), "ramona", Status::Done, done_at)
+            .await
+            .unwrap();
+
+        let done = store.find_by_id(Id(1)).await.unwrap().unwrap();
+        assert_eq!(Some(Id(2)), done.next_instance());
+        assert_eq!(None, store.find_by_id(Id(3)).await.unwrap());
+
+        let next = store.find_by_id(Id(2)).await.unwrap().unwrap();
+        assert_eq!(Status::Todo, next.status());
+        assert_eq!(Priority::High, next.priority());
+        assert_eq!(Duration::from_secs(1800), next.estimate());
+        assert_eq!(done.recurrence(), next.recurrence());
+        assert_eq!(
+            Some(Berlin.with_ymd_and_hms(2024, 5, 9, 18, 0, 0).unwrap()),
+            next.deadline()
+        );
+        assert_eq!(
+            vec![
+                Requirement::TodoDone(Id(5)),
+                Requirement::AfterDate(Utc.with_ymd_and_hms(2024, 5, 8, 7, 0, 0).unwrap()),
+            ],
+            next.requirements()
+        );
+    }
+
+    #[tokio::test]
+    pub async fn can_find_by_filter() {
+        let mut done = Todo::new(
+            Id(1),
+            "pay rent".to_string(),
+            Priority::Low,
+            vec![],
+            Duration::from_secs(60),
+            None,
+        );
+        done.transition_to(Status::Done, Utc::now());
+        let high = Todo::new(
+            Id(2),
+            "pay taxes".to_string(),
+            Priority::High,
+            vec![],
+            Duration::from_secs(60),
+            None,
+        );
+        let medium = Todo::new(
+            Id(3),
+            "pay bills".to_string(),
+            Priority::Medium,
+            vec![],
+            Duration::from_secs(60),
+            None,
+        );
+
+        let data_file_reader = MockStore(
+            Mutex::new((vec![done.clone(), medium.clone(), high.clone()], vec![])),
+            Mutex::default(),
+        );
+        let store = create_store(Arc::new(data_file_reader));
+
+        assert_eq!(
+            vec![done],
+            store
+                .find(&Filter {
+                    status: Some(Status::Done),
+                    ..Default::default()
+                })
+                .await
+                .unwrap()
+        );
+        assert_eq!(
+            vec![high, medium],
+            store
+                .find(&Filter {
+                    status: Some(Status::Todo),
+                    title_contains: Some("PAY".to_string()),
+                    ..Default::default()
+                })
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    pub async fn records_time_spent() {
+        let at = |hour| Utc.with_ymd_and_hms(2024, 7, 1, hour, 0, 0).unwrap();
+        let data = Mutex::new((vec![], vec![]));
+        let mut store = create_store(Arc::new(MockStore(data, Mutex::default())));
+
+        let id = store
+            .create(
+                "ramona",
+                "Write the report".to_string(),
+                Priority::High,
+                Duration::from_secs(3600),
+                vec![],
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+
+        store
+            .move_to_status_as_of(id, "ramona", Status::Doing, at(9))
+            .await
+            .unwrap();
+        store
+            .move_to_status_as_of(id, "ramona", Status::Done, at(11))
+            .await
+            .unwrap();
+
+        let todo = store.find_by_id(id).await.unwrap().unwrap();
+        assert_eq!(Some(at(9)), todo.started_at());
+        assert_eq!(Some(at(11)), todo.completed_at());
+
+        let report = store.find_report("ramona", at(0), at(23)).await.unwrap();
+        assert_eq!(Priority::High, report.priorities[0].priority);
+        assert_eq!(1, report.priorities[0].done);
+        assert_eq!(Duration::from_secs(3600), report.priorities[0].estimated);
+        assert_eq!(Duration::from_secs(2 * 3600), report.priorities[0].spent);
+    }
+
+    #[tokio::test]
+    pub async fn refuses_to_undo_creating_a_todo_others_require() {
+        let mut todo = Todo::new(
+            Id(1),
+            "water the plants".to_string(),
+            Priority::Medium,
+            vec![],
+            Duration::from_secs(600),
+            None,
+        );
+        todo.set_recurrence(Some(Recurrence::new(
+            Rule::Daily {
+                every: 1,
+                at: NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+            },
+            Berlin,
+        )));
+
+        let data = Mutex::new((vec![todo], vec![]));
+        let mut store = create_store(Arc::new(MockStore(data, Mutex::default())));
+
+        store
+            .move_to_status(Id(1), "ramona", Status::Done)
+            .await
+            .unwrap();
+        let next = store
+            .find_by_id(Id(1))
+            .await
+            .unwrap()
+            .unwrap()
+            .next_instance()
+            .unwrap();
+        let dependent = store
+            .create(
+                "someone",
+                "buy fertilizer".to_string(),
+                Priority::Low,
+                Duration::from_secs(600),
+                vec![Requirement::TodoDone(next)],
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            store.undo("ramona").await,
+            Err(Error::RequiredSinceUndoable(_, id, by)) if id == next && by == dependent
+        ));
+
+        store.undo("someone").await.unwrap();
+        store.undo("ramona").await.unwrap();
+
+        assert_eq!(None, store.find_by_id(next).await.unwrap());
+        assert_eq!(
+            Status::Todo,
+            store.find_by_id(Id(1)).await.unwrap().unwrap().status()
+        );
+    }
 
-        let mut store = Store::new(data_file_reader.clone());
-        store.save(todo.clone());
+    #[tokio::test]
+    pub async fn keeps_todos_and_undo_per_user() {
+        let data = Mutex::new((vec![], vec![]));
+        let mut store = create_store(Arc::new(MockStore(data, Mutex::default())));
 
-        let store = Store::new(data_file_reader);
-        assert_eq!(vec![todo], store.find_ready_to_do());
+        let mut ids = vec![];
+        for user in ["ramona", "someone"] {
+            ids.push(
+                store
+                    .create(
+                        user,
+                        format!("{user}'s todo"),
+                        Priority::Medium,
+                        Duration::from_secs(60),
+                        vec![],
+                        None,
+                        None,
+                    )
+                    .await
+                    .unwrap(),
+            );
+        }
+        let (ramonas, someones) = (ids[0], ids[1]);
+
+        assert!(matches!(
+            store
+                .move_to_status(ramonas, "someone", Status::Doing)
+                .await,
+            Err(Error::NotFound(_))
+        ));
+        assert!(matches!(
+            store.find_blockers(ramonas, "someone").await,
+            Err(Error::NotFound(_))
+        ));
+        assert!(matches!(
+            store
+                .edit(
+                    someones,
+                    "someone",
+                    TodoEdit {
+                        add_requirements: vec![Requirement::TodoDone(ramonas)],
+                        ..Default::default()
+                    }
+                )
+                .await,
+            Err(Error::UnknownRequirement(_))
+        ));
+
+        store
+            .edit(
+                ramonas,
+                "ramona",
+                TodoEdit {
+                    set_shared: Some(true),
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        store
+            .move_to_status(ramonas, "someone", Status::Doing)
+            .await
+            .unwrap();
+        assert_eq!(
+            vec![ramonas],
+            store
+                .find_graph("ramona")
+                .await
+                .unwrap()
+                .nodes
+                .iter()
+                .map(|x| x.id)
+                .collect::<Vec<_>>()
+        );
+
+        // Someone moved the todo after it was shared, undoing the sharing would lose that
+        assert!(matches!(
+            store.undo("ramona").await,
+            Err(Error::ChangedSinceUndoable(_, id)) if id == ramonas
+        ));
+        let undone = store.undo("someone").await.unwrap();
+        assert_eq!(format!("move #{ramonas} to Doing"), undone.description);
+        // Ramona gets to hear about it, it's her todo
+        assert!(!undone.ownerships.is_empty());
+        assert!(undone
+            .ownerships
+            .iter()
+            .all(|x| *x == Ownership::new("ramona", true)));
+        assert_eq!(
+            format!("edit #{ramonas}"),
+            store.undo("ramona").await.unwrap().description
+        );
+        assert_eq!(
+            format!("create #{someones}"),
+            store.undo("someone").await.unwrap().description
+        );
+        assert!(matches!(
+            store.undo("someone").await,
+            Err(Error::NothingToUndo)
+        ));
     }
 }
@@ -1,18 +1,25 @@
 use std::time::Duration;
 
-use crate::todo::{Priority, Requirement};
+use ratlib::todo::client::Client;
+
+use crate::{
+    cli::or_exit,
+    todo::{recurrence::Recurrence, Priority, Requirement},
+};
 
 pub async fn execute(
-    server_url: &str,
+    client: &Client,
     title: &str,
     priority: Priority,
     estimate: Duration,
     requirements: Vec<Requirement>,
+    recurrence: Option<Recurrence>,
 ) {
-    let client = ratlib::todo::client::Client::new(server_url);
-    let id = client
-        .create(title, priority, estimate, requirements, None)
-        .await;
+    let id = or_exit(
+        client
+            .create(title, priority, estimate, requirements, None, recurrence)
+            .await,
+    );
 
     println!("Inserted a new TODO with title \"{title}\" and ID {id}");
 }
@@ -0,0 +1,54 @@
+use chrono_tz::Europe::Berlin;
+use colored::{Color, Colorize as _};
+use ratlib::todo::client::Client;
+
+use crate::{cli::or_exit, todo::Id};
+
+const PAGE_SIZE: usize = 20;
+
+pub async fn archive(client: &Client, id: Id) {
+    or_exit(client.archive(id).await);
+}
+
+pub async fn delete(client: &Client, id: Id) {
+    or_exit(client.delete(id).await);
+}
+
+pub async fn undo(client: &Client) {
+    let undone = or_exit(client.undo().await);
+
+    println!("Undid {undone}");
+}
+
+/// Pages are numbered from 1
+pub async fn list(client: &Client, page: usize) {
+    let archive = or_exit(
+        client
+            .find_archive(page.saturating_sub(1) * PAGE_SIZE, PAGE_SIZE)
+            .await,
+    );
+
+    for archived in &archive.todos {
+        // todo pull the default TZ from the OS
+        println!(
+            "{:>10} {} {}",
+            archived.todo.id().to_string().color(Color::BrightBlack),
+            archived
+                .archived_at
+                .with_timezone(&Berlin)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+                .color(Color::Blue),
+            archived.todo.title()
+        );
+    }
+
+    println!(
+        "{}",
+        format!(
+            "Page {page} of {}",
+            archive.total.div_ceil(PAGE_SIZE).max(1)
+        )
+        .color(Color::BrightBlack)
+    );
+}
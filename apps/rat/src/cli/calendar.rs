@@ -1,72 +1,129 @@
-use chrono::{Datelike, TimeZone, Utc};
+use chrono::{DateTime, Datelike, Days, NaiveDate, TimeZone, Utc};
 use chrono_tz::Europe::Berlin;
 use colored::{Color, Colorize};
-use ratlib::{calendar::event::Event, todo::Todo, PostEvent};
+use ratlib::{calendar::event::Event, todo::client::Client};
 
-use crate::cli::list::render_todo;
+use crate::cli::{list::render_todo, or_exit};
 
-pub(crate) fn execute(server_url: &str, action: crate::CalendarAction) {
-    let client = reqwest::blocking::Client::new();
+fn render_event(event: &Event, day: NaiveDate) -> String {
+    let start = event.start().with_timezone(&Berlin);
 
-    match action {
-        crate::CalendarAction::Today => {
-            let berlin_now = Berlin.from_utc_datetime(&Utc::now().naive_utc());
-
-            let todos_becoming_valid: Vec<Todo> = client
-                .get(format!(
-                    "{}todos?becoming_ready_on={}",
-                    server_url,
-                    berlin_now.date_naive()
-                ))
-                .send()
-                .unwrap()
-                .json()
-                .unwrap();
-
-            let events_today: Vec<Event> = client
-                .get(format!(
-                    "{}events?date={}",
-                    server_url,
-                    berlin_now.date_naive()
-                ))
-                .send()
-                .unwrap()
-                .json()
-                .unwrap();
-
-            println!(
-                "Today: {} {}",
-                berlin_now.date_naive(),
-                berlin_now.weekday()
-            );
+    // Events that started on an earlier day are still going on
+    let start = if start.date_naive() == day {
+        start.time().to_string()
+    } else {
+        format!("(since {})", start.naive_local())
+    };
 
-            for event in events_today {
-                println!(
-                    "{} ({} min) {}",
-                    event.start().time().to_string().color(Color::Blue),
-                    event.duration().as_secs() / 60,
-                    event.title()
-                );
-            }
+    format!(
+        "{} {} ({} min) {}",
+        event.id().to_string().color(Color::Magenta),
+        start.color(Color::Blue),
+        event.duration().as_secs() / 60,
+        event.title()
+    )
+}
 
-            for todo in todos_becoming_valid {
-                println!("{}", render_todo(&todo));
-            }
+async fn show_agenda(client: &Client, days: u64) {
+    let today = Berlin
+        .from_utc_datetime(&Utc::now().naive_utc())
+        .date_naive();
+    let last_day = today + Days::new(days.saturating_sub(1));
+
+    for day in or_exit(client.find_events(today, last_day, Berlin).await) {
+        println!(
+            "{}",
+            format!("{} {}", day.date, day.date.weekday()).color(Color::Green)
+        );
+
+        for event in &day.events {
+            println!("  {}", render_event(event, day.date));
+        }
+    }
+}
+
+async fn show_today(client: &Client) {
+    let berlin_now = Berlin.from_utc_datetime(&Utc::now().naive_utc());
+    let today = berlin_now.date_naive();
+
+    let todos_becoming_valid = or_exit(client.find_becoming_ready_on(today).await);
+    let mut days = or_exit(client.find_events(today, today, Berlin).await);
+
+    println!("Today: {} {}", today, berlin_now.weekday());
+
+    if let Some(today) = days.pop() {
+        for event in today.events {
+            println!("{}", render_event(&event, today.date));
         }
+    }
+
+    for todo in todos_becoming_valid {
+        println!("{}", render_todo(&todo));
+    }
+}
+
+pub(crate) async fn execute(client: &Client, action: crate::CalendarAction) {
+    match action {
+        crate::CalendarAction::Today => show_today(client).await,
+        crate::CalendarAction::Agenda { days } => show_agenda(client, days).await,
         crate::CalendarAction::Add {
             when,
             duration,
             title,
+            rrule,
+            shared,
+        } => {
+            let id = or_exit(
+                client
+                    .create_event(when, duration, title, rrule, shared)
+                    .await,
+            );
+
+            println!("Created event {id}");
+        }
+        crate::CalendarAction::Edit {
+            id,
+            title,
+            when,
+            duration,
+            rrule,
+            no_rrule,
+            skip,
         } => {
-            client
-                .post(format!("{server_url}events"))
-                .json(&PostEvent::Add {
-                    date: when,
-                    duration,
-                    title,
-                })
-                .send()
-                .unwrap();
+            or_exit(
+                client
+                    .edit_event(
+                        ratlib::calendar::event::Id(id),
+                        title,
+                        when,
+                        duration,
+                        if no_rrule {
+                            Some(None)
+                        } else {
+                            rrule.map(Some)
+                        },
+                        skip.iter().map(DateTime::naive_local).collect(),
+                    )
+                    .await,
+            );
+        }
+        crate::CalendarAction::Rm { id } => {
+            or_exit(client.delete_event(ratlib::calendar::event::Id(id)).await);
+        }
+        crate::CalendarAction::Import { file } => {
+            let contents = std::fs::read_to_string(&file).unwrap();
+
+            let ids = or_exit(client.import_events(contents, Berlin).await);
+
+            println!("Imported {} event(s) from {}", ids.len(), file.display());
+        }
+        crate::CalendarAction::Export { file } => {
+            let calendar = or_exit(client.export_events().await);
+
+            match file {
+                Some(file) => std::fs::write(file, calendar).unwrap(),
+                None => print!("{calendar}"),
+            }
         }
     }
 }
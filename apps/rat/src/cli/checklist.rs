@@ -0,0 +1,64 @@
+use colored::{Color, Colorize as _};
+use ratlib::todo::client::Client;
+
+use crate::{cli::or_exit, todo::Id, ChecklistAction};
+
+fn index(item: usize) -> usize {
+    if item == 0 {
+        eprintln!("{}", "Checklist items are numbered from 1".red());
+
+        std::process::exit(1);
+    }
+
+    item - 1
+}
+
+fn show(todo: &crate::todo::Todo) {
+    let checklist = todo.checklist();
+    let (done, total) = checklist.progress();
+
+    println!(
+        "{} {} {}",
+        todo.id().to_string().color(Color::BrightBlack),
+        todo.title(),
+        format!("{done}/{total}").color(Color::Green)
+    );
+
+    if checklist.completes_todo() {
+        println!(
+            "{}",
+            "Moves to done once everything is checked".color(Color::BrightBlack)
+        );
+    }
+
+    for (i, item) in checklist.items().iter().enumerate() {
+        let title = if item.done {
+            item.title.color(Color::BrightBlack).strikethrough()
+        } else {
+            item.title.normal()
+        };
+
+        println!(
+            "{:>4} [{}] {title}",
+            i + 1,
+            if item.done { "x" } else { " " }
+        );
+    }
+}
+
+pub(crate) async fn execute(client: &Client, id: Id, action: Option<ChecklistAction>) {
+    match action {
+        None => {}
+        Some(ChecklistAction::Add { title }) => {
+            or_exit(client.add_checklist_item(id, title).await);
+        }
+        Some(ChecklistAction::Toggle { item }) => {
+            or_exit(client.toggle_checklist_item(id, index(item)).await);
+        }
+        Some(ChecklistAction::Rm { item }) => {
+            or_exit(client.remove_checklist_item(id, index(item)).await);
+        }
+    }
+
+    show(&or_exit(client.find_by_id(id).await));
+}
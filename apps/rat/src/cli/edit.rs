@@ -1,28 +1,10 @@
-use std::time::Duration;
+use ratlib::{
+    todo::{client::Client, Id},
+    TodoEdit,
+};
 
-use ratlib::PostTodoWithId;
+use crate::cli::or_exit;
 
-use crate::todo::{Id, Priority, Requirement};
-
-pub async fn execute(
-    server_url: &str,
-    id: Id,
-    add_requirements: Option<Vec<Requirement>>,
-    set_priority: Option<Priority>,
-    set_estimate: Option<Duration>,
-    set_title: Option<String>,
-) {
-    let client = reqwest::Client::new();
-
-    client
-        .post(format!("{server_url}todos/{}", id.0))
-        .json(&PostTodoWithId::Edit {
-            set_title,
-            set_estimate,
-            add_requirements: add_requirements.unwrap_or_default(),
-            set_priority,
-        })
-        .send()
-        .await
-        .unwrap();
+pub async fn execute(client: &Client, id: Id, edit: TodoEdit) {
+    or_exit(client.edit(id, edit).await);
 }
@@ -0,0 +1,57 @@
+use chrono::DateTime;
+use chrono_tz::Tz;
+use clap::Args;
+use ratlib::todo::{client::Client, filter::Filter};
+
+use crate::{
+    cli::{list::render_todo, or_exit},
+    parse_id, parse_priority, parse_status, parse_timespec,
+    todo::{Id, Priority, Status},
+};
+
+#[derive(Args)]
+pub struct FindArgs {
+    #[arg(short, long, value_parser = parse_status)]
+    status: Option<Status>,
+    #[arg(long, value_parser = parse_priority)]
+    min_priority: Option<Priority>,
+    #[arg(long, value_parser = parse_priority)]
+    max_priority: Option<Priority>,
+    #[arg(long)]
+    has_deadline: Option<bool>,
+    #[arg(long, value_parser = parse_timespec)]
+    deadline_before: Option<DateTime<Tz>>,
+    #[arg(long, value_parser = parse_timespec)]
+    deadline_after: Option<DateTime<Tz>>,
+    #[arg(short, long)]
+    title: Option<String>,
+    #[arg(short, long, value_parser = parse_id)]
+    requires: Option<Id>,
+    #[arg(long)]
+    tag: Option<String>,
+    #[arg(long)]
+    project: Option<String>,
+}
+
+impl From<FindArgs> for Filter {
+    fn from(value: FindArgs) -> Self {
+        Filter {
+            status: value.status,
+            min_priority: value.min_priority,
+            max_priority: value.max_priority,
+            has_deadline: value.has_deadline,
+            deadline_before: value.deadline_before.map(|x| x.to_utc()),
+            deadline_after: value.deadline_after.map(|x| x.to_utc()),
+            title_contains: value.title,
+            requires: value.requires,
+            tag: value.tag,
+            project: value.project,
+        }
+    }
+}
+
+pub async fn execute(client: &Client, args: FindArgs) {
+    for todo in or_exit(client.find(&args.into()).await) {
+        println!("{}", render_todo(&todo));
+    }
+}
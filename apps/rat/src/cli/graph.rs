@@ -0,0 +1,183 @@
+use std::{collections::HashMap, fmt::Display};
+
+use colored::{Color, Colorize as _};
+use petgraph::{
+    algo::tarjan_scc,
+    dot::{Config, Dot},
+    graph::{DiGraph, NodeIndex},
+    Direction,
+};
+use ratlib::todo::{client::Client, graph::Graph, Id, Status};
+
+use crate::cli::or_exit;
+
+struct Vertex {
+    id: Id,
+    // None if a todo requires one that doesn't exist
+    todo: Option<(String, Status)>,
+}
+
+impl Vertex {
+    fn color(&self) -> Color {
+        match self.todo {
+            Some((_, Status::Todo)) => Color::White,
+            Some((_, Status::Doing)) => Color::Yellow,
+            Some((_, Status::Done)) => Color::BrightBlack,
+            None => Color::Red,
+        }
+    }
+
+    fn dot_attributes(&self) -> String {
+        match self.todo {
+            Some((_, Status::Todo)) => String::new(),
+            Some((_, Status::Doing)) => "color = orange ".to_string(),
+            Some((_, Status::Done)) => "style = dashed ".to_string(),
+            None => "color = red style = dashed ".to_string(),
+        }
+    }
+}
+
+impl Display for Vertex {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.todo {
+            Some((title, _)) => write!(f, "{}: {title}", self.id),
+            None => write!(f, "{}: (missing)", self.id),
+        }
+    }
+}
+
+// Edges point from a todo to the ones it requires. They have no weight, but `Dot` wants
+// something it can display.
+fn build(graph: Graph) -> DiGraph<Vertex, &'static str> {
+    let mut result = DiGraph::new();
+    let mut indices = HashMap::new();
+
+    for node in graph.nodes {
+        indices.insert(
+            node.id,
+            result.add_node(Vertex {
+                id: node.id,
+                todo: Some((node.title, node.status)),
+            }),
+        );
+    }
+
+    for edge in graph.edges {
+        let [todo, requires] = [edge.todo, edge.requires].map(|id| {
+            *indices
+                .entry(id)
+                .or_insert_with(|| result.add_node(Vertex { id, todo: None }))
+        });
+
+        result.add_edge(todo, requires, "");
+    }
+
+    result
+}
+
+fn sorted_by_id(
+    graph: &DiGraph<Vertex, &'static str>,
+    nodes: impl Iterator<Item = NodeIndex>,
+) -> Vec<NodeIndex> {
+    let mut nodes: Vec<_> = nodes.collect();
+    nodes.sort_by_key(|x| graph[*x].id.0);
+    nodes.dedup();
+
+    nodes
+}
+
+fn print_tree(
+    graph: &DiGraph<Vertex, &'static str>,
+    node: NodeIndex,
+    prefix: &str,
+    path: &mut Vec<NodeIndex>,
+    visited: &mut Vec<NodeIndex>,
+) {
+    path.push(node);
+    visited.push(node);
+
+    let requirements = sorted_by_id(graph, graph.neighbors_directed(node, Direction::Outgoing));
+
+    for (i, requirement) in requirements.iter().enumerate() {
+        let last = i == requirements.len() - 1;
+        let vertex = &graph[*requirement];
+
+        print!("{prefix}{}", if last { "└── " } else { "├── " });
+
+        if path.contains(requirement) {
+            println!("{} {}", vertex, "(cycle)".color(Color::Red));
+            continue;
+        }
+
+        println!("{}", vertex.to_string().color(vertex.color()));
+        print_tree(
+            graph,
+            *requirement,
+            &format!("{prefix}{}", if last { "    " } else { "│   " }),
+            path,
+            visited,
+        );
+    }
+
+    path.pop();
+}
+
+pub async fn execute(client: &Client, include_done: bool, dot: bool) {
+    let graph = build(or_exit(client.find_graph(include_done).await));
+
+    for component in tarjan_scc(&graph) {
+        if component.len() > 1 || graph.contains_edge(component[0], component[0]) {
+            let ids: Vec<_> = sorted_by_id(&graph, component.into_iter())
+                .into_iter()
+                .map(|x| graph[x].id.to_string())
+                .collect();
+
+            eprintln!(
+                "{}",
+                format!(
+                    "Todos {} require each other, none of them will ever be ready",
+                    ids.join(", ")
+                )
+                .color(Color::Red)
+            );
+        }
+    }
+
+    if dot {
+        println!(
+            "{}",
+            Dot::with_attr_getters(
+                &graph,
+                &[Config::EdgeNoLabel],
+                &|_, _| String::new(),
+                &|_, (_, vertex)| vertex.dot_attributes(),
+            )
+        );
+
+        return;
+    }
+
+    // Start with the todos nothing else requires, then cover the cycles that have no way in
+    let mut roots = sorted_by_id(
+        &graph,
+        graph.node_indices().filter(|x| {
+            graph
+                .neighbors_directed(*x, Direction::Incoming)
+                .next()
+                .is_none()
+        }),
+    );
+    roots.extend(sorted_by_id(&graph, graph.node_indices()));
+
+    let mut visited = vec![];
+
+    for root in roots {
+        if visited.contains(&root) {
+            continue;
+        }
+
+        let vertex = &graph[root];
+        println!("{}", vertex.to_string().color(vertex.color()).bold());
+        print_tree(&graph, root, "", &mut vec![], &mut visited);
+    }
+}
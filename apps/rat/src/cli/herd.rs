@@ -0,0 +1,130 @@
+use chrono::Local;
+use colored::{Color, Colorize as _};
+use ratlib::{
+    herd::{describe_elapsed, Drift, Health},
+    todo::client::Client,
+};
+
+use crate::{cli::or_exit, HerdAction};
+
+pub async fn execute(client: &Client, action: HerdAction) {
+    match action {
+        HerdAction::Status => show_status(client).await,
+        HerdAction::Drift => show_drift(client).await,
+        HerdAction::History { hostname } => show_history(client, &hostname).await,
+    }
+}
+
+async fn show_history(client: &Client, hostname: &str) {
+    let history = or_exit(client.find_herd_history(hostname).await);
+
+    println!(
+        "{}",
+        format!("{:<16} {:>7}  {}", "since", "ran for", "closure").bold()
+    );
+
+    for period in history {
+        let ran_for = match period.ended_at {
+            Some(ended_at) => {
+                describe_elapsed((ended_at - period.started_at).to_std().unwrap_or_default())
+                    .normal()
+            }
+            None => "current".color(Color::Green),
+        };
+
+        println!(
+            "{:<16} {:>7}  {}",
+            period
+                .started_at
+                .with_timezone(&Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+                .color(Color::BrightBlack),
+            ran_for,
+            period.closure_name()
+        );
+    }
+}
+
+async fn show_drift(client: &Client) {
+    let mut machines: Vec<_> = or_exit(client.find_herd_machines().await)
+        .into_iter()
+        .filter(|x| x.drift == Drift::Drifting)
+        .collect();
+
+    if machines.is_empty() {
+        println!("{}", "Every machine runs its desired closure".green());
+
+        return;
+    }
+
+    // The ones that have been out of date for the longest first
+    machines.sort_by_key(|x| std::cmp::Reverse(x.drifting_for));
+
+    for machine in machines {
+        println!(
+            "{:<16} drifting for {:>4}",
+            machine.hostname.bold(),
+            machine
+                .drifting_for
+                .map_or_else(|| "-".to_string(), describe_elapsed)
+                .color(Color::Red),
+        );
+        println!(
+            "    {} {}",
+            "running".color(Color::BrightBlack),
+            machine.closure_name()
+        );
+
+        if let Some(desired) = &machine.desired_closure {
+            println!(
+                "    {} {}",
+                "desired".color(Color::BrightBlack),
+                desired.closure_name()
+            );
+        }
+    }
+}
+
+async fn show_status(client: &Client) {
+    let machines = or_exit(client.find_herd_machines().await);
+
+    println!(
+        "{}",
+        format!(
+            "{:<16} {:<7} {:>9} {:>8}  {}",
+            "host", "status", "last seen", "switched", "closure"
+        )
+        .bold()
+    );
+
+    for machine in machines {
+        let status = match machine.health {
+            Health::Online => "online".color(Color::Green),
+            Health::Stale => "stale".color(Color::Red),
+        };
+
+        println!(
+            "{:<16} {:<7} {:>9} {:>8}  {}",
+            machine.hostname,
+            status,
+            describe_elapsed(machine.since_last_seen),
+            machine
+                .since_closure_change
+                .map_or_else(|| "-".to_string(), describe_elapsed),
+            machine.closure_name().color(Color::BrightBlack)
+        );
+
+        if machine.reboot_pending {
+            println!("    {}", "reboot pending".color(Color::Yellow));
+        }
+
+        if !machine.system.failed_units.is_empty() {
+            println!(
+                "    {} {}",
+                "failed units".color(Color::Red),
+                machine.system.failed_units.join(", ")
+            );
+        }
+    }
+}
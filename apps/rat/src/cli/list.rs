@@ -1,31 +1,77 @@
+use std::fmt::Write as _;
+
+use chrono::Utc;
 use colored::{Color, Colorize as _};
+use ratlib::todo::{client::Client, urgency::ScoredTodo};
+
+use crate::{cli::or_exit, todo::Todo};
+
+fn render_time(todo: &Todo) -> String {
+    let estimate = todo.estimate().as_secs() / 60;
+
+    if todo.started_at().is_none() {
+        return format!("{estimate}min");
+    }
 
-use crate::todo::Todo;
+    format!(
+        "{}/{estimate}min",
+        todo.time_spent(Utc::now()).as_secs() / 60
+    )
+}
 
-pub fn render_todo(todo: &Todo) -> String {
+pub fn render_todo(scored: &ScoredTodo) -> String {
+    let todo = &scored.todo;
     let mut depends_string = "reqs: ".to_string();
     for requirement in todo.requirements() {
-        depends_string += &format!("{requirement} ");
+        let _ = write!(depends_string, "{requirement} ");
     }
 
+    let tags: Vec<_> = todo.tags().iter().map(|x| format!("#{x}")).collect();
+    let progress = match todo.checklist().progress() {
+        (_, 0) => String::new(),
+        (done, total) => format!(" {done}/{total}"),
+    };
+
     format!(
-        "{:>10} {:>10}     {} {} {}",
+        "{:>10} {:>10} {:>5}     {}{}{} {} {} {}",
         todo.id().to_string().color(Color::BrightBlack),
         todo.priority().to_string(),
+        format!("{:.2}", scored.urgency).color(Color::Magenta),
         todo.title(),
+        progress.color(Color::Green),
+        // Notes are too long for a single line, just hint that there are some
+        if todo.notes().is_empty() {
+            ""
+        } else {
+            " [notes]"
+        }
+        .color(Color::BrightBlack),
+        tags.join(" ").color(Color::Cyan),
         if todo.requirements().is_empty() {
             "".color(Color::Blue)
         } else {
             depends_string.color(Color::Blue)
         },
-        format!("{}min", todo.estimate().as_secs() / 60).color(Color::BrightYellow)
+        render_time(todo).color(Color::BrightYellow)
     )
 }
 
-pub async fn execute(server_url: &str) {
-    let todo_client = ratlib::todo::client::Client::new(server_url);
+pub async fn execute(todo_client: &Client) {
+    let at_risk = or_exit(todo_client.find_at_risk().await);
+
+    if !at_risk.is_empty() {
+        println!("{}", "At risk: ".color(Color::BrightRed).bold());
 
-    let doing: Vec<Todo> = todo_client.find_doing().await;
+        for todo in at_risk {
+            let todo = render_todo(&todo);
+
+            println!("{todo}");
+        }
+
+        println!();
+    }
+
+    let doing = or_exit(todo_client.find_doing().await);
 
     if !doing.is_empty() {
         println!("{}", "Doing: ".color(Color::Yellow).bold());
@@ -40,11 +86,38 @@ pub async fn execute(server_url: &str) {
     }
 
     println!("{}", "Todo: ".color(Color::Red).bold());
-    let ready_to_do: Vec<Todo> = todo_client.find_ready_to_do().await;
+    let ready_to_do = or_exit(todo_client.find_ready_to_do().await);
 
-    for todo in ready_to_do {
-        let todo = render_todo(&todo);
+    for (project, todos) in by_project(ready_to_do) {
+        println!(
+            "{}",
+            project
+                .unwrap_or_else(|| "No project".to_string())
+                .color(Color::White)
+                .underline()
+        );
 
-        println!("{todo}");
+        for todo in todos {
+            let todo = render_todo(&todo);
+
+            println!("{todo}");
+        }
     }
 }
+
+// Keeps the order the server returned the todos in, both between and within projects, so the
+// most urgent project comes first
+fn by_project(todos: Vec<ScoredTodo>) -> Vec<(Option<String>, Vec<ScoredTodo>)> {
+    let mut projects: Vec<(Option<String>, Vec<ScoredTodo>)> = vec![];
+
+    for todo in todos {
+        let project = todo.todo.project().map(ToString::to_string);
+
+        match projects.iter_mut().find(|(x, _)| *x == project) {
+            Some((_, todos)) => todos.push(todo),
+            None => projects.push((project, vec![todo])),
+        }
+    }
+
+    projects
+}
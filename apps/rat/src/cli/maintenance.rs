@@ -1,22 +1,11 @@
-use reqwest::StatusCode;
+use ratlib::todo::client::Client;
 
-use crate::MaintenanceAction;
-
-pub async fn execute(server_url: &str, action: MaintenanceAction) {
-    let client = reqwest::Client::new();
+use crate::{cli::or_exit, MaintenanceAction};
 
+pub async fn execute(client: &Client, action: MaintenanceAction) {
     match action {
         MaintenanceAction::Monitoring => {
-            let response = client
-                .post(format!("{server_url}maintenance/monitoring"))
-                .send()
-                .await
-                .unwrap();
-
-            assert!(
-                response.status() == StatusCode::OK,
-                "Failed to execute monitoring maintenance"
-            );
+            or_exit(client.run_monitoring_maintenance().await);
         }
     }
 }
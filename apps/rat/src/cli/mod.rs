@@ -1,6 +1,27 @@
 pub mod add;
+pub mod archive;
 pub mod calendar;
+pub mod checklist;
 pub mod edit;
+pub mod find;
+pub mod graph;
+pub mod herd;
 pub mod list;
 pub mod maintenance;
+pub mod plan;
+pub mod report;
 pub mod state_transition;
+pub mod watch;
+pub mod why;
+
+use colored::Colorize as _;
+use ratlib::todo::client::ClientError;
+
+/// Prints why talking to the server failed and exits
+pub(crate) fn or_exit<T>(result: Result<T, ClientError>) -> T {
+    result.unwrap_or_else(|error| {
+        eprintln!("{}", error.to_string().red());
+
+        std::process::exit(1)
+    })
+}
@@ -0,0 +1,56 @@
+use chrono::{NaiveDate, TimeDelta, TimeZone, Utc};
+use chrono_tz::Europe::Berlin;
+use colored::{Color, Colorize as _};
+use ratlib::todo::client::Client;
+
+use crate::cli::or_exit;
+
+pub async fn execute(client: &Client, date: Option<NaiveDate>, commit: bool) {
+    let date = date.unwrap_or_else(|| {
+        Berlin
+            .from_utc_datetime(&Utc::now().naive_utc())
+            .date_naive()
+    });
+
+    let plan = or_exit(client.find_plan(date, Berlin).await);
+
+    println!("{}", format!("Plan for {date}:").bold());
+
+    for slot in &plan.slots {
+        let end = slot.start + TimeDelta::from_std(slot.duration).unwrap_or(TimeDelta::zero());
+
+        println!(
+            "{}-{} {:>6} {}",
+            slot.start.format("%H:%M").to_string().color(Color::Blue),
+            end.format("%H:%M").to_string().color(Color::Blue),
+            slot.todo.to_string().color(Color::BrightBlack),
+            slot.title
+        );
+    }
+
+    if !plan.unscheduled.is_empty() {
+        println!();
+        println!("{}", "No time for:".color(Color::Red).bold());
+
+        for todo in &plan.unscheduled {
+            println!(
+                "{:>6} {} {}",
+                todo.todo.to_string().color(Color::BrightBlack),
+                todo.title,
+                format!("{}min", todo.duration.as_secs() / 60).color(Color::BrightYellow)
+            );
+        }
+    }
+
+    if commit {
+        for slot in plan.slots {
+            or_exit(
+                client
+                    .create_event(slot.start, slot.duration, slot.title, None, false)
+                    .await,
+            );
+        }
+
+        println!("Added the plan to the calendar");
+    }
+}
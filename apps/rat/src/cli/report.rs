@@ -0,0 +1,60 @@
+use std::time::Duration;
+
+use colored::{Color, Colorize as _};
+use ratlib::todo::client::Client;
+
+use crate::cli::or_exit;
+
+fn minutes(duration: Duration) -> u64 {
+    duration.as_secs() / 60
+}
+
+pub async fn execute(client: &Client, days: u32) {
+    let report = or_exit(client.find_report(days).await);
+
+    println!(
+        "{}",
+        format!(
+            "Done from {} to {}:",
+            report.from.format("%Y-%m-%d"),
+            report.to.format("%Y-%m-%d")
+        )
+        .bold()
+    );
+
+    for priority in report.priorities {
+        let tracked = priority.done - priority.untracked;
+        let difference = if priority.spent > priority.estimated {
+            format!(
+                "+{}min",
+                minutes(priority.spent.saturating_sub(priority.estimated))
+            )
+            .color(Color::Red)
+        } else {
+            format!(
+                "-{}min",
+                minutes(priority.estimated.saturating_sub(priority.spent))
+            )
+            .color(Color::Green)
+        };
+
+        println!(
+            "{:>10} {:>4} done {:>6}min estimated {:>6}min spent {:>10}{}",
+            priority.priority.to_string(),
+            priority.done,
+            minutes(priority.estimated),
+            minutes(priority.spent),
+            if tracked == 0 {
+                String::new().normal()
+            } else {
+                difference
+            },
+            if priority.untracked == 0 {
+                String::new()
+            } else {
+                format!(" ({} untracked)", priority.untracked)
+            }
+            .color(Color::BrightBlack)
+        );
+    }
+}
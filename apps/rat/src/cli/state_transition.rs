@@ -1,13 +1,10 @@
-use ratlib::PostTodoWithId;
+use ratlib::todo::client::Client;
 
-use crate::todo::{Id, Status};
+use crate::{
+    cli::or_exit,
+    todo::{Id, Status},
+};
 
-pub fn execute(server_url: &str, id: Id, status: Status) {
-    let client = reqwest::blocking::Client::new();
-
-    client
-        .post(format!("{}todos/{}", server_url, id.0))
-        .json(&PostTodoWithId::MoveToStatus(status))
-        .send()
-        .unwrap();
+pub async fn execute(client: &Client, id: Id, status: Status) {
+    or_exit(client.move_to_status(id, status).await);
 }
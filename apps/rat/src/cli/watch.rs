@@ -0,0 +1,35 @@
+use chrono::Local;
+use colored::{Color, Colorize as _};
+use ratlib::todo::client::Client;
+
+use crate::cli::{list, or_exit};
+
+/// Prints the changes on ras as they happen. With `show_list`, shows `rat list` instead, and
+/// refreshes it after every change.
+pub async fn execute(client: &Client, show_list: bool) {
+    let mut watch = or_exit(client.watch().await);
+
+    if show_list {
+        list::execute(client).await;
+    }
+
+    while let Some(change) = or_exit(watch.next().await) {
+        if show_list {
+            // Clears the terminal
+            print!("\x1B[2J\x1B[H");
+            list::execute(client).await;
+
+            continue;
+        }
+
+        println!(
+            "{} {change}",
+            Local::now()
+                .format("%H:%M:%S")
+                .to_string()
+                .color(Color::BrightBlack)
+        );
+    }
+
+    eprintln!("{}", "ras closed the connection".red());
+}
@@ -0,0 +1,27 @@
+use chrono_tz::Europe::Berlin;
+use colored::{Color, Colorize as _};
+use ratlib::todo::client::Client;
+
+use crate::{cli::or_exit, todo::Id};
+
+pub async fn execute(client: &Client, id: Id) {
+    let todo = or_exit(client.find_by_id(id).await);
+    let blockers = or_exit(client.find_blockers(id).await);
+
+    print!(
+        "{} {}: ",
+        id.to_string().color(Color::BrightBlack),
+        todo.title()
+    );
+
+    if blockers.is_empty() {
+        println!("{}", "ready".color(Color::Green));
+
+        return;
+    }
+
+    // todo pull the default TZ from the OS
+    let reasons: Vec<_> = blockers.iter().map(|x| x.describe(Berlin)).collect();
+
+    println!("{}", reasons.join(", ").color(Color::Red));
+}
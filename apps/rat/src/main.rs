@@ -2,12 +2,20 @@
 
 use std::{num::ParseIntError, path::PathBuf, time::Duration};
 
-use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, ParseError, TimeZone};
+use chrono::{
+    DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, ParseError, TimeZone, Utc, Weekday,
+};
 use chrono_tz::Europe::Berlin;
 use chrono_tz::Tz;
 use clap::{Parser, Subcommand};
-use ratlib::todo::{self, Id, Priority, Requirement, Status};
-use regex::Regex;
+use ratlib::calendar::rrule::RRule;
+use ratlib::todo::client::Client;
+use ratlib::todo::{
+    self,
+    recurrence::{CronError, Recurrence, Rule},
+    Id, Priority, Requirement, Status,
+};
+use ratlib::TodoEdit;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
@@ -29,33 +37,110 @@ fn parse_priority(value: &str) -> Result<Priority, PriorityError> {
     }
 }
 
+#[derive(Debug, Error)]
+enum StatusError {
+    #[error("Invalid status: \"{0}\"")]
+    InvalidValue(String),
+}
+
 #[derive(Debug, Error)]
 enum RequirementError {
     #[error("Failed to parse \"{0}\" as a requirement")]
     FailedToParse(String),
 }
 
-// TODO: Actually hanlde errors...
+fn parse_requirement_date(value: &str) -> Option<DateTime<Utc>> {
+    let date = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .ok()
+                .map(NaiveDateTime::from)
+        })?;
+
+    Some(Local.from_local_datetime(&date).single()?.to_utc())
+}
+
+// Splits on the commas that aren't inside parentheses
+fn split_requirements(value: &str) -> Vec<&str> {
+    let mut result = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in value.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                result.push(&value[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    result.push(&value[start..]);
+
+    result
+}
+
+// Accepts a todo ID, `after(<date>)`, `before(<date>)`, `on(<weekday>)`, `between(HH:MM-HH:MM)`,
+// `host(<hostname>)`, and `any(...)`/`all(...)` of comma-separated requirements
 fn parse_requirement(value: &str) -> Result<Requirement, RequirementError> {
-    let after_date =
-        Regex::new(r"after\(([0-9]{4}\-[0-9]{2}\-[0-9]{2}(?: [0-9]{2}:[0-9]{2}:[0-9]{2})?)\)")
-            .unwrap();
+    let failed = || RequirementError::FailedToParse(value.to_string());
 
     if let Ok(id) = value.parse() {
-        Ok(todo::Requirement::TodoDone(Id(id)))
-    } else if let Some(captures) = after_date.captures(value) {
-        let date = if let Ok(x) = NaiveDateTime::parse_from_str(&captures[1], "%Y-%m-%d %H:%M:%S") {
-            x
-        } else if let Ok(x) = NaiveDate::parse_from_str(&captures[1], "%Y-%m-%d") {
-            NaiveDateTime::from(x)
-        } else {
-            panic!("Invalid date/time");
-        };
-        let local_now = Local.from_local_datetime(&date).unwrap();
-
-        Ok(todo::Requirement::AfterDate(local_now.into()))
-    } else {
-        Err(RequirementError::FailedToParse(value.to_string()))
+        return Ok(Requirement::TodoDone(Id(id)));
+    }
+
+    let (kind, argument) = value
+        .strip_suffix(')')
+        .and_then(|x| x.split_once('('))
+        .ok_or_else(failed)?;
+
+    match kind {
+        "after" => Ok(Requirement::AfterDate(
+            parse_requirement_date(argument).ok_or_else(failed)?,
+        )),
+        "before" => Ok(Requirement::BeforeDate(
+            parse_requirement_date(argument).ok_or_else(failed)?,
+        )),
+        // todo pull the default TZ from the OS
+        "on" => Ok(Requirement::OnWeekday(
+            argument.parse().map_err(|_| failed())?,
+            Berlin,
+        )),
+        "between" => {
+            let (start, end) = argument.split_once('-').ok_or_else(failed)?;
+
+            Ok(Requirement::DuringHours(
+                NaiveTime::parse_from_str(start, "%H:%M").map_err(|_| failed())?,
+                NaiveTime::parse_from_str(end, "%H:%M").map_err(|_| failed())?,
+                Berlin,
+            ))
+        }
+        "host" => Ok(Requirement::HostOnline(argument.to_string())),
+        "any" | "all" => {
+            let requirements = split_requirements(argument)
+                .into_iter()
+                .map(parse_requirement)
+                .collect::<Result<_, _>>()?;
+
+            Ok(if kind == "any" {
+                Requirement::AnyOf(requirements)
+            } else {
+                Requirement::AllOf(requirements)
+            })
+        }
+        _ => Err(failed()),
+    }
+}
+
+fn parse_status(value: &str) -> Result<Status, StatusError> {
+    match value {
+        "todo" => Ok(Status::Todo),
+        "doing" => Ok(Status::Doing),
+        "done" => Ok(Status::Done),
+        _ => Err(StatusError::InvalidValue(value.to_string())),
     }
 }
 
@@ -71,6 +156,62 @@ fn parse_id(id: &str) -> Result<Id, ParseIntError> {
     Ok(Id(id))
 }
 
+#[derive(Debug, Error)]
+enum RecurrenceError {
+    #[error("Failed to parse \"{0}\" as a recurrence")]
+    FailedToParse(String),
+    #[error("Invalid cron expression: {0}")]
+    Cron(#[from] CronError),
+}
+
+// Accepts `<daily|weekly|monthly>[/<every>][:<days>][@<HH:MM>]`, e.g. `weekly:mon,thu@18:00`,
+// `monthly/2:15` or `daily`, as well as `cron:<expression>`.
+fn parse_recurrence(value: &str) -> Result<Recurrence, RecurrenceError> {
+    let failed = || RecurrenceError::FailedToParse(value.to_string());
+
+    if let Some(expression) = value.strip_prefix("cron:") {
+        // todo pull the default TZ from the OS
+        return Ok(Recurrence::new(Rule::Cron(expression.parse()?), Berlin));
+    }
+
+    let (rest, at) = match value.split_once('@') {
+        Some((rest, at)) => (
+            rest,
+            NaiveTime::parse_from_str(at, "%H:%M").map_err(|_| failed())?,
+        ),
+        None => (value, NaiveTime::from_hms_opt(0, 0, 0).ok_or_else(failed)?),
+    };
+    let (rest, days) = match rest.split_once(':') {
+        Some((rest, days)) => (rest, Some(days)),
+        None => (rest, None),
+    };
+    let (kind, every) = match rest.split_once('/') {
+        Some((kind, every)) => (kind, every.parse().map_err(|_| failed())?),
+        None => (rest, 1),
+    };
+
+    let rule = match (kind, days) {
+        ("daily", None) => Rule::Daily { every, at },
+        ("weekly", Some(days)) => Rule::Weekly {
+            every,
+            on: days
+                .split(',')
+                .map(str::parse::<Weekday>)
+                .collect::<Result<_, _>>()
+                .map_err(|_| failed())?,
+            at,
+        },
+        ("monthly", Some(day)) => Rule::Monthly {
+            every,
+            day: day.parse().map_err(|_| failed())?,
+            at,
+        },
+        _ => return Err(failed()),
+    };
+
+    Ok(Recurrence::new(rule, Berlin))
+}
+
 #[derive(Error, Debug)]
 enum TimespecError {
     #[error("parse error: {0}")]
@@ -88,13 +229,70 @@ fn parse_timespec(timespec: &str) -> Result<DateTime<Tz>, TimespecError> {
 #[derive(Subcommand)]
 enum CalendarAction {
     Today,
+    /// Shows the events of the coming days
+    Agenda {
+        #[arg(short = 'd', long, default_value_t = 7)]
+        days: u64,
+    },
     Add {
         #[arg(value_parser=parse_timespec)]
         when: DateTime<Tz>,
         #[arg(value_parser=parse_minutes)]
         duration: Duration,
         title: String,
+        /// RFC 5545 recurrence rule, e.g. "FREQ=WEEKLY;BYDAY=MO,WE;COUNT=10"
+        #[arg(short = 'r', long)]
+        rrule: Option<RRule>,
+        /// Makes the event visible to everyone using ras, not just you
+        #[arg(long)]
+        shared: bool,
+    },
+    Edit {
+        id: u32,
+        #[arg(short = 't', long)]
+        title: Option<String>,
+        #[arg(short = 'w', long, value_parser=parse_timespec)]
+        when: Option<DateTime<Tz>>,
+        #[arg(short = 'd', long, value_parser=parse_minutes)]
+        duration: Option<Duration>,
+        #[arg(short = 'r', long, conflicts_with = "no_rrule")]
+        rrule: Option<RRule>,
+        /// Makes the event a single one again
+        #[arg(long)]
+        no_rrule: bool,
+        /// Removes the occurrence starting at this time from the series
+        #[arg(short = 's', long, value_parser=parse_timespec)]
+        skip: Vec<DateTime<Tz>>,
+    },
+    Rm {
+        id: u32,
     },
+    /// Imports events from an iCalendar (.ics) file
+    Import {
+        file: PathBuf,
+    },
+    /// Writes all events and todos with deadlines as iCalendar, to stdout if no file is given
+    Export {
+        file: Option<PathBuf>,
+    },
+}
+
+/// Checklist items are numbered from 1, as shown by `rat checklist <id>`
+#[derive(Subcommand)]
+enum ChecklistAction {
+    Add { title: String },
+    Toggle { item: usize },
+    Rm { item: usize },
+}
+
+#[derive(Subcommand)]
+enum HerdAction {
+    /// Shows when each machine last reported, and which closure it's running
+    Status,
+    /// Lists the machines that don't run the closure they should
+    Drift,
+    /// Every closure the machine ran, the current one first
+    History { hostname: String },
 }
 
 #[derive(Subcommand)]
@@ -112,6 +310,8 @@ enum Command {
         estimate: Duration,
         #[arg(value_parser=parse_requirement)]
         requirements: Vec<Requirement>,
+        #[arg(short = 'r', long, value_parser = parse_recurrence)]
+        every: Option<Recurrence>,
     },
     Done {
         #[arg(value_parser=parse_id)]
@@ -136,12 +336,90 @@ enum Command {
         set_estimate: Option<Duration>,
         #[arg(short = 't', long)]
         set_title: Option<String>,
+        /// Can be given more than once, with or without the leading `#`
+        #[arg(long)]
+        add_tag: Vec<String>,
+        #[arg(long)]
+        remove_tag: Vec<String>,
+        /// An empty project removes the todo from its current one
+        #[arg(long)]
+        set_project: Option<String>,
+        #[arg(long)]
+        set_notes: Option<String>,
+        /// Move the todo to done once every item of its checklist is checked
+        #[arg(long)]
+        set_completes_with_checklist: Option<bool>,
+        /// Shared todos are visible to everyone using ras, the rest only to you
+        #[arg(long)]
+        set_shared: Option<bool>,
     },
     List,
+    /// Proposes when to work on which todo, around the events of the day
+    Plan {
+        /// Defaults to today
+        #[arg(short = 'd', long)]
+        date: Option<NaiveDate>,
+        /// Adds the proposed slots to the calendar as events
+        #[arg(long)]
+        commit: bool,
+    },
+    /// Compares the estimates of recently done todos to the time spent on them
+    Report {
+        #[arg(short = 'd', long, default_value_t = 30)]
+        days: u32,
+    },
+    /// Explains why a todo isn't ready to be done yet
+    Why {
+        #[arg(value_parser=parse_id)]
+        id: Id,
+    },
+    /// Shows which todos require which, as a tree or in the DOT format
+    Graph {
+        /// Also show the todos that are done
+        #[arg(short, long)]
+        all: bool,
+        #[arg(long)]
+        dot: bool,
+    },
+    Find(cli::find::FindArgs),
+    /// Moves a todo out of the way, todos that required it no longer do if it's done
+    Archive {
+        #[arg(value_parser=parse_id)]
+        id: Id,
+    },
+    /// Shows the archived todos, most recently archived first
+    Archived {
+        #[arg(short, long, default_value_t = 1)]
+        page: usize,
+    },
+    Rm {
+        #[arg(value_parser=parse_id)]
+        id: Id,
+    },
+    /// Reverts your most recent change to the todos, unless they have changed since. ras only
+    /// remembers changes until it restarts.
+    Undo,
+    /// Follows the changes on ras as they happen
+    Watch {
+        /// Show the todo list, and refresh it on every change
+        #[arg(short, long)]
+        list: bool,
+    },
+    /// Shows the checklist of a todo, or changes it
+    Checklist {
+        #[arg(value_parser=parse_id)]
+        id: Id,
+        #[command(subcommand)]
+        action: Option<ChecklistAction>,
+    },
     Calendar {
         #[command(subcommand)]
         action: CalendarAction,
     },
+    Herd {
+        #[command(subcommand)]
+        action: HerdAction,
+    },
     Maintenance {
         #[command(subcommand)]
         action: MaintenanceAction,
@@ -159,6 +437,34 @@ struct Configuration {
     server_address: String,
 }
 
+// The token is personal, so it doesn't go into the shared configuration. It's taken from
+// `RAS_TOKEN`, or `~/.config/rat/token`.
+fn read_token() -> String {
+    if let Ok(token) = std::env::var("RAS_TOKEN") {
+        return token;
+    }
+
+    let token_path =
+        PathBuf::from(std::env::var("HOME").expect("HOME is not set")).join(".config/rat/token");
+
+    std::fs::read_to_string(&token_path).unwrap_or_else(|_| {
+        eprintln!(
+            "Missing token, set RAS_TOKEN or put it in {}",
+            token_path.display()
+        );
+        std::process::exit(1);
+    })
+}
+
+fn create_client(server_address: String) -> Client {
+    Client::new(server_address)
+        .with_token(&read_token())
+        .unwrap_or_else(|e| {
+            eprintln!("Invalid token: {e}");
+            std::process::exit(1);
+        })
+}
+
 fn read_configuration() -> Configuration {
     let config_path = PathBuf::from("/etc/ramona/rat/config.json");
     assert!(config_path.exists(), "Missing configuration file!");
@@ -173,6 +479,7 @@ fn read_configuration() -> Configuration {
 async fn main() {
     let cli = Cli::parse();
     let configuration = read_configuration();
+    let client = create_client(configuration.server_address);
 
     match cli.command {
         Command::Add {
@@ -180,27 +487,54 @@ async fn main() {
             priority,
             estimate,
             requirements,
+            every,
         } => {
-            cli::add::execute(
-                &configuration.server_address,
-                &title,
-                priority,
-                estimate,
-                requirements,
-            )
-            .await;
+            cli::add::execute(&client, &title, priority, estimate, requirements, every).await;
         }
         Command::List => {
-            cli::list::execute(&configuration.server_address).await;
+            cli::list::execute(&client).await;
+        }
+        Command::Plan { date, commit } => {
+            cli::plan::execute(&client, date, commit).await;
+        }
+        Command::Report { days } => {
+            cli::report::execute(&client, days).await;
+        }
+        Command::Archive { id } => {
+            cli::archive::archive(&client, id).await;
+        }
+        Command::Archived { page } => {
+            cli::archive::list(&client, page).await;
+        }
+        Command::Rm { id } => {
+            cli::archive::delete(&client, id).await;
+        }
+        Command::Undo => {
+            cli::archive::undo(&client).await;
+        }
+        Command::Watch { list } => {
+            cli::watch::execute(&client, list).await;
+        }
+        Command::Checklist { id, action } => {
+            cli::checklist::execute(&client, id, action).await;
+        }
+        Command::Why { id } => {
+            cli::why::execute(&client, id).await;
+        }
+        Command::Graph { all, dot } => {
+            cli::graph::execute(&client, all, dot).await;
+        }
+        Command::Find(args) => {
+            cli::find::execute(&client, args).await;
         }
         Command::Doing { id } => {
-            cli::state_transition::execute(&configuration.server_address, id, Status::Doing);
+            cli::state_transition::execute(&client, id, Status::Doing).await;
         }
         Command::Done { id } => {
-            cli::state_transition::execute(&configuration.server_address, id, Status::Done);
+            cli::state_transition::execute(&client, id, Status::Done).await;
         }
         Command::Todo { id } => {
-            cli::state_transition::execute(&configuration.server_address, id, Status::Todo);
+            cli::state_transition::execute(&client, id, Status::Todo).await;
         }
         Command::Edit {
             id,
@@ -208,22 +542,39 @@ async fn main() {
             set_priority,
             set_estimate,
             set_title,
+            add_tag,
+            remove_tag,
+            set_project,
+            set_notes,
+            set_completes_with_checklist,
+            set_shared,
         } => {
             cli::edit::execute(
-                &configuration.server_address,
+                &client,
                 id,
-                add_requirements,
-                set_priority,
-                set_estimate,
-                set_title,
+                TodoEdit {
+                    set_title,
+                    set_estimate,
+                    add_requirements: add_requirements.unwrap_or_default(),
+                    set_priority,
+                    add_tags: add_tag,
+                    remove_tags: remove_tag,
+                    set_project,
+                    set_notes,
+                    set_completes_with_checklist,
+                    set_shared,
+                },
             )
             .await;
         }
         Command::Calendar { action } => {
-            cli::calendar::execute(&configuration.server_address, action);
+            cli::calendar::execute(&client, action).await;
+        }
+        Command::Herd { action } => {
+            cli::herd::execute(&client, action).await;
         }
         Command::Maintenance { action } => {
-            cli::maintenance::execute(&configuration.server_address, action).await;
+            cli::maintenance::execute(&client, action).await;
         }
     }
 }
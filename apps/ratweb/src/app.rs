@@ -1,12 +1,18 @@
 use std::time::Duration;
 
-use crate::error_template::{AppError, ErrorTemplate};
+use crate::{
+    error_template::{AppError, ErrorTemplate},
+    herd::HerdPage,
+};
 use chrono::{DateTime, Local, NaiveDateTime};
 use chrono_tz::Europe::Berlin;
 use leptos::{html::Form, *};
 use leptos_meta::*;
 use leptos_router::*;
-use ratlib::todo::{Priority, Todo};
+use ratlib::{
+    feed::Change,
+    todo::{urgency::ScoredTodo, Priority, Todo},
+};
 use strum::IntoEnumIterator;
 
 #[component]
@@ -29,6 +35,7 @@ pub fn App() -> impl IntoView {
             <main>
                 <Routes>
                     <Route path="" view=HomePage ssr=SsrMode::Async />
+                    <Route path="herd" view=HerdPage ssr=SsrMode::Async />
                 </Routes>
             </main>
         </Router>
@@ -38,12 +45,45 @@ pub fn App() -> impl IntoView {
 #[component]
 fn HomePage() -> impl IntoView {
     let (todos_update, todos_update_set) = create_signal(0);
+    refresh_on_changes(todos_update_set, Change::affects_todos);
+
     view! {
         <InlineTodoCreation on_send=todos_update_set />
         <Todos update_signal=todos_update />
     }
 }
 
+/// Bumps `update` whenever something `relevant` changes on ras, so that changes made elsewhere
+/// (e.g. with rat) show up without reloading
+pub(crate) fn refresh_on_changes(update: WriteSignal<usize>, relevant: fn(&Change) -> bool) {
+    #[cfg(feature = "hydrate")]
+    {
+        use wasm_bindgen::{closure::Closure, JsCast};
+
+        let Ok(source) = web_sys::EventSource::new("/stream") else {
+            return;
+        };
+
+        let on_message =
+            Closure::<dyn FnMut(web_sys::MessageEvent)>::new(move |x: web_sys::MessageEvent| {
+                let change = x
+                    .data()
+                    .as_string()
+                    .and_then(|x| serde_json::from_str::<Change>(&x).ok());
+                if change.is_some_and(|x| relevant(&x)) {
+                    update.update(|x| *x += 1);
+                }
+            });
+        source.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        on_message.forget();
+
+        on_cleanup(move || source.close());
+    }
+
+    #[cfg(not(feature = "hydrate"))]
+    let _ = (update, relevant);
+}
+
 #[component]
 fn CompactTodo(todo: Todo) -> impl IntoView {
     let duration_seconds = todo.estimate().as_secs();
@@ -72,6 +112,30 @@ fn CompactTodo(todo: Todo) -> impl IntoView {
         .deadline()
         .map(|x| x.format("%Y-%m-%d %H:%M").to_string())
         .map(|x| view! {<span class="deadline">{ move || x.clone() }</span>});
+    let project = todo
+        .project()
+        .map(ToString::to_string)
+        .map(|x| view! {<span class="project">{ move || x.clone() }</span>});
+    let tags = todo
+        .tags()
+        .iter()
+        .map(|x| format!("#{x}"))
+        .map(|x| view! {<span class="tag">{ move || x.clone() }</span>})
+        .collect::<Vec<_>>();
+    let progress = match todo.checklist().progress() {
+        (_, 0) => None,
+        (done, total) => {
+            let progress = format!("{done}/{total}");
+
+            Some(view! {<span class="progress">{ move || progress.clone() }</span>})
+        }
+    };
+    let notes = todo.notes().to_string();
+    let notes: Option<_> = if notes != "" {
+        Some(view! {<p class="notes">{ move || notes.clone() }</p>})
+    } else {
+        None
+    };
 
     view! {
         <div class="todo -compact">
@@ -81,37 +145,51 @@ fn CompactTodo(todo: Todo) -> impl IntoView {
                 " "
                 {estimate}
                 " "
+                {progress}
+                " "
                 <span class="priority">{ move || priority.clone() }</span>
                 " "
                 {deadline}
+                " "
+                {project}
+                {tags}
             </p>
+            {notes}
         </div>
     }
 }
 
-fn create_todo_client() -> ratlib::todo::client::Client {
+// ratweb acts as a single user towards ras, whose token is in `RAS_TOKEN`. `main` makes sure
+// it's set.
+pub(crate) fn create_todo_client(
+) -> Result<ratlib::todo::client::Client, ratlib::todo::client::ClientError> {
     ratlib::todo::client::Client::new("http://localhost:8438/")
+        .with_token(&std::env::var("RAS_TOKEN").expect("RAS_TOKEN is set"))
+}
+
+fn without_urgency(todos: Vec<ScoredTodo>) -> Vec<Todo> {
+    todos.into_iter().map(|x| x.todo).collect()
 }
 
 #[server(FindReadyToDo, "/api")]
 pub async fn find_ready_to_do() -> Result<Vec<Todo>, ServerFnError> {
-    let client = create_todo_client();
+    let client = create_todo_client()?;
 
-    Ok(client.find_ready_to_do().await)
+    Ok(without_urgency(client.find_ready_to_do().await?))
 }
 
 #[server(FindDoing, "/api")]
 pub async fn find_doing() -> Result<Vec<Todo>, ServerFnError> {
-    let client = create_todo_client();
+    let client = create_todo_client()?;
 
-    Ok(client.find_doing().await)
+    Ok(without_urgency(client.find_doing().await?))
 }
 
-#[server(FindAroundDeadline, "/api")]
-pub async fn find_around_deadline() -> Result<Vec<Todo>, ServerFnError> {
-    let client = create_todo_client();
+#[server(FindAtRisk, "/api")]
+pub async fn find_at_risk() -> Result<Vec<Todo>, ServerFnError> {
+    let client = create_todo_client()?;
 
-    Ok(client.find_around_deadline().await)
+    Ok(without_urgency(client.find_at_risk().await?))
 }
 
 #[component]
@@ -126,16 +204,16 @@ fn Todos(update_signal: ReadSignal<usize>) -> impl IntoView {
         |_| async move { find_doing().await.unwrap() },
     );
 
-    let around_deadline = create_resource(
+    let at_risk = create_resource(
         move || update_signal.get(),
-        |_| async move { find_around_deadline().await.unwrap() },
+        |_| async move { find_at_risk().await.unwrap() },
     );
 
     view! {
         <Suspense>
-            <h1>"Around deadline"</h1>
+            <h1>"At risk"</h1>
             <ul class="todo-list">
-                {move || around_deadline.get().map(|y| y.iter().map(|x| view! { <li><CompactTodo todo=x.clone() /></li> }).collect::<Vec<_>>())}
+                {move || at_risk.get().map(|y| y.iter().map(|x| view! { <li><CompactTodo todo=x.clone() /></li> }).collect::<Vec<_>>())}
             </ul>
 
             <h1>"Currently doing"</h1>
@@ -169,7 +247,7 @@ pub async fn add_todo(
         .transpose()?
         .flatten();
     // FIXME return the address back to hallewell
-    let client = ratlib::todo::client::Client::new("http://localhost:8438/");
+    let client = create_todo_client()?;
 
     client
         .create(
@@ -178,8 +256,9 @@ pub async fn add_todo(
             Duration::from_secs(estimate * 60),
             vec![],
             deadline,
+            None,
         )
-        .await;
+        .await?;
 
     Ok(())
 }
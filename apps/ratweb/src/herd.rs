@@ -0,0 +1,68 @@
+use leptos::*;
+use ratlib::{
+    feed::Change,
+    herd::{describe_elapsed, Health, Machine},
+};
+
+use crate::app::refresh_on_changes;
+
+#[server(FindHerdMachines, "/api")]
+pub async fn find_herd_machines() -> Result<Vec<Machine>, ServerFnError> {
+    let client = crate::app::create_todo_client()?;
+
+    Ok(client.find_herd_machines().await?)
+}
+
+/// When each machine last reported to ras, and which closure it's running
+#[component]
+pub fn HerdPage() -> impl IntoView {
+    let (update, update_set) = create_signal(0);
+    refresh_on_changes(update_set, Change::affects_herd);
+
+    let machines = create_resource(
+        move || update.get(),
+        |_| async move { find_herd_machines().await.unwrap() },
+    );
+
+    view! {
+        <h1>"Herd"</h1>
+        <Suspense>
+            <table class="herd">
+                <thead>
+                    <tr>
+                        <th>"Host"</th>
+                        <th>"Status"</th>
+                        <th>"Last seen"</th>
+                        <th>"Switched"</th>
+                        <th>"Closure"</th>
+                    </tr>
+                </thead>
+                <tbody>
+                    {move || machines.get().map(|x| x.into_iter().map(|x| view! { <MachineRow machine=x /> }).collect::<Vec<_>>())}
+                </tbody>
+            </table>
+        </Suspense>
+    }
+}
+
+#[component]
+fn MachineRow(machine: Machine) -> impl IntoView {
+    let (status, status_class) = match machine.health {
+        Health::Online => ("online", "status -online"),
+        Health::Stale => ("stale", "status -stale"),
+    };
+    let switched = machine.since_closure_change.map_or_else(
+        || "-".to_string(),
+        |x| format!("{} ago", describe_elapsed(x)),
+    );
+
+    view! {
+        <tr>
+            <td>{machine.hostname.clone()}</td>
+            <td><span class=status_class>{status}</span></td>
+            <td>{format!("{} ago", describe_elapsed(machine.since_last_seen))}</td>
+            <td>{switched}</td>
+            <td class="closure">{machine.closure_name().to_string()}</td>
+        </tr>
+    }
+}
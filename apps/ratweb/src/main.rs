@@ -1,12 +1,18 @@
 #[cfg(feature = "ssr")]
 #[tokio::main]
 async fn main() {
-    use axum::Router;
+    use axum::{routing::get, Router};
     use leptos::*;
     use leptos_axum::{generate_route_list, LeptosRoutes};
     use ratweb::app::*;
     use ratweb::fileserv::file_and_error_handler;
 
+    // Without a token ras rejects everything, better to say so right away
+    if std::env::var("RAS_TOKEN").map_or(true, |x| x.trim().is_empty()) {
+        eprintln!("RAS_TOKEN is not set");
+        std::process::exit(1);
+    }
+
     // Setting get_configuration(None) means we'll be using cargo-leptos's env values
     // For deployment these variables are:
     // <https://github.com/leptos-rs/start-axum#executing-a-server-on-a-remote-machine-without-the-toolchain>
@@ -20,6 +26,7 @@ async fn main() {
 
     // build our application with a route
     let app = Router::new()
+        .route("/stream", get(ratweb::stream::get))
         .leptos_routes(&leptos_options, routes, App)
         .fallback(file_and_error_handler)
         .with_state(leptos_options);
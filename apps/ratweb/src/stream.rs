@@ -0,0 +1,27 @@
+use std::convert::Infallible;
+
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures::{stream, Stream};
+
+/// Relays the changes on ras, so the browser doesn't have to reach it directly. The stream ends
+/// when ras can't be reached, browsers reconnect by themselves.
+pub async fn get() -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let watch = match crate::app::create_todo_client() {
+        Ok(client) => client.watch().await.ok(),
+        Err(_) => None,
+    };
+
+    let changes = stream::unfold(watch, |watch| async move {
+        let mut watch = watch?;
+        let change = watch.next().await.ok().flatten()?;
+
+        Some((
+            Ok(Event::default()
+                .json_data(change)
+                .expect("Changes can always be serialized")),
+            Some(watch),
+        ))
+    });
+
+    Sse::new(changes).keep_alive(KeepAlive::default())
+}
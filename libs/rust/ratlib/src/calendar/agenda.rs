@@ -0,0 +1,11 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use super::event::Event;
+
+/// Everything happening on one day. Events spanning midnight show up on every day they overlap.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Day {
+    pub date: NaiveDate,
+    pub events: Vec<Event>,
+}
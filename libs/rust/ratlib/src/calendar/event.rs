@@ -1,14 +1,25 @@
-use std::time::Duration;
+use std::{fmt::Display, time::Duration};
 
-use chrono::DateTime;
+use chrono::{DateTime, NaiveDateTime, TimeDelta, Utc};
 use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 
-use crate::datetime::{deserialize_date_time_tz, serialize_date_time_tz};
+use crate::{
+    datetime::{deserialize_date_time_tz, serialize_date_time_tz},
+    ownership::Ownership,
+};
+
+use super::rrule::RRule;
 
 #[derive(Serialize, Deserialize, Debug, Hash, Eq, PartialEq, Copy, Clone)]
 pub struct Id(pub u32);
 
+impl Display for Id {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Event {
     id: Id,
@@ -20,6 +31,16 @@ pub struct Event {
     duration: Duration,
 
     title: String,
+    /// Identifies events that came from other calendars, so importing them again updates them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    uid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    recurrence: Option<RRule>,
+    /// Local start times (in the timezone of `start`) of occurrences removed from the series
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    exdates: Vec<NaiveDateTime>,
+    #[serde(default)]
+    ownership: Ownership,
 }
 
 impl Event {
@@ -29,6 +50,10 @@ impl Event {
             start,
             duration,
             title,
+            uid: None,
+            recurrence: None,
+            exdates: vec![],
+            ownership: Ownership::default(),
         }
     }
 
@@ -47,4 +72,90 @@ impl Event {
     pub fn title(&self) -> &str {
         &self.title
     }
+
+    pub fn set_start(&mut self, start: DateTime<Tz>) {
+        self.start = start;
+    }
+
+    pub fn set_duration(&mut self, duration: Duration) {
+        self.duration = duration;
+    }
+
+    pub fn set_title(&mut self, title: String) {
+        self.title = title;
+    }
+
+    /// The iCalendar UID of the event. Events created here get one based on their ID.
+    pub fn uid(&self) -> String {
+        self.uid
+            .clone()
+            .unwrap_or_else(|| format!("event-{}@ras", self.id))
+    }
+
+    /// The UID the event had in the calendar it was imported from, if it came from one
+    pub fn external_uid(&self) -> Option<&str> {
+        self.uid.as_deref()
+    }
+
+    pub fn set_uid(&mut self, uid: String) {
+        self.uid = Some(uid);
+    }
+
+    pub fn recurrence(&self) -> Option<&RRule> {
+        self.recurrence.as_ref()
+    }
+
+    pub fn set_recurrence(&mut self, recurrence: Option<RRule>) {
+        self.recurrence = recurrence;
+    }
+
+    pub fn exdates(&self) -> &[NaiveDateTime] {
+        &self.exdates
+    }
+
+    pub fn add_exdate(&mut self, exdate: NaiveDateTime) {
+        if !self.exdates.contains(&exdate) {
+            self.exdates.push(exdate);
+        }
+    }
+
+    pub fn ownership(&self) -> &Ownership {
+        &self.ownership
+    }
+
+    pub fn set_ownership(&mut self, ownership: Ownership) {
+        self.ownership = ownership;
+    }
+
+    /// Returns a copy of the event for every occurrence that starts in `[from, to)`. Events
+    /// without a recurrence have at most one occurrence - themselves.
+    pub fn occurrences_between(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<Event> {
+        let starts = match &self.recurrence {
+            Some(rule) => rule.occurrences_between(self.start, from, to),
+            None if self.start.to_utc() >= from && self.start.to_utc() < to => vec![self.start],
+            None => vec![],
+        };
+
+        starts
+            .into_iter()
+            .filter(|x| !self.exdates.contains(&x.naive_local()))
+            .map(|start| {
+                let mut occurrence = self.clone();
+                occurrence.start = start;
+
+                occurrence
+            })
+            .collect()
+    }
+
+    /// Like `occurrences_between`, but also includes occurrences that started before `from` and
+    /// are still going on then.
+    pub fn occurrences_overlapping(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<Event> {
+        let duration = TimeDelta::from_std(self.duration).unwrap_or(TimeDelta::zero());
+
+        self.occurrences_between(from - duration, to)
+            .into_iter()
+            .filter(|x| x.start.to_utc() >= from || x.start.to_utc() + duration > from)
+            .collect()
+    }
 }
@@ -0,0 +1,533 @@
+//! Just enough of iCalendar (RFC 5545) to move events in and out of other calendars.
+//!
+//! Times are written with Olson `TZID`s and no `VTIMEZONE` components, which all the clients we
+//! care about understand.
+
+use std::{str::FromStr, time::Duration};
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use thiserror::Error;
+
+use crate::todo::{Priority, Status, Todo};
+
+use super::{
+    event::{Event, Id},
+    rrule::{RRule, RRuleError},
+};
+
+const MAX_LINE_LENGTH: usize = 75;
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Line {0} is not a valid content line")]
+    InvalidLine(usize),
+    #[error("An event is missing {0}")]
+    MissingProperty(&'static str),
+    #[error("Invalid {0}: \"{1}\"")]
+    InvalidValue(&'static str, String),
+    #[error("Unknown timezone \"{0}\"")]
+    UnknownTimezone(String),
+    #[error("Invalid recurrence: {0}")]
+    Recurrence(#[from] RRuleError),
+    #[error("{0} was never closed")]
+    Unterminated(String),
+}
+
+/// An event read from an iCalendar file, not stored anywhere yet.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedEvent {
+    pub uid: String,
+    pub start: DateTime<Tz>,
+    pub duration: Duration,
+    pub title: String,
+    pub recurrence: Option<RRule>,
+    pub exdates: Vec<NaiveDateTime>,
+}
+
+impl ImportedEvent {
+    pub fn into_event(self, id: Id) -> Event {
+        let mut event = Event::new(id, self.start, self.duration, self.title);
+
+        event.set_uid(self.uid);
+        event.set_recurrence(self.recurrence);
+
+        for exdate in self.exdates {
+            event.add_exdate(exdate);
+        }
+
+        event
+    }
+}
+
+struct Property {
+    name: String,
+    parameters: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Reads all `VEVENT`s from an iCalendar file. Times without a timezone are taken to be in
+/// `default_timezone`, and so are UTC times (converted), so they show up in local time.
+///
+/// Overrides of single occurrences (events with a `RECURRENCE-ID`) are skipped.
+pub fn parse(input: &str, default_timezone: Tz) -> Result<Vec<ImportedEvent>, Error> {
+    let mut result = vec![];
+    let mut components: Vec<String> = vec![];
+    let mut properties: Vec<Property> = vec![];
+
+    for (number, line) in unfold(input) {
+        let property = parse_line(&line).ok_or(Error::InvalidLine(number))?;
+
+        match property.name.as_str() {
+            "BEGIN" => components.push(property.value.to_uppercase()),
+            "END" => {
+                let closed = components.pop();
+
+                if closed.as_deref() == Some("VEVENT") {
+                    let event_properties = std::mem::take(&mut properties);
+
+                    if !event_properties.iter().any(|x| x.name == "RECURRENCE-ID") {
+                        result.push(build_event(&event_properties, default_timezone)?);
+                    }
+                }
+            }
+            _ if components.last().map(String::as_str) == Some("VEVENT") => {
+                properties.push(property);
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(component) = components.pop() {
+        return Err(Error::Unterminated(component));
+    }
+
+    Ok(result)
+}
+
+/// Writes the events and the todos that have a deadline (as `VTODO`s) into an iCalendar file.
+pub fn export(events: &[Event], todos: &[Todo], now: DateTime<Utc>) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//ramona//ras//EN".to_string(),
+    ];
+    let timestamp = format!("DTSTAMP:{}", now.format("%Y%m%dT%H%M%SZ"));
+
+    for event in events {
+        lines.push("BEGIN:VEVENT".to_string());
+        lines.push(format!("UID:{}", escape(&event.uid())));
+        lines.push(timestamp.clone());
+        lines.push(format_date_time("DTSTART", event.start()));
+        lines.push(format!("DURATION:PT{}S", event.duration().as_secs()));
+        lines.push(format!("SUMMARY:{}", escape(event.title())));
+
+        if let Some(recurrence) = event.recurrence() {
+            lines.push(format!("RRULE:{recurrence}"));
+        }
+
+        for exdate in event.exdates() {
+            lines.push(format!(
+                "EXDATE;TZID={}:{}",
+                event.start().timezone().name(),
+                exdate.format("%Y%m%dT%H%M%S")
+            ));
+        }
+
+        lines.push("END:VEVENT".to_string());
+    }
+
+    for todo in todos {
+        let Some(deadline) = todo.deadline() else {
+            continue;
+        };
+
+        lines.push("BEGIN:VTODO".to_string());
+        lines.push(format!("UID:todo-{}@ras", todo.id().0));
+        lines.push(timestamp.clone());
+        lines.push(format_date_time("DUE", deadline));
+        lines.push(format!("SUMMARY:{}", escape(todo.title())));
+        lines.push(format!(
+            "PRIORITY:{}",
+            match todo.priority() {
+                Priority::High => 1,
+                Priority::Medium => 5,
+                Priority::Low => 9,
+            }
+        ));
+        lines.push(format!(
+            "STATUS:{}",
+            match todo.status() {
+                Status::Todo => "NEEDS-ACTION",
+                Status::Doing => "IN-PROCESS",
+                Status::Done => "COMPLETED",
+            }
+        ));
+        lines.push("END:VTODO".to_string());
+    }
+
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().fold(String::new(), |mut output, line| {
+        fold(&mut output, line);
+
+        output
+    })
+}
+
+fn build_event(properties: &[Property], default_timezone: Tz) -> Result<ImportedEvent, Error> {
+    let find = |name| properties.iter().find(|x| x.name == name);
+
+    let uid = find("UID")
+        .ok_or(Error::MissingProperty("UID"))?
+        .value
+        .clone();
+    let start_property = find("DTSTART").ok_or(Error::MissingProperty("DTSTART"))?;
+    let (start, all_day) = parse_date_time(start_property, default_timezone)?;
+
+    let duration = if let Some(end) = find("DTEND") {
+        let (end, _) = parse_date_time(end, default_timezone)?;
+
+        (end - start)
+            .to_std()
+            .map_err(|_| Error::InvalidValue("DTEND", end.to_string()))?
+    } else if let Some(duration) = find("DURATION") {
+        parse_duration(&duration.value)
+            .ok_or_else(|| Error::InvalidValue("DURATION", duration.value.clone()))?
+    } else if all_day {
+        Duration::from_secs(24 * 60 * 60)
+    } else {
+        Duration::ZERO
+    };
+
+    let recurrence = find("RRULE")
+        .map(|x| RRule::from_str(&x.value))
+        .transpose()?;
+
+    let mut exdates = vec![];
+    for property in properties.iter().filter(|x| x.name == "EXDATE") {
+        for value in property.value.split(',') {
+            let exdate = Property {
+                name: property.name.clone(),
+                parameters: property.parameters.clone(),
+                value: value.to_string(),
+            };
+            let (exdate, _) = parse_date_time(&exdate, default_timezone)?;
+
+            exdates.push(exdate.with_timezone(&start.timezone()).naive_local());
+        }
+    }
+
+    Ok(ImportedEvent {
+        uid,
+        start,
+        duration,
+        title: find("SUMMARY")
+            .map(|x| unescape(&x.value))
+            .unwrap_or_default(),
+        recurrence,
+        exdates,
+    })
+}
+
+// Returns the parsed time and whether it was a plain date
+fn parse_date_time(
+    property: &Property,
+    default_timezone: Tz,
+) -> Result<(DateTime<Tz>, bool), Error> {
+    let invalid = || Error::InvalidValue("date", property.value.clone());
+
+    let timezone = match property.parameter("TZID") {
+        Some(name) => Tz::from_str(name).map_err(|_| Error::UnknownTimezone(name.to_string()))?,
+        None => default_timezone,
+    };
+
+    if let Some(utc) = property.value.strip_suffix('Z') {
+        let utc = NaiveDateTime::parse_from_str(utc, "%Y%m%dT%H%M%S").map_err(|_| invalid())?;
+
+        return Ok((utc.and_utc().with_timezone(&timezone), false));
+    }
+
+    let (local, all_day) = if property.value.len() == 8 {
+        let date = NaiveDate::parse_from_str(&property.value, "%Y%m%d").map_err(|_| invalid())?;
+
+        (date.and_hms_opt(0, 0, 0).ok_or_else(invalid)?, true)
+    } else {
+        (
+            NaiveDateTime::parse_from_str(&property.value, "%Y%m%dT%H%M%S")
+                .map_err(|_| invalid())?,
+            false,
+        )
+    };
+
+    let date_time = timezone
+        .from_local_datetime(&local)
+        .earliest()
+        .ok_or_else(invalid)?;
+
+    Ok((date_time, all_day))
+}
+
+// ISO 8601 durations as used by RFC 5545, e.g. `P1W`, `P1DT2H`, `PT15M`
+fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.strip_prefix('+').unwrap_or(value).strip_prefix('P')?;
+
+    let mut seconds = 0;
+    let mut number = String::new();
+    let mut in_time = false;
+
+    for c in value.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => in_time = true,
+            unit => {
+                let amount: u64 = std::mem::take(&mut number).parse().ok()?;
+                let multiplier = match (unit, in_time) {
+                    ('W', false) => 7 * 24 * 60 * 60,
+                    ('D', false) => 24 * 60 * 60,
+                    ('H', true) => 60 * 60,
+                    ('M', true) => 60,
+                    ('S', true) => 1,
+                    _ => return None,
+                };
+
+                seconds += amount * multiplier;
+            }
+        }
+    }
+
+    number.is_empty().then_some(Duration::from_secs(seconds))
+}
+
+fn format_date_time(name: &str, date_time: DateTime<Tz>) -> String {
+    format!(
+        "{name};TZID={}:{}",
+        date_time.timezone().name(),
+        date_time.format("%Y%m%dT%H%M%S")
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n' | 'N') => result.push('\n'),
+            Some(other) => result.push(other),
+            None => {}
+        }
+    }
+
+    result
+}
+
+// Lines longer than 75 octets are split, with every continuation line starting with a space
+fn fold(output: &mut String, line: &str) {
+    let mut length = 0;
+
+    for c in line.chars() {
+        if length + c.len_utf8() > MAX_LINE_LENGTH {
+            output.push_str("\r\n ");
+            length = 1;
+        }
+
+        output.push(c);
+        length += c.len_utf8();
+    }
+
+    output.push_str("\r\n");
+}
+
+// Joins folded lines back together, keeping the number of the line each one started on
+fn unfold(input: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = vec![];
+
+    for (number, line) in input.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some((_, last))) => last.push_str(continuation),
+            _ if line.is_empty() => {}
+            _ => lines.push((number + 1, line.to_string())),
+        }
+    }
+
+    lines
+}
+
+// NAME;PARAM=VALUE;PARAM="QUOTED:VALUE":VALUE
+fn parse_line(line: &str) -> Option<Property> {
+    let mut in_quotes = false;
+    let mut separators = vec![];
+    let mut value_start = None;
+
+    for (index, c) in line.char_indices() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => separators.push(index),
+            ':' if !in_quotes => {
+                value_start = Some(index);
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    let value_start = value_start?;
+    let name_end = separators.first().copied().unwrap_or(value_start);
+    separators.push(value_start);
+
+    let parameters = separators
+        .windows(2)
+        .map(|x| {
+            let (key, value) = line[x[0] + 1..x[1]].split_once('=')?;
+
+            Some((key.to_uppercase(), value.trim_matches('"').to_string()))
+        })
+        .collect::<Option<_>>()?;
+
+    let name = line[..name_end].to_uppercase();
+    if name.is_empty() {
+        return None;
+    }
+
+    Some(Property {
+        name,
+        parameters,
+        value: line[value_start + 1..].to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use chrono_tz::Europe::{Berlin, London};
+
+    use crate::todo::Id as TodoId;
+
+    use super::*;
+
+    #[test]
+    fn can_parse_events() {
+        let input = "BEGIN:VCALENDAR\r\n\
+            VERSION:2.0\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:abc@example.com\r\n\
+            DTSTART;TZID=Europe/London:20240501T100000\r\n\
+            DTEND;TZID=Europe/London:20240501T113000\r\n\
+            SUMMARY:Planning\\, weekly\r\n\
+            RRULE:FREQ=WEEKLY;BYDAY=WE\r\n\
+            EXDATE;TZID=Europe/London:20240508T100000,20240515T100000\r\n\
+            BEGIN:VALARM\r\n\
+            TRIGGER:-PT15M\r\n\
+            END:VALARM\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:abc@example.com\r\n\
+            RECURRENCE-ID;TZID=Europe/London:20240522T100000\r\n\
+            DTSTART;TZID=Europe/London:20240522T110000\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:holiday\r\n\
+            DTSTART;VALUE=DATE:20240509\r\n\
+            SUMMARY:A very long summary that definitely does not fit into a single line of \r\n \
+            an iCalendar file\r\n\
+            END:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+
+        let result = parse(input, Berlin).unwrap();
+
+        assert_eq!(2, result.len());
+
+        assert_eq!("abc@example.com", result[0].uid);
+        assert_eq!("Planning, weekly", result[0].title);
+        assert_eq!(
+            London.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap(),
+            result[0].start
+        );
+        assert_eq!(Duration::from_secs(90 * 60), result[0].duration);
+        assert_eq!(
+            Some("FREQ=WEEKLY;BYDAY=WE".parse().unwrap()),
+            result[0].recurrence
+        );
+        assert_eq!(2, result[0].exdates.len());
+
+        assert_eq!(
+            Berlin.with_ymd_and_hms(2024, 5, 9, 0, 0, 0).unwrap(),
+            result[1].start
+        );
+        assert_eq!(Duration::from_secs(24 * 60 * 60), result[1].duration);
+        assert_eq!(
+            "A very long summary that definitely does not fit into a single line of an iCalendar file",
+            result[1].title
+        );
+    }
+
+    #[test]
+    fn exported_events_can_be_imported() {
+        let mut event = Event::new(
+            Id(7),
+            Berlin.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap(),
+            Duration::from_secs(15 * 60),
+            "Standup; with a rather long title, so that it has to be folded somewhere".to_string(),
+        );
+        event.set_recurrence(Some("FREQ=DAILY;COUNT=10".parse().unwrap()));
+        event.add_exdate(
+            NaiveDate::from_ymd_opt(2024, 5, 3)
+                .unwrap()
+                .and_hms_opt(10, 0, 0)
+                .unwrap(),
+        );
+
+        let todo = Todo::new(
+            TodoId(3),
+            "Taxes".to_string(),
+            Priority::High,
+            vec![],
+            Duration::from_secs(60),
+            Some(Berlin.with_ymd_and_hms(2024, 7, 31, 23, 59, 0).unwrap()),
+        );
+
+        let output = export(&[event.clone()], &[todo], Utc::now());
+
+        assert!(output.lines().all(|x| x.len() <= MAX_LINE_LENGTH + 1));
+        assert!(output.contains("BEGIN:VTODO\r\nUID:todo-3@ras\r\n"));
+        assert!(output.contains("DUE;TZID=Europe/Berlin:20240731T235900\r\n"));
+
+        let imported = parse(&output, London).unwrap();
+
+        assert_eq!(
+            vec![ImportedEvent {
+                uid: "event-7@ras".to_string(),
+                start: event.start(),
+                duration: event.duration(),
+                title: event.title().to_string(),
+                recurrence: event.recurrence().cloned(),
+                exdates: event.exdates().to_vec(),
+            }],
+            imported
+        );
+    }
+}
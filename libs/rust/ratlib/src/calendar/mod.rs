@@ -1 +1,4 @@
+pub mod agenda;
 pub mod event;
+pub mod ical;
+pub mod rrule;
@@ -0,0 +1,487 @@
+use std::{fmt::Display, str::FromStr};
+
+use chrono::{
+    DateTime, Datelike, Months, NaiveDate, NaiveDateTime, TimeDelta, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+#[derive(Debug, Error)]
+pub enum RRuleError {
+    #[error("Expected KEY=VALUE, got \"{0}\"")]
+    InvalidPart(String),
+    #[error("Unsupported property \"{0}\"")]
+    UnsupportedProperty(String),
+    #[error("Invalid value for {0}: \"{1}\"")]
+    InvalidValue(&'static str, String),
+    #[error("FREQ is required")]
+    MissingFrequency,
+    #[error("UNTIL and COUNT can't be used together")]
+    UntilAndCount,
+    #[error("BYDAY can't be used with FREQ=YEARLY")]
+    ByDayWithYearly,
+}
+
+/// A subset of RFC 5545 recurrence rules: `FREQ` (`DAILY`, `WEEKLY`, `MONTHLY` or `YEARLY`),
+/// `INTERVAL`, `BYDAY` (plain weekdays, no ordinals like `1MO`), `UNTIL` (in UTC) and `COUNT`.
+/// Weeks start on Monday.
+///
+/// Occurrences are computed on the local calendar of the event's timezone, so a meeting at 9:00
+/// stays at 9:00 local time across DST changes. Days that don't exist (e.g. the 31st of a
+/// 30-day month) are skipped, as the RFC requires.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RRule {
+    frequency: Frequency,
+    interval: u32,
+    by_day: Vec<Weekday>,
+    until: Option<DateTime<Utc>>,
+    count: Option<u32>,
+}
+
+impl RRule {
+    pub fn new(frequency: Frequency) -> Self {
+        Self {
+            frequency,
+            interval: 1,
+            by_day: vec![],
+            until: None,
+            count: None,
+        }
+    }
+
+    pub fn frequency(&self) -> Frequency {
+        self.frequency
+    }
+
+    pub fn interval(&self) -> u32 {
+        self.interval
+    }
+
+    pub fn by_day(&self) -> &[Weekday] {
+        &self.by_day
+    }
+
+    pub fn until(&self) -> Option<DateTime<Utc>> {
+        self.until
+    }
+
+    pub fn count(&self) -> Option<u32> {
+        self.count
+    }
+
+    /// Finds all occurrences of a series starting at `start` that begin in `[from, to)`. `COUNT`
+    /// is counted from `start`, not from `from`.
+    pub fn occurrences_between(
+        &self,
+        start: DateTime<Tz>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Vec<DateTime<Tz>> {
+        let timezone = start.timezone();
+        let start_date = start.date_naive();
+        let time = start.time();
+        let last_date = to.with_timezone(&timezone).date_naive();
+
+        let mut result = vec![];
+        let mut seen = 0;
+
+        for period in 0.. {
+            let Some((first_date, dates)) = self.period(start_date, period) else {
+                break;
+            };
+
+            if first_date > last_date {
+                break;
+            }
+
+            for date in dates.into_iter().filter(|x| *x >= start_date) {
+                let Some(occurrence) = resolve(timezone, date.and_time(time)) else {
+                    continue;
+                };
+
+                if self.until.is_some_and(|until| occurrence.to_utc() > until)
+                    || self.count.is_some_and(|count| seen >= count)
+                {
+                    return result;
+                }
+
+                seen += 1;
+
+                if occurrence.to_utc() >= from && occurrence.to_utc() < to {
+                    result.push(occurrence);
+                }
+            }
+        }
+
+        result
+    }
+
+    // Returns the first day of the period with the given index, and the candidate dates in it
+    fn period(&self, start_date: NaiveDate, index: u32) -> Option<(NaiveDate, Vec<NaiveDate>)> {
+        let offset = index.checked_mul(self.interval)?;
+
+        match self.frequency {
+            Frequency::Daily => {
+                let date =
+                    start_date.checked_add_signed(TimeDelta::try_days(i64::from(offset))?)?;
+                let dates = if self.by_day.is_empty() || self.by_day.contains(&date.weekday()) {
+                    vec![date]
+                } else {
+                    vec![]
+                };
+
+                Some((date, dates))
+            }
+            Frequency::Weekly => {
+                let monday = start_date
+                    .checked_sub_signed(TimeDelta::try_days(i64::from(
+                        start_date.weekday().num_days_from_monday(),
+                    ))?)?
+                    .checked_add_signed(TimeDelta::try_weeks(i64::from(offset))?)?;
+
+                let mut days = if self.by_day.is_empty() {
+                    vec![start_date.weekday()]
+                } else {
+                    self.by_day.clone()
+                };
+                days.sort_by_key(Weekday::num_days_from_monday);
+                days.dedup();
+
+                let dates = days
+                    .into_iter()
+                    .filter_map(|day| {
+                        monday.checked_add_signed(TimeDelta::try_days(i64::from(
+                            day.num_days_from_monday(),
+                        ))?)
+                    })
+                    .collect();
+
+                Some((monday, dates))
+            }
+            Frequency::Monthly => {
+                let first_of_month = start_date
+                    .with_day(1)?
+                    .checked_add_months(Months::new(offset))?;
+
+                let dates = if self.by_day.is_empty() {
+                    first_of_month
+                        .with_day(start_date.day())
+                        .into_iter()
+                        .collect()
+                } else {
+                    first_of_month
+                        .iter_days()
+                        .take_while(|x| x.month() == first_of_month.month())
+                        .filter(|x| self.by_day.contains(&x.weekday()))
+                        .collect()
+                };
+
+                Some((first_of_month, dates))
+            }
+            Frequency::Yearly => {
+                let year = start_date.year().checked_add(i32::try_from(offset).ok()?)?;
+
+                Some((
+                    NaiveDate::from_ymd_opt(year, 1, 1)?,
+                    NaiveDate::from_ymd_opt(year, start_date.month(), start_date.day())
+                        .into_iter()
+                        .collect(),
+                ))
+            }
+        }
+    }
+}
+
+// Local times that fall into a DST gap don't exist, so we move them forward by the size of the
+// gap (an hour in every zone we care about). Ambiguous times pick the earlier instant.
+fn resolve(timezone: Tz, local: NaiveDateTime) -> Option<DateTime<Tz>> {
+    timezone.from_local_datetime(&local).earliest().or_else(|| {
+        timezone
+            .from_local_datetime(&(local + TimeDelta::try_hours(1)?))
+            .earliest()
+    })
+}
+
+fn weekday_code(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+fn parse_weekday(code: &str) -> Option<Weekday> {
+    match code {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn parse_until(value: &str) -> Option<DateTime<Utc>> {
+    if let Some(date_time) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(date_time, "%Y%m%dT%H%M%S")
+            .ok()
+            .map(|x| x.and_utc());
+    }
+
+    // A plain date includes the whole day
+    NaiveDate::parse_from_str(value, "%Y%m%d")
+        .ok()
+        .and_then(|x| x.and_hms_opt(23, 59, 59))
+        .map(|x| x.and_utc())
+}
+
+impl FromStr for RRule {
+    type Err = RRuleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let s = s.strip_prefix("RRULE:").unwrap_or(s);
+
+        let mut frequency = None;
+        let mut rule = Self::new(Frequency::Daily);
+
+        for part in s.split(';').filter(|x| !x.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| RRuleError::InvalidPart(part.to_string()))?;
+            let invalid = |name| RRuleError::InvalidValue(name, value.to_string());
+
+            match key.to_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        _ => return Err(invalid("FREQ")),
+                    });
+                }
+                "INTERVAL" => {
+                    rule.interval = value
+                        .parse()
+                        .ok()
+                        .filter(|x| *x > 0)
+                        .ok_or_else(|| invalid("INTERVAL"))?;
+                }
+                "BYDAY" => {
+                    rule.by_day = value
+                        .split(',')
+                        .map(|x| parse_weekday(&x.to_uppercase()))
+                        .collect::<Option<_>>()
+                        .ok_or_else(|| invalid("BYDAY"))?;
+                }
+                "UNTIL" => rule.until = Some(parse_until(value).ok_or_else(|| invalid("UNTIL"))?),
+                "COUNT" => rule.count = Some(value.parse().map_err(|_| invalid("COUNT"))?),
+                // Our weeks always start on Monday, which is also the RFC's default
+                "WKST" if value.eq_ignore_ascii_case("MO") => {}
+                _ => return Err(RRuleError::UnsupportedProperty(part.to_string())),
+            }
+        }
+
+        rule.frequency = frequency.ok_or(RRuleError::MissingFrequency)?;
+
+        if rule.until.is_some() && rule.count.is_some() {
+            return Err(RRuleError::UntilAndCount);
+        }
+
+        if rule.frequency == Frequency::Yearly && !rule.by_day.is_empty() {
+            return Err(RRuleError::ByDayWithYearly);
+        }
+
+        Ok(rule)
+    }
+}
+
+impl Display for RRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let frequency = match self.frequency {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        };
+
+        write!(f, "FREQ={frequency}")?;
+
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+
+        if !self.by_day.is_empty() {
+            let days = self
+                .by_day
+                .iter()
+                .map(|x| weekday_code(*x))
+                .collect::<Vec<_>>()
+                .join(",");
+
+            write!(f, ";BYDAY={days}")?;
+        }
+
+        if let Some(until) = self.until {
+            write!(f, ";UNTIL={}", until.format("%Y%m%dT%H%M%SZ"))?;
+        }
+
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={count}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl TryFrom<String> for RRule {
+    type Error = RRuleError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<RRule> for String {
+    fn from(value: RRule) -> Self {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Timelike, Utc};
+    use chrono_tz::Europe::Berlin;
+
+    use super::*;
+
+    fn occurrences(rule: &str, start: DateTime<Tz>, days: i64) -> Vec<DateTime<Tz>> {
+        rule.parse::<RRule>().unwrap().occurrences_between(
+            start,
+            start.to_utc(),
+            start.to_utc() + TimeDelta::try_days(days).unwrap(),
+        )
+    }
+
+    #[test]
+    fn can_roundtrip_through_string() {
+        let rule: RRule = "RRULE:FREQ=weekly;INTERVAL=2;BYDAY=MO,we;UNTIL=20240601T120000Z;WKST=MO"
+            .parse()
+            .unwrap();
+
+        assert_eq!(Frequency::Weekly, rule.frequency());
+        assert_eq!(2, rule.interval());
+        assert_eq!(&[Weekday::Mon, Weekday::Wed], rule.by_day());
+        assert_eq!(
+            "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE;UNTIL=20240601T120000Z",
+            rule.to_string()
+        );
+        assert_eq!(rule, rule.to_string().parse().unwrap());
+
+        assert!(matches!(
+            "FREQ=DAILY;BYMONTH=1".parse::<RRule>(),
+            Err(RRuleError::UnsupportedProperty(_))
+        ));
+        assert!(matches!(
+            "FREQ=DAILY;COUNT=2;UNTIL=20240101".parse::<RRule>(),
+            Err(RRuleError::UntilAndCount)
+        ));
+        assert!(matches!(
+            "INTERVAL=2".parse::<RRule>(),
+            Err(RRuleError::MissingFrequency)
+        ));
+    }
+
+    #[test]
+    fn weekly_with_interval_and_days() {
+        // Wednesday
+        let start = Berlin.with_ymd_and_hms(2024, 5, 8, 10, 0, 0).unwrap();
+
+        let result = occurrences("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,WE,FR", start, 21);
+
+        assert_eq!(
+            vec![
+                Berlin.with_ymd_and_hms(2024, 5, 8, 10, 0, 0).unwrap(),
+                Berlin.with_ymd_and_hms(2024, 5, 10, 10, 0, 0).unwrap(),
+                Berlin.with_ymd_and_hms(2024, 5, 20, 10, 0, 0).unwrap(),
+                Berlin.with_ymd_and_hms(2024, 5, 22, 10, 0, 0).unwrap(),
+                Berlin.with_ymd_and_hms(2024, 5, 24, 10, 0, 0).unwrap(),
+            ],
+            result
+        );
+    }
+
+    #[test]
+    fn keeps_local_time_across_dst() {
+        let start = Berlin.with_ymd_and_hms(2024, 3, 29, 9, 0, 0).unwrap();
+
+        let result = occurrences("FREQ=DAILY", start, 3);
+
+        assert_eq!(4, result.len());
+        assert!(result.iter().all(|x| x.hour() == 9));
+        assert_eq!(8, result[0].to_utc().hour());
+        assert_eq!(7, result[3].to_utc().hour());
+    }
+
+    #[test]
+    fn moves_occurrences_out_of_dst_gap() {
+        let start = Berlin.with_ymd_and_hms(2024, 3, 30, 2, 30, 0).unwrap();
+
+        let result = occurrences("FREQ=DAILY;COUNT=3", start, 10);
+
+        assert_eq!(
+            vec![
+                Berlin.with_ymd_and_hms(2024, 3, 30, 2, 30, 0).unwrap(),
+                Berlin.with_ymd_and_hms(2024, 3, 31, 3, 30, 0).unwrap(),
+                Berlin.with_ymd_and_hms(2024, 4, 1, 2, 30, 0).unwrap(),
+            ],
+            result
+        );
+    }
+
+    #[test]
+    fn monthly_skips_missing_days() {
+        let start = Berlin.with_ymd_and_hms(2024, 1, 31, 18, 0, 0).unwrap();
+
+        let result: Vec<_> = occurrences("FREQ=MONTHLY", start, 200)
+            .into_iter()
+            .map(|x| x.month())
+            .collect();
+
+        assert_eq!(vec![1, 3, 5, 7], result);
+    }
+
+    #[test]
+    fn count_and_until_limit_the_series() {
+        let start = Berlin.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap();
+        let rule: RRule = "FREQ=DAILY;COUNT=5".parse().unwrap();
+
+        // COUNT is counted from the start of the series, not the start of the range
+        let result = rule.occurrences_between(
+            start,
+            Utc.with_ymd_and_hms(2024, 5, 3, 0, 0, 0).unwrap(),
+            Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap(),
+        );
+        assert_eq!(3, result.len());
+
+        let result = occurrences("FREQ=YEARLY;UNTIL=20270501", start, 366 * 10);
+        assert_eq!(4, result.len());
+    }
+}
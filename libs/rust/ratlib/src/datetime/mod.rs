@@ -14,14 +14,14 @@ where
         return serialize_date_time_tz(dt, se);
     }
 
-    Ok(se.serialize_none()?)
+    se.serialize_none()
 }
 
 pub fn deserialize_date_time_tz_option<'de, D>(de: D) -> Result<Option<DateTime<Tz>>, D::Error>
 where
     D: Deserializer<'de>,
 {
-    Ok(de.deserialize_option(OptionDateTimeTzVisitor)?)
+    de.deserialize_option(OptionDateTimeTzVisitor)
 }
 
 struct OptionDateTimeTzVisitor;
@@ -45,6 +45,14 @@ impl<'a> Visitor<'a> for OptionDateTimeTzVisitor {
     {
         Ok(None)
     }
+
+    // Buffered (e.g. `#[serde(flatten)]`) nulls come as unit instead of none
+    fn visit_unit<E>(self) -> Result<Self::Value, E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(None)
+    }
 }
 
 pub fn serialize_date_time_tz<S>(date_time: &DateTime<Tz>, se: S) -> Result<S::Ok, S::Error>
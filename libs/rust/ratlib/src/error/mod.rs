@@ -0,0 +1,124 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    NotFound,
+    InvalidRequest,
+    Conflict,
+    Unauthorized,
+    Forbidden,
+    Internal,
+}
+
+impl ErrorKind {
+    pub fn status_code(self) -> u16 {
+        match self {
+            ErrorKind::NotFound => 404,
+            ErrorKind::InvalidRequest => 400,
+            ErrorKind::Conflict => 409,
+            ErrorKind::Unauthorized => 401,
+            ErrorKind::Forbidden => 403,
+            ErrorKind::Internal => 500,
+        }
+    }
+
+    fn from_status_code(status: u16) -> Self {
+        match status {
+            404 => ErrorKind::NotFound,
+            409 => ErrorKind::Conflict,
+            401 => ErrorKind::Unauthorized,
+            403 => ErrorKind::Forbidden,
+            400..=499 => ErrorKind::InvalidRequest,
+            _ => ErrorKind::Internal,
+        }
+    }
+}
+
+/// The body of every error response from ras. Internal errors only carry a correlation ID that
+/// can be looked up in the server logs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Error)]
+pub struct ApiError {
+    pub kind: ErrorKind,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
+}
+
+impl ApiError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            correlation_id: None,
+        }
+    }
+
+    /// Reads an error response. Not everything that fails is a handler returning an `ApiError`
+    /// (e.g. requests axum itself rejects), so other bodies are kept as the message.
+    pub fn from_response(status: u16, body: &str) -> Self {
+        serde_json::from_str(body).unwrap_or_else(|_| {
+            Self::new(ErrorKind::from_status_code(status), body.trim().to_string())
+        })
+    }
+
+    /// Passes successful responses through and turns the rest into an `ApiError`
+    pub async fn check(response: reqwest::Response) -> Result<reqwest::Response, ApiError> {
+        let status = response.status();
+
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        Err(Self::from_response(
+            status.as_u16(),
+            &response.text().await.unwrap_or_default(),
+        ))
+    }
+}
+
+impl Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+
+        if let Some(correlation_id) = &self.correlation_id {
+            write!(f, " (correlation ID: {correlation_id})")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_read_error_responses() {
+        let error = ApiError {
+            kind: ErrorKind::Internal,
+            message: "Internal server error".to_string(),
+            correlation_id: Some("abc".to_string()),
+        };
+
+        assert_eq!(
+            error,
+            ApiError::from_response(500, &serde_json::to_string(&error).unwrap())
+        );
+        assert_eq!(
+            "Internal server error (correlation ID: abc)",
+            error.to_string()
+        );
+
+        assert_eq!(
+            ApiError::new(
+                ErrorKind::InvalidRequest,
+                "Failed to parse the request body"
+            ),
+            ApiError::from_response(422, "Failed to parse the request body\n")
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    calendar::event,
+    todo::{self, client::ClientError, Status},
+};
+
+/// Something that changed on ras, as published on `GET /events/stream`. Only says what changed,
+/// subscribers fetch whatever they show again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Change {
+    TodoCreated(todo::Id),
+    TodoEdited(todo::Id),
+    TodoStatusChanged(todo::Id, Status),
+    TodoArchived(todo::Id),
+    TodoDeleted(todo::Id),
+    // Undoing can touch any number of todos
+    TodosReverted,
+    EventAdded(event::Id),
+    EventEdited(event::Id),
+    EventDeleted(event::Id),
+    EventsImported,
+    HostUpdated(String),
+    // The subscriber fell behind and missed some changes, it should assume anything changed
+    Missed,
+}
+
+impl Change {
+    /// Whether todos might look different after this change
+    pub fn affects_todos(&self) -> bool {
+        matches!(
+            self,
+            Change::TodoCreated(_)
+                | Change::TodoEdited(_)
+                | Change::TodoStatusChanged(..)
+                | Change::TodoArchived(_)
+                | Change::TodoDeleted(_)
+                | Change::TodosReverted
+                | Change::Missed
+        )
+    }
+
+    /// Whether the herd might look different after this change
+    pub fn affects_herd(&self) -> bool {
+        matches!(self, Change::HostUpdated(_) | Change::Missed)
+    }
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::TodoCreated(id) => write!(f, "todo #{id} created"),
+            Change::TodoEdited(id) => write!(f, "todo #{id} edited"),
+            Change::TodoStatusChanged(id, status) => write!(f, "todo #{id} moved to {status:?}"),
+            Change::TodoArchived(id) => write!(f, "todo #{id} archived"),
+            Change::TodoDeleted(id) => write!(f, "todo #{id} deleted"),
+            Change::TodosReverted => write!(f, "todos reverted"),
+            Change::EventAdded(id) => write!(f, "event {id} added"),
+            Change::EventEdited(id) => write!(f, "event {id} edited"),
+            Change::EventDeleted(id) => write!(f, "event {id} deleted"),
+            Change::EventsImported => write!(f, "events imported"),
+            Change::HostUpdated(hostname) => write!(f, "{hostname} updated"),
+            Change::Missed => write!(f, "missed some changes"),
+        }
+    }
+}
+
+/// A subscription to the changes on ras, see [`todo::client::Client::watch`]
+pub struct Watch {
+    response: reqwest::Response,
+    // Raw bytes, as a chunk can end in the middle of a character
+    buffer: Vec<u8>,
+}
+
+impl Watch {
+    pub(crate) fn new(response: reqwest::Response) -> Self {
+        Self {
+            response,
+            buffer: Vec::new(),
+        }
+    }
+
+    /// Waits for the next change, returns `None` once ras closes the stream
+    pub async fn next(&mut self) -> Result<Option<Change>, ClientError> {
+        loop {
+            while let Some(data) = take_event(&mut self.buffer) {
+                // Anything that's not a change is either a keep-alive or from a newer ras
+                if let Ok(change) = serde_json::from_str(&data) {
+                    return Ok(Some(change));
+                }
+            }
+
+            match self.response.chunk().await? {
+                Some(chunk) => self.buffer.extend_from_slice(&chunk),
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+// Removes the first complete server-sent event from `buffer` and returns its data. Events
+// without data (like keep-alive comments) come back as empty strings. Only complete events are
+// decoded, so characters split across chunks stay intact.
+fn take_event(buffer: &mut Vec<u8>) -> Option<String> {
+    let (end, separator) = [&b"\r\n\r\n"[..], b"\n\n"]
+        .into_iter()
+        .filter_map(|separator| {
+            let end = buffer
+                .windows(separator.len())
+                .position(|x| x == separator)?;
+
+            Some((end, separator.len()))
+        })
+        .min()?;
+
+    let event: Vec<_> = buffer.drain(..end + separator).collect();
+    let event = String::from_utf8_lossy(&event[..end]);
+
+    let data: Vec<_> = event
+        .lines()
+        .filter_map(|x| x.strip_prefix("data:"))
+        .map(|x| x.strip_prefix(' ').unwrap_or(x))
+        .collect();
+
+    Some(data.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_the_stream_into_events() {
+        let mut buffer = b":\n\ndata: {\"TodoCreated\":1}\r\n\r\ndata:\"Tod".to_vec();
+
+        assert_eq!(Some(String::new()), take_event(&mut buffer));
+        assert_eq!(
+            Change::TodoCreated(todo::Id(1)),
+            serde_json::from_str(&take_event(&mut buffer).unwrap()).unwrap()
+        );
+        assert_eq!(None, take_event(&mut buffer));
+
+        buffer.extend_from_slice(b"osReverted\"\n\n");
+        assert_eq!(
+            Change::TodosReverted,
+            serde_json::from_str(&take_event(&mut buffer).unwrap()).unwrap()
+        );
+    }
+
+    #[test]
+    fn keeps_characters_split_across_chunks() {
+        let event = "data: {\"HostUpdated\":\"hällewell\"}\n\n".as_bytes();
+        let split = event.iter().position(|x| !x.is_ascii()).unwrap() + 1;
+
+        let mut buffer = event[..split].to_vec();
+        assert_eq!(None, take_event(&mut buffer));
+
+        buffer.extend_from_slice(&event[split..]);
+        assert_eq!(
+            Change::HostUpdated("hällewell".to_string()),
+            serde_json::from_str(&take_event(&mut buffer).unwrap()).unwrap()
+        );
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn tells_what_a_change_affects() {
+        assert!(Change::TodoArchived(todo::Id(1)).affects_todos());
+        assert!(!Change::TodoArchived(todo::Id(1)).affects_herd());
+        assert!(!Change::EventsImported.affects_todos());
+        assert!(Change::HostUpdated("hallewell".to_string()).affects_herd());
+        assert!(!Change::HostUpdated("hallewell".to_string()).affects_todos());
+        assert!(Change::Missed.affects_todos());
+        assert!(Change::Missed.affects_herd());
+    }
+}
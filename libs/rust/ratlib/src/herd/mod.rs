@@ -1,6 +1,315 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
+/// How long a host can go without reporting to ras before it's considered offline. rad reports
+/// every minute, so this leaves room for a few missed reports.
+pub const HOST_ONLINE_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
 #[derive(Serialize, Deserialize)]
 pub struct PostHerdMachine {
     pub current_closure: String,
+    /// When rad collected the report, which is earlier than when it's received if rad had to
+    /// queue it while ras was unreachable
+    #[serde(default)]
+    pub reported_at: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    pub system: SystemReport,
+}
+
+/// What rad reports about a machine besides its closure. Everything is optional, as older
+/// versions of rad don't send it and rad leaves out what it fails to find out.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SystemReport {
+    /// The closure the machine booted into, which differs from the current one until it reboots
+    pub booted_closure: Option<String>,
+    pub kernel_version: Option<String>,
+    pub uptime: Option<Duration>,
+    /// The number of the system profile generation that's current
+    pub generation: Option<u32>,
+    pub failed_units: Vec<String>,
+    pub disks: Vec<DiskUsage>,
+    pub load_average: Option<LoadAverage>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiskUsage {
+    pub mount_point: String,
+    pub used_bytes: u64,
+    pub total_bytes: u64,
+}
+
+impl DiskUsage {
+    /// How full the disk is, from 0 to 1
+    pub fn used_fraction(&self) -> f64 {
+        if self.total_bytes == 0 {
+            return 0.0;
+        }
+
+        self.used_bytes as f64 / self.total_bytes as f64
+    }
+}
+
+/// Averaged over the last 1, 5 and 15 minutes
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LoadAverage {
+    pub one: f64,
+    pub five: f64,
+    pub fifteen: f64,
+}
+
+/// Sets the closure a machine should be running, e.g. after CI built a new one
+#[derive(Serialize, Deserialize)]
+pub struct PostDesiredClosure {
+    pub closure: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DesiredClosure {
+    pub closure: String,
+    pub set_at: DateTime<Utc>,
+}
+
+impl DesiredClosure {
+    /// The store path of the closure, without the `/nix/store/` in front of it
+    pub fn closure_name(&self) -> &str {
+        without_store_prefix(&self.closure)
+    }
+}
+
+/// Whether a host that last reported at `last_seen` is still around
+pub fn is_online(last_seen: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+    // A negative duration is just clock skew between ras and the host
+    now.signed_duration_since(last_seen)
+        .to_std()
+        .map_or(true, |x| x <= HOST_ONLINE_TIMEOUT)
+}
+
+/// A closure the machine ran, from when it first reported it until it switched to the next one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClosurePeriod {
+    pub closure: String,
+    pub started_at: DateTime<Utc>,
+    /// Still running it if there's none
+    pub ended_at: Option<DateTime<Utc>>,
+}
+
+impl ClosurePeriod {
+    /// The store path of the closure, without the `/nix/store/` in front of it
+    pub fn closure_name(&self) -> &str {
+        without_store_prefix(&self.closure)
+    }
+}
+
+/// Whether a machine runs the closure it should
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Drift {
+    /// Nobody said which closure the machine should run
+    Untracked,
+    InSync,
+    Drifting,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Health {
+    Online,
+    /// Didn't report within [`HOST_ONLINE_TIMEOUT`]
+    Stale,
+}
+
+/// What a machine last reported to ras, along with what follows from it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Machine {
+    pub hostname: String,
+    pub last_seen: DateTime<Utc>,
+    pub running_closure: String,
+    /// Machines that never switched closures since they first reported don't have one
+    pub last_closure_change: Option<DateTime<Utc>>,
+    pub health: Health,
+    pub since_last_seen: Duration,
+    pub since_closure_change: Option<Duration>,
+    pub desired_closure: Option<DesiredClosure>,
+    pub drift: Drift,
+    /// How long the machine has been running something other than the desired closure, counted
+    /// from when that was set, or from when the machine switched away from it
+    pub drifting_for: Option<Duration>,
+    pub system: SystemReport,
+    /// The current closure is not the one the machine booted into
+    pub reboot_pending: bool,
+}
+
+impl Machine {
+    pub fn new(
+        hostname: String,
+        last_seen: DateTime<Utc>,
+        running_closure: String,
+        last_closure_change: Option<DateTime<Utc>>,
+        desired_closure: Option<DesiredClosure>,
+        system: SystemReport,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let since = |when: DateTime<Utc>| (now - when).to_std().unwrap_or_default();
+
+        let (drift, drifting_for) = match &desired_closure {
+            None => (Drift::Untracked, None),
+            Some(desired) if desired.closure == running_closure => (Drift::InSync, None),
+            Some(desired) => (
+                Drift::Drifting,
+                Some(since(
+                    last_closure_change.map_or(desired.set_at, |x| x.max(desired.set_at)),
+                )),
+            ),
+        };
+
+        Self {
+            reboot_pending: system
+                .booted_closure
+                .as_ref()
+                .is_some_and(|x| *x != running_closure),
+            health: if is_online(last_seen, now) {
+                Health::Online
+            } else {
+                Health::Stale
+            },
+            since_last_seen: since(last_seen),
+            since_closure_change: last_closure_change.map(since),
+            hostname,
+            last_seen,
+            running_closure,
+            last_closure_change,
+            desired_closure,
+            drift,
+            drifting_for,
+            system,
+        }
+    }
+
+    /// The store path of the running closure, without the `/nix/store/` in front of it
+    pub fn closure_name(&self) -> &str {
+        without_store_prefix(&self.running_closure)
+    }
+}
+
+fn without_store_prefix(path: &str) -> &str {
+    path.strip_prefix("/nix/store/").unwrap_or(path)
+}
+
+/// Roughly how long something took, e.g. "5m" or "3d", in the largest unit that fits
+pub fn describe_elapsed(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+
+    match seconds {
+        0..=59 => format!("{seconds}s"),
+        60..=3599 => format!("{}m", seconds / 60),
+        3600..=86399 => format!("{}h", seconds / 3600),
+        _ => format!("{}d", seconds / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn derives_health_from_last_seen() {
+        let now = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        let machine = |last_seen| {
+            Machine::new(
+                "hallewell".to_string(),
+                last_seen,
+                "/nix/store/abc-nixos-system".to_string(),
+                Some(Utc.with_ymd_and_hms(2024, 4, 30, 12, 0, 0).unwrap()),
+                None,
+                SystemReport::default(),
+                now,
+            )
+        };
+
+        let online = machine(Utc.with_ymd_and_hms(2024, 5, 1, 11, 59, 0).unwrap());
+        assert_eq!(Health::Online, online.health);
+        assert_eq!(Duration::from_secs(60), online.since_last_seen);
+        assert_eq!(
+            Some(Duration::from_secs(24 * 3600)),
+            online.since_closure_change
+        );
+
+        let stale = machine(Utc.with_ymd_and_hms(2024, 5, 1, 11, 0, 0).unwrap());
+        assert_eq!(Health::Stale, stale.health);
+
+        assert!(!online.reboot_pending);
+
+        // Clock skew
+        let ahead = machine(Utc.with_ymd_and_hms(2024, 5, 1, 12, 1, 0).unwrap());
+        assert_eq!(Health::Online, ahead.health);
+        assert_eq!(Duration::ZERO, ahead.since_last_seen);
+    }
+
+    #[test]
+    fn compares_the_running_closure_to_the_desired_one() {
+        let at = |hour| Utc.with_ymd_and_hms(2024, 5, 1, hour, 0, 0).unwrap();
+        let machine = |desired: &str, set_at, switched_at| {
+            Machine::new(
+                "hallewell".to_string(),
+                at(12),
+                "/nix/store/old".to_string(),
+                Some(at(switched_at)),
+                Some(DesiredClosure {
+                    closure: desired.to_string(),
+                    set_at: at(set_at),
+                }),
+                SystemReport::default(),
+                at(12),
+            )
+        };
+
+        let in_sync = machine("/nix/store/old", 8, 10);
+        assert_eq!(Drift::InSync, in_sync.drift);
+        assert_eq!(None, in_sync.drifting_for);
+
+        let not_deployed_yet = machine("/nix/store/new", 9, 8);
+        assert_eq!(Drift::Drifting, not_deployed_yet.drift);
+        assert_eq!(
+            Some(Duration::from_secs(3 * 3600)),
+            not_deployed_yet.drifting_for
+        );
+
+        // e.g. rolled back after the new closure got deployed
+        let switched_away = machine("/nix/store/new", 9, 11);
+        assert_eq!(Some(Duration::from_secs(3600)), switched_away.drifting_for);
+    }
+
+    #[test]
+    fn reads_reports_of_older_rad_versions() {
+        let report: PostHerdMachine =
+            serde_json::from_str(r#"{"current_closure": "/nix/store/abc-nixos-system"}"#).unwrap();
+
+        assert_eq!(None, report.reported_at);
+        assert_eq!(SystemReport::default(), report.system);
+
+        let machine = Machine::new(
+            "hallewell".to_string(),
+            Utc::now(),
+            "/nix/store/new".to_string(),
+            None,
+            None,
+            SystemReport {
+                booted_closure: Some("/nix/store/old".to_string()),
+                ..Default::default()
+            },
+            Utc::now(),
+        );
+        assert!(machine.reboot_pending);
+    }
+
+    #[test]
+    fn describes_elapsed_time() {
+        assert_eq!("0s", describe_elapsed(Duration::ZERO));
+        assert_eq!("2m", describe_elapsed(Duration::from_secs(150)));
+        assert_eq!("23h", describe_elapsed(Duration::from_secs(86399)));
+        assert_eq!("3d", describe_elapsed(Duration::from_secs(3 * 86400 + 5)));
+    }
 }
@@ -1,9 +1,10 @@
 use std::time::Duration;
 
-use chrono::DateTime;
+use calendar::rrule::RRule;
+use chrono::{DateTime, NaiveDateTime};
 use chrono_tz::Tz;
-use serde::{Deserialize, Serialize};
-use todo::{Priority, Requirement, Status};
+use serde::{Deserialize, Deserializer, Serialize};
+use todo::{recurrence::Recurrence, Priority, Requirement, Status};
 
 use crate::datetime::{
     deserialize_date_time_tz, deserialize_date_time_tz_option, serialize_date_time_tz,
@@ -12,7 +13,11 @@ use crate::datetime::{
 
 pub mod calendar;
 pub mod datetime;
+pub mod error;
+pub mod feed;
 pub mod herd;
+pub mod ownership;
+pub mod plan;
 pub mod secrets;
 pub mod todo;
 
@@ -28,18 +33,47 @@ pub enum PostTodo {
             deserialize_with = "deserialize_date_time_tz_option"
         )]
         deadline: Option<DateTime<Tz>>,
+        #[serde(default)]
+        recurrence: Option<Recurrence>,
     },
 }
 
+/// Changes to a todo, everything that's left empty stays as it is
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct TodoEdit {
+    pub set_title: Option<String>,
+    pub set_estimate: Option<Duration>,
+    pub add_requirements: Vec<Requirement>,
+    pub set_priority: Option<Priority>,
+    #[serde(default)]
+    pub add_tags: Vec<String>,
+    #[serde(default)]
+    pub remove_tags: Vec<String>,
+    /// An empty project removes the todo from its project
+    #[serde(default)]
+    pub set_project: Option<String>,
+    #[serde(default)]
+    pub set_notes: Option<String>,
+    /// Whether checking the last item of the checklist moves the todo to done
+    #[serde(default)]
+    pub set_completes_with_checklist: Option<bool>,
+    /// Whether other users can see the todo too
+    #[serde(default)]
+    pub set_shared: Option<bool>,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub enum PostTodoWithId {
     MoveToStatus(Status),
-    Edit {
-        set_title: Option<String>,
-        set_estimate: Option<Duration>,
-        add_requirements: Vec<Requirement>,
-        set_priority: Option<Priority>,
-    },
+    Edit(TodoEdit),
+    AddChecklistItem(String),
+    /// Items are addressed by their position in the checklist, starting at 0
+    ToggleChecklistItem(usize),
+    RemoveChecklistItem(usize),
+    /// Moves the todo to the archive, where the usual queries don't see it
+    Archive,
+    /// Like archiving, but the todo is left out of the archive listing too
+    Delete,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -52,5 +86,78 @@ pub enum PostEvent {
         date: DateTime<Tz>,
         duration: Duration,
         title: String,
+        #[serde(default)]
+        recurrence: Option<RRule>,
+        /// Whether other users can see the event too
+        #[serde(default)]
+        shared: bool,
+    },
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub enum PostEventWithId {
+    Edit {
+        set_title: Option<String>,
+        #[serde(
+            serialize_with = "serialize_date_time_tz_option",
+            deserialize_with = "deserialize_date_time_tz_option",
+            default
+        )]
+        set_start: Option<DateTime<Tz>>,
+        set_duration: Option<Duration>,
+        /// `null` removes the recurrence, leaving the field out keeps it as it is
+        #[serde(
+            default,
+            deserialize_with = "deserialize_present",
+            skip_serializing_if = "Option::is_none"
+        )]
+        set_recurrence: Option<Option<RRule>>,
+        #[serde(default)]
+        add_exdates: Vec<NaiveDateTime>,
     },
+    Delete,
+}
+
+// serde treats a missing field and `null` the same for options, this tells them apart for fields
+// that default to `None`.
+fn deserialize_present<'de, D, T>(de: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::deserialize(de).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PostEventWithId;
+
+    fn set_recurrence(json: &str) -> Option<Option<String>> {
+        match serde_json::from_str(json).unwrap() {
+            PostEventWithId::Edit { set_recurrence, .. } => {
+                set_recurrence.map(|x| x.map(|x| x.to_string()))
+            }
+            PostEventWithId::Delete => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn tells_a_missing_recurrence_from_a_removed_one() {
+        assert_eq!(
+            None,
+            set_recurrence(r#"{"Edit": {"set_title": null, "set_duration": null}}"#)
+        );
+        assert_eq!(
+            Some(None),
+            set_recurrence(
+                r#"{"Edit": {"set_title": null, "set_duration": null, "set_recurrence": null}}"#
+            )
+        );
+        assert_eq!(
+            Some(Some("FREQ=DAILY".to_string())),
+            set_recurrence(
+                r#"{"Edit": {"set_title": null, "set_duration": null, "set_recurrence": "FREQ=DAILY"}}"#
+            )
+        );
+    }
 }
@@ -0,0 +1,55 @@
+use serde::{Deserialize, Serialize};
+
+/// Who can see a todo or an event. Shared ones are visible to everyone, the rest only to their
+/// owner. Things created before there were users have no owner and stay visible to everyone.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ownership {
+    #[serde(default)]
+    owner: Option<String>,
+    #[serde(default)]
+    shared: bool,
+}
+
+impl Ownership {
+    pub fn new(owner: impl Into<String>, shared: bool) -> Self {
+        Self {
+            owner: Some(owner.into()),
+            shared,
+        }
+    }
+
+    pub fn owner(&self) -> Option<&str> {
+        self.owner.as_deref()
+    }
+
+    pub fn is_shared(&self) -> bool {
+        self.shared
+    }
+
+    pub fn set_shared(&mut self, shared: bool) {
+        self.shared = shared;
+    }
+
+    pub fn is_visible_to(&self, user: &str) -> bool {
+        match &self.owner {
+            Some(owner) => self.shared || owner == user,
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn private_things_are_only_visible_to_their_owner() {
+        let private = Ownership::new("ramona", false);
+        let shared = Ownership::new("ramona", true);
+
+        assert!(private.is_visible_to("ramona"));
+        assert!(!private.is_visible_to("someone"));
+        assert!(shared.is_visible_to("someone"));
+        assert!(Ownership::default().is_visible_to("someone"));
+    }
+}
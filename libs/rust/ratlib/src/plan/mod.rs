@@ -0,0 +1,237 @@
+use std::{cmp::Reverse, time::Duration};
+
+use chrono::{DateTime, NaiveDate, NaiveTime, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    calendar::event::Event,
+    datetime::{deserialize_date_time_tz, serialize_date_time_tz},
+    todo::{Id, Status, Todo},
+};
+
+/// Time set aside for working on a todo
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Slot {
+    pub todo: Id,
+    pub title: String,
+    #[serde(
+        serialize_with = "serialize_date_time_tz",
+        deserialize_with = "deserialize_date_time_tz"
+    )]
+    pub start: DateTime<Tz>,
+    pub duration: Duration,
+}
+
+/// A todo there was no room for, `duration` is how much time it would've needed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Unscheduled {
+    pub todo: Id,
+    pub title: String,
+    pub duration: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Plan {
+    pub date: NaiveDate,
+    pub slots: Vec<Slot>,
+    pub unscheduled: Vec<Unscheduled>,
+}
+
+// If the time doesn't exist on that day (because of DST), this takes the first one after it
+fn local_time(date: NaiveDate, time: NaiveTime, timezone: Tz) -> DateTime<Tz> {
+    let local = date.and_time(time);
+
+    timezone
+        .from_local_datetime(&local)
+        .earliest()
+        .or_else(|| {
+            timezone
+                .from_local_datetime(&(local + TimeDelta::try_hours(1).unwrap()))
+                .earliest()
+        })
+        .unwrap()
+}
+
+/// Proposes when to work on `todos` on `date`, between `working_hours` and around `events`.
+/// Todos that are already being worked on go first, then the ones with the closest deadlines,
+/// then the rest by priority. Each todo gets one slot as long as what's left of its estimate, in
+/// the first gap it fits in, and nothing gets planned before `now`. Todos with nothing left of
+/// their estimate need no time, so they're left out.
+pub fn plan(
+    date: NaiveDate,
+    working_hours: (NaiveTime, NaiveTime),
+    timezone: Tz,
+    events: &[Event],
+    todos: &[Todo],
+    now: DateTime<Utc>,
+) -> Plan {
+    let day_start = local_time(date, working_hours.0, timezone).max(now.with_timezone(&timezone));
+    let day_end = local_time(date, working_hours.1, timezone);
+
+    let mut gaps = vec![];
+    if day_start < day_end {
+        gaps.push((day_start, day_end));
+    }
+
+    for event in events {
+        let start = event.start().with_timezone(&timezone);
+        let end = start + TimeDelta::from_std(event.duration()).unwrap_or(TimeDelta::zero());
+
+        gaps = gaps
+            .into_iter()
+            .flat_map(|(gap_start, gap_end)| {
+                if end <= gap_start || start >= gap_end {
+                    return vec![(gap_start, gap_end)];
+                }
+
+                [(gap_start, start), (end, gap_end)]
+                    .into_iter()
+                    .filter(|(start, end)| start < end)
+                    .collect()
+            })
+            .collect();
+    }
+
+    let mut todos: Vec<_> = todos.iter().collect();
+    todos.sort_by_key(|x| {
+        (
+            x.status() != Status::Doing,
+            x.deadline().is_none(),
+            x.deadline(),
+            Reverse(x.priority()),
+            x.id().0,
+        )
+    });
+
+    let mut slots = vec![];
+    let mut unscheduled = vec![];
+
+    for todo in todos {
+        let duration = todo.estimate().saturating_sub(todo.time_spent(now));
+        if duration.is_zero() {
+            continue;
+        }
+
+        let gap = gaps
+            .iter_mut()
+            .find(|(start, end)| (*end - *start).to_std().unwrap_or_default() >= duration);
+
+        match gap {
+            Some(gap) => {
+                slots.push(Slot {
+                    todo: todo.id(),
+                    title: todo.title().to_string(),
+                    start: gap.0,
+                    duration,
+                });
+                // It fit into the gap, so it's not too long for a TimeDelta
+                gap.0 += TimeDelta::from_std(duration).unwrap();
+            }
+            None => unscheduled.push(Unscheduled {
+                todo: todo.id(),
+                title: todo.title().to_string(),
+                duration,
+            }),
+        }
+    }
+
+    slots.sort_by_key(|x| x.start);
+
+    Plan {
+        date,
+        slots,
+        unscheduled,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono_tz::Europe::Berlin;
+
+    use crate::todo::Priority;
+
+    use super::*;
+
+    #[test]
+    fn fits_todos_around_events() {
+        let date = NaiveDate::from_ymd_opt(2024, 7, 1).unwrap();
+        let at = |hour, minute| {
+            local_time(
+                date,
+                NaiveTime::from_hms_opt(hour, minute, 0).unwrap(),
+                Berlin,
+            )
+        };
+        let minutes = |x: u64| Duration::from_secs(x * 60);
+        let todo = |id, priority, estimate, deadline| {
+            Todo::new(
+                Id(id),
+                format!("todo {id}"),
+                priority,
+                vec![],
+                minutes(estimate),
+                deadline,
+            )
+        };
+
+        let events = [
+            Event::new(
+                crate::calendar::event::Id(1),
+                at(10, 0),
+                minutes(60),
+                "Standup".to_string(),
+            ),
+            Event::new(
+                crate::calendar::event::Id(2),
+                at(12, 0),
+                minutes(60),
+                "Lunch".to_string(),
+            ),
+        ];
+        let mut doing = todo(5, Priority::Low, 60, None);
+        doing.transition_to(Status::Doing, at(8, 30).to_utc());
+
+        let todos = [
+            todo(1, Priority::Low, 30, None),
+            todo(2, Priority::High, 120, None),
+            todo(3, Priority::Medium, 45, Some(at(18, 0))),
+            todo(4, Priority::High, 300, None),
+            todo(6, Priority::High, 0, None),
+            doing,
+        ];
+
+        let plan = plan(
+            date,
+            (
+                NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(17, 0, 0).unwrap(),
+            ),
+            Berlin,
+            &events,
+            &todos,
+            at(9, 0).to_utc(),
+        );
+
+        assert_eq!(
+            vec![
+                (5, at(9, 0), 30),
+                (1, at(9, 30), 30),
+                (3, at(11, 0), 45),
+                (2, at(13, 0), 120),
+            ]
+            .into_iter()
+            .map(|(id, start, duration)| (Id(id), start, minutes(duration)))
+            .collect::<Vec<_>>(),
+            plan.slots
+                .iter()
+                .map(|x| (x.todo, x.start, x.duration))
+                .collect::<Vec<_>>()
+        );
+        // 6 needs no time, so it's neither planned nor missing a slot
+        assert_eq!(
+            vec![Id(4)],
+            plan.unscheduled.iter().map(|x| x.todo).collect::<Vec<_>>()
+        );
+    }
+}
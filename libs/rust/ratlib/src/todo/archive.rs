@@ -0,0 +1,23 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::Todo;
+
+/// A todo that's out of the way of the usual queries. Deleted todos end up here too, so deleting
+/// can be undone.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArchivedTodo {
+    #[serde(flatten)]
+    pub todo: Todo,
+    pub archived_at: DateTime<Utc>,
+    pub deleted: bool,
+}
+
+/// Part of the archive, most recently archived first
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Page {
+    pub todos: Vec<ArchivedTodo>,
+    pub offset: usize,
+    /// How many todos there are on all pages together
+    pub total: usize,
+}
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Item {
+    pub title: String,
+    pub done: bool,
+}
+
+/// Smaller steps of a todo that aren't worth being todos of their own. Items are addressed by
+/// their position, starting at 0.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checklist {
+    items: Vec<Item>,
+    /// Whether the todo should be moved to done once every item is checked
+    #[serde(default)]
+    completes_todo: bool,
+}
+
+impl Checklist {
+    pub fn items(&self) -> &[Item] {
+        &self.items
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn add(&mut self, title: String) {
+        self.items.push(Item { title, done: false });
+    }
+
+    /// Returns false if there's no item at `index`
+    pub fn toggle(&mut self, index: usize) -> bool {
+        match self.items.get_mut(index) {
+            Some(item) => {
+                item.done = !item.done;
+
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns false if there's no item at `index`
+    pub fn remove(&mut self, index: usize) -> bool {
+        if index >= self.items.len() {
+            return false;
+        }
+
+        self.items.remove(index);
+
+        true
+    }
+
+    /// How many of the items are done, and how many there are in total
+    pub fn progress(&self) -> (usize, usize) {
+        (
+            self.items.iter().filter(|x| x.done).count(),
+            self.items.len(),
+        )
+    }
+
+    /// An empty checklist is never complete, as there's nothing to tell if the todo is done
+    pub fn is_complete(&self) -> bool {
+        !self.items.is_empty() && self.items.iter().all(|x| x.done)
+    }
+
+    pub fn completes_todo(&self) -> bool {
+        self.completes_todo
+    }
+
+    pub fn set_completes_todo(&mut self, completes_todo: bool) {
+        self.completes_todo = completes_todo;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_progress() {
+        let mut checklist = Checklist::default();
+        assert!(!checklist.is_complete());
+
+        checklist.add("Buy paint".to_string());
+        checklist.add("Tape the edges".to_string());
+        checklist.add("Paint".to_string());
+
+        assert!(checklist.toggle(0));
+        assert!(checklist.toggle(2));
+        assert!(!checklist.toggle(3));
+        assert_eq!((2, 3), checklist.progress());
+        assert!(!checklist.is_complete());
+
+        assert!(checklist.remove(1));
+        assert!(!checklist.remove(2));
+        assert_eq!((2, 2), checklist.progress());
+        assert!(checklist.is_complete());
+        assert_eq!("Paint", checklist.items()[1].title);
+    }
+}
@@ -1,60 +1,156 @@
 use std::time::Duration;
 
-use chrono::DateTime;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
 use chrono_tz::Tz;
+use reqwest::{
+    header::{HeaderMap, HeaderValue, AUTHORIZATION},
+    RequestBuilder,
+};
+use serde::de::DeserializeOwned;
+use thiserror::Error;
 
-use crate::PostTodo;
+use crate::{
+    calendar::{
+        agenda::Day,
+        event::{self, Event},
+        rrule::RRule,
+    },
+    error::ApiError,
+    feed::Watch,
+    herd::{ClosurePeriod, Machine, PostDesiredClosure, PostHerdMachine},
+    plan::Plan,
+    PostEvent, PostEventWithId, PostTodo, PostTodoWithId, TodoEdit,
+};
 
-use super::{Id, Priority, Requirement, Todo};
+use super::{
+    archive::Page, filter::Filter, graph::Graph, recurrence::Recurrence, report::Report,
+    rules::Blocker, urgency::ScoredTodo, Id, Priority, Requirement, Status, Todo,
+};
 
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("Request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("{0}")]
+    Api(#[from] ApiError),
+    #[error("Tokens can only contain visible ASCII characters")]
+    InvalidToken,
+}
+
+/// Talks to ras. Cloning is cheap, all clones share one connection pool.
+#[derive(Clone)]
 pub struct Client {
     server_url: String,
+    client: reqwest::Client,
 }
 
 impl Client {
+    /// `server_url` is where ras is, e.g. `http://hallewell:8438`, with or without a trailing
+    /// slash
     pub fn new(server_url: impl Into<String>) -> Self {
         Self {
-            server_url: server_url.into(),
+            server_url: server_url.into().trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
         }
     }
 
-    pub async fn find_doing(&self) -> Vec<Todo> {
-        let client = reqwest::Client::new();
+    /// Authenticates every request with `token`, ras refuses the ones without a valid token.
+    /// Surrounding whitespace, like the newline at the end of a token file, is ignored.
+    pub fn with_token(self, token: &str) -> Result<Self, ClientError> {
+        let mut headers = HeaderMap::new();
+        let mut authorization = HeaderValue::from_str(&format!("Bearer {}", token.trim()))
+            .map_err(|_| ClientError::InvalidToken)?;
+        authorization.set_sensitive(true);
+        headers.insert(AUTHORIZATION, authorization);
+
+        Ok(Self {
+            server_url: self.server_url,
+            client: reqwest::Client::builder()
+                .default_headers(headers)
+                .build()?,
+        })
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{path}", self.server_url)
+    }
+
+    async fn send(request: RequestBuilder) -> Result<reqwest::Response, ClientError> {
+        Ok(ApiError::check(request.send().await?).await?)
+    }
+
+    async fn fetch<T: DeserializeOwned>(request: RequestBuilder) -> Result<T, ClientError> {
+        Ok(Self::send(request).await?.json().await?)
+    }
+
+    pub async fn find(&self, filter: &Filter) -> Result<Vec<ScoredTodo>, ClientError> {
+        Self::fetch(self.client.get(self.url("todos")).query(filter)).await
+    }
+
+    pub async fn find_doing(&self) -> Result<Vec<ScoredTodo>, ClientError> {
+        self.find(&Filter {
+            status: Some(Status::Doing),
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Todos that might not get done before their deadline
+    pub async fn find_at_risk(&self) -> Result<Vec<ScoredTodo>, ClientError> {
+        Self::fetch(self.client.get(self.url("todos?query=AtRisk"))).await
+    }
+
+    /// Todos that can be worked on, most urgent first
+    pub async fn find_ready_to_do(&self) -> Result<Vec<ScoredTodo>, ClientError> {
+        Self::fetch(self.client.get(self.url("todos"))).await
+    }
+
+    pub async fn find_becoming_ready_on(
+        &self,
+        day: NaiveDate,
+    ) -> Result<Vec<ScoredTodo>, ClientError> {
+        Self::fetch(
+            self.client
+                .get(self.url("todos"))
+                .query(&[("becoming_ready_on", day)]),
+        )
+        .await
+    }
 
-        client
-            .get(format!("{}todos?status=Doing", self.server_url))
-            .send()
-            .await
-            .unwrap()
-            .json()
-            .await
-            .unwrap()
+    pub async fn find_by_id(&self, id: Id) -> Result<Todo, ClientError> {
+        Self::fetch(self.client.get(self.url(&format!("todos/{id}")))).await
     }
 
-    pub async fn find_around_deadline(&self) -> Vec<Todo> {
-        let client = reqwest::Client::new();
+    /// Why the todo isn't ready yet, empty if it is
+    pub async fn find_blockers(&self, id: Id) -> Result<Vec<Blocker>, ClientError> {
+        Self::fetch(self.client.get(self.url(&format!("todos/{id}/blockers")))).await
+    }
 
-        client
-            .get(format!("{}todos?query=AroundDeadline", self.server_url))
-            .send()
-            .await
-            .unwrap()
-            .json()
-            .await
-            .unwrap()
+    /// How estimates compared to the time spent, for the todos done in the last `days` days
+    pub async fn find_report(&self, days: u32) -> Result<Report, ClientError> {
+        Self::fetch(
+            self.client
+                .get(self.url("todos/report"))
+                .query(&[("days", days)]),
+        )
+        .await
     }
 
-    pub async fn find_ready_to_do(&self) -> Vec<Todo> {
-        let client = reqwest::Client::new();
+    /// Subscribes to the changes on ras, they come in until the returned watch is dropped
+    pub async fn watch(&self) -> Result<Watch, ClientError> {
+        Ok(Watch::new(
+            Self::send(self.client.get(self.url("events/stream"))).await?,
+        ))
+    }
 
-        client
-            .get(format!("{}todos", self.server_url))
-            .send()
-            .await
-            .unwrap()
-            .json()
-            .await
-            .unwrap()
+    /// The requirements between todos, leaving out the ones that are done unless `include_done`
+    pub async fn find_graph(&self, include_done: bool) -> Result<Graph, ClientError> {
+        Self::fetch(
+            self.client
+                .get(self.url("todos/graph"))
+                .query(&[("include_done", include_done)]),
+        )
+        .await
     }
 
     pub async fn create(
@@ -64,25 +160,297 @@ impl Client {
         estimate: Duration,
         requirements: Vec<Requirement>,
         deadline: Option<DateTime<Tz>>,
-    ) -> Id {
-        let client = reqwest::Client::new();
-
-        let id: Id = client
-            .post(format!("{}todos", self.server_url))
-            .json(&PostTodo::Add {
-                title: title.into(),
-                priority,
-                estimate,
-                requirements,
-                deadline,
-            })
-            .send()
-            .await
-            .unwrap()
-            .json()
-            .await
-            .unwrap();
-
-        id
+        recurrence: Option<Recurrence>,
+    ) -> Result<Id, ClientError> {
+        Self::fetch(self.client.post(self.url("todos")).json(&PostTodo::Add {
+            title: title.into(),
+            priority,
+            estimate,
+            requirements,
+            deadline,
+            recurrence,
+        }))
+        .await
+    }
+
+    pub async fn edit(&self, id: Id, edit: TodoEdit) -> Result<(), ClientError> {
+        Self::send(
+            self.client
+                .post(self.url(&format!("todos/{id}")))
+                .json(&PostTodoWithId::Edit(edit)),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn add_checklist_item(&self, id: Id, title: String) -> Result<(), ClientError> {
+        Self::send(
+            self.client
+                .post(self.url(&format!("todos/{id}")))
+                .json(&PostTodoWithId::AddChecklistItem(title)),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn toggle_checklist_item(&self, id: Id, index: usize) -> Result<(), ClientError> {
+        Self::send(
+            self.client
+                .post(self.url(&format!("todos/{id}")))
+                .json(&PostTodoWithId::ToggleChecklistItem(index)),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn remove_checklist_item(&self, id: Id, index: usize) -> Result<(), ClientError> {
+        Self::send(
+            self.client
+                .post(self.url(&format!("todos/{id}")))
+                .json(&PostTodoWithId::RemoveChecklistItem(index)),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn archive(&self, id: Id) -> Result<(), ClientError> {
+        Self::send(
+            self.client
+                .post(self.url(&format!("todos/{id}")))
+                .json(&PostTodoWithId::Archive),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete(&self, id: Id) -> Result<(), ClientError> {
+        Self::send(
+            self.client
+                .post(self.url(&format!("todos/{id}")))
+                .json(&PostTodoWithId::Delete),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Archived todos, most recently archived first
+    pub async fn find_archive(&self, offset: usize, limit: usize) -> Result<Page, ClientError> {
+        Self::fetch(
+            self.client
+                .get(self.url("todos/archive"))
+                .query(&[("offset", offset), ("limit", limit)]),
+        )
+        .await
+    }
+
+    /// Reverts the most recent change to the todos, returns what it was
+    pub async fn undo(&self) -> Result<String, ClientError> {
+        Self::fetch(self.client.post(self.url("todos/undo"))).await
+    }
+
+    pub async fn move_to_status(&self, id: Id, status: Status) -> Result<(), ClientError> {
+        Self::send(
+            self.client
+                .post(self.url(&format!("todos/{id}")))
+                .json(&PostTodoWithId::MoveToStatus(status)),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Finds the events of the days from `from` to `to` (inclusive), with days starting at
+    /// midnight in `timezone`
+    pub async fn find_events(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+        timezone: Tz,
+    ) -> Result<Vec<Day>, ClientError> {
+        Self::fetch(self.client.get(self.url("events")).query(&[
+            ("from", from.to_string()),
+            ("to", to.to_string()),
+            ("tz", timezone.name().to_string()),
+        ]))
+        .await
+    }
+
+    pub async fn find_event(&self, id: event::Id) -> Result<Event, ClientError> {
+        Self::fetch(self.client.get(self.url(&format!("events/{id}")))).await
+    }
+
+    pub async fn create_event(
+        &self,
+        start: DateTime<Tz>,
+        duration: Duration,
+        title: impl Into<String>,
+        recurrence: Option<RRule>,
+        shared: bool,
+    ) -> Result<event::Id, ClientError> {
+        Self::fetch(self.client.post(self.url("events")).json(&PostEvent::Add {
+            date: start,
+            duration,
+            title: title.into(),
+            recurrence,
+            shared,
+        }))
+        .await
+    }
+
+    pub async fn edit_event(
+        &self,
+        id: event::Id,
+        set_title: Option<String>,
+        set_start: Option<DateTime<Tz>>,
+        set_duration: Option<Duration>,
+        set_recurrence: Option<Option<RRule>>,
+        add_exdates: Vec<NaiveDateTime>,
+    ) -> Result<(), ClientError> {
+        Self::send(self.client.post(self.url(&format!("events/{id}"))).json(
+            &PostEventWithId::Edit {
+                set_title,
+                set_start,
+                set_duration,
+                set_recurrence,
+                add_exdates,
+            },
+        ))
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn delete_event(&self, id: event::Id) -> Result<(), ClientError> {
+        Self::send(
+            self.client
+                .post(self.url(&format!("events/{id}")))
+                .json(&PostEventWithId::Delete),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Imports the events of an iCalendar file, returning the IDs they were stored under. Times
+    /// without a timezone of their own are taken to be in `timezone`.
+    pub async fn import_events(
+        &self,
+        calendar: impl Into<String>,
+        timezone: Tz,
+    ) -> Result<Vec<event::Id>, ClientError> {
+        Self::fetch(
+            self.client
+                .post(self.url("events/import"))
+                .query(&[("tz", timezone.name())])
+                .body(calendar.into()),
+        )
+        .await
+    }
+
+    /// All events and todos with deadlines as an iCalendar file
+    pub async fn export_events(&self) -> Result<String, ClientError> {
+        Ok(Self::send(self.client.get(self.url("events.ics")))
+            .await?
+            .text()
+            .await?)
+    }
+
+    /// A proposal for when to work on which todo on `date`
+    pub async fn find_plan(&self, date: NaiveDate, timezone: Tz) -> Result<Plan, ClientError> {
+        Self::fetch(self.client.get(self.url("plan")).query(&[
+            ("date", date.to_string()),
+            ("tz", timezone.name().to_string()),
+        ]))
+        .await
+    }
+
+    pub async fn update_herd_machine(
+        &self,
+        hostname: &str,
+        request: &PostHerdMachine,
+    ) -> Result<(), ClientError> {
+        Self::send(
+            self.client
+                .post(self.url(&format!("herd/machines/{hostname}")))
+                .json(request),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Sets the closure the machine should be running, machines running another one are drifting
+    pub async fn set_herd_desired_closure(
+        &self,
+        hostname: &str,
+        closure: impl Into<String>,
+    ) -> Result<(), ClientError> {
+        Self::send(
+            self.client
+                .post(self.url(&format!("herd/machines/{hostname}/desired")))
+                .json(&PostDesiredClosure {
+                    closure: closure.into(),
+                }),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Every machine that ever reported to ras, by hostname
+    pub async fn find_herd_machines(&self) -> Result<Vec<Machine>, ClientError> {
+        Self::fetch(self.client.get(self.url("herd/machines"))).await
+    }
+
+    pub async fn find_herd_machine(&self, hostname: &str) -> Result<Machine, ClientError> {
+        Self::fetch(
+            self.client
+                .get(self.url(&format!("herd/machines/{hostname}"))),
+        )
+        .await
+    }
+
+    /// Every closure the machine ran, the current one first
+    pub async fn find_herd_history(
+        &self,
+        hostname: &str,
+    ) -> Result<Vec<ClosurePeriod>, ClientError> {
+        Self::fetch(
+            self.client
+                .get(self.url(&format!("herd/machines/{hostname}/history"))),
+        )
+        .await
+    }
+
+    pub async fn run_monitoring_maintenance(&self) -> Result<(), ClientError> {
+        Self::send(self.client.post(self.url("maintenance/monitoring"))).await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn joins_paths_with_one_slash() {
+        assert_eq!("http://ras/todos", Client::new("http://ras/").url("todos"));
+        assert_eq!("http://ras/todos", Client::new("http://ras").url("todos"));
+    }
+
+    #[test]
+    fn rejects_tokens_that_cannot_be_sent() {
+        assert!(Client::new("http://ras/").with_token("token\n").is_ok());
+        assert!(matches!(
+            Client::new("http://ras/").with_token("to\nken"),
+            Err(ClientError::InvalidToken)
+        ));
     }
 }
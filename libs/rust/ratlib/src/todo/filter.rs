@@ -0,0 +1,162 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{Id, Priority, Requirement, Status, Todo};
+
+/// Criteria for finding todos. Every field that is set has to match (they're combined with AND),
+/// and a filter with no fields set matches everything. ras doesn't take an empty filter to mean
+/// "all todos" though, asking it for todos without any criteria lists the ones ready to do.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Filter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<Status>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_priority: Option<Priority>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_priority: Option<Priority>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub has_deadline: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline_before: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline_after: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title_contains: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requires: Option<Id>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project: Option<String>,
+}
+
+impl Filter {
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
+    }
+
+    pub fn matches(&self, todo: &Todo) -> bool {
+        if self.status.is_some_and(|x| x != todo.status()) {
+            return false;
+        }
+
+        if self.min_priority.is_some_and(|x| todo.priority() < x)
+            || self.max_priority.is_some_and(|x| todo.priority() > x)
+        {
+            return false;
+        }
+
+        if self
+            .has_deadline
+            .is_some_and(|x| x != todo.deadline().is_some())
+        {
+            return false;
+        }
+
+        if let Some(before) = self.deadline_before {
+            if todo.deadline().map_or(true, |x| x >= before) {
+                return false;
+            }
+        }
+
+        if let Some(after) = self.deadline_after {
+            if todo.deadline().map_or(true, |x| x <= after) {
+                return false;
+            }
+        }
+
+        if let Some(title) = &self.title_contains {
+            if !todo.title().to_lowercase().contains(&title.to_lowercase()) {
+                return false;
+            }
+        }
+
+        if let Some(id) = self.requires {
+            if !todo
+                .requirements()
+                .iter()
+                .flat_map(Requirement::flatten)
+                .any(|x| x == &Requirement::TodoDone(id))
+            {
+                return false;
+            }
+        }
+
+        if self.tag.as_ref().is_some_and(|x| !todo.has_tag(x)) {
+            return false;
+        }
+
+        if self
+            .project
+            .as_ref()
+            .is_some_and(|x| todo.project() != Some(x))
+        {
+            return false;
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::TimeZone;
+    use chrono_tz::Europe::Berlin;
+
+    use super::*;
+
+    #[test]
+    fn combines_criteria() {
+        let mut todo = Todo::new(
+            Id(3),
+            "Pay the Electricity bill".to_string(),
+            Priority::High,
+            vec![Requirement::TodoDone(Id(1))],
+            Duration::from_secs(600),
+            Some(Berlin.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap()),
+        );
+        todo.add_tag("bills");
+        todo.set_project(Some("flat".to_string()));
+
+        let mut filter = Filter {
+            status: Some(Status::Todo),
+            min_priority: Some(Priority::Medium),
+            title_contains: Some("electricity".to_string()),
+            requires: Some(Id(1)),
+            deadline_before: Some(Utc.with_ymd_and_hms(2024, 7, 1, 0, 0, 0).unwrap()),
+            tag: Some("#bills".to_string()),
+            project: Some("flat".to_string()),
+            ..Default::default()
+        };
+        assert!(filter.matches(&todo));
+
+        filter.project = Some("car".to_string());
+        assert!(!filter.matches(&todo));
+        filter.project = None;
+
+        filter.has_deadline = Some(false);
+        assert!(!filter.matches(&todo));
+
+        assert!(Filter::default().matches(&todo));
+    }
+
+    #[test]
+    fn can_roundtrip_as_query_string() {
+        let filter = Filter {
+            status: Some(Status::Done),
+            max_priority: Some(Priority::Low),
+            has_deadline: Some(true),
+            deadline_after: Some(Utc.with_ymd_and_hms(2024, 7, 1, 0, 0, 0).unwrap()),
+            title_contains: Some("bills & taxes".to_string()),
+            requires: Some(Id(12)),
+            ..Default::default()
+        };
+
+        let result: Filter =
+            serde_urlencoded::from_str(&serde_urlencoded::to_string(&filter).unwrap()).unwrap();
+
+        assert_eq!(filter, result);
+    }
+}
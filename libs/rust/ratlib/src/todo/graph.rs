@@ -0,0 +1,168 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use super::{Id, Requirement, Status, Todo};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Node {
+    pub id: Id,
+    pub title: String,
+    pub status: Status,
+}
+
+/// `todo` can only be done once `requires` is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Edge {
+    pub todo: Id,
+    pub requires: Id,
+}
+
+/// The `TodoDone` requirements between todos, including the ones combined with others. Edges can
+/// point at todos that are not in `nodes`, if a todo requires one that doesn't exist.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+impl Graph {
+    pub fn new<'a>(todos: impl IntoIterator<Item = &'a Todo>) -> Self {
+        let mut graph = Self::default();
+
+        for todo in todos {
+            graph.nodes.push(Node {
+                id: todo.id(),
+                title: todo.title().to_string(),
+                status: todo.status(),
+            });
+
+            for requirement in todo.requirements().iter().flat_map(Requirement::flatten) {
+                if let Requirement::TodoDone(requires) = requirement {
+                    graph.edges.push(Edge {
+                        todo: todo.id(),
+                        requires: *requires,
+                    });
+                }
+            }
+        }
+
+        graph.nodes.sort_by_key(|x| x.id.0);
+        graph.edges.sort_by_key(|x| (x.todo.0, x.requires.0));
+
+        graph
+    }
+
+    /// Drops the todos that are done, along with the requirements on them, as those are met
+    #[must_use]
+    pub fn without_done(mut self) -> Self {
+        let done: HashSet<_> = self
+            .nodes
+            .iter()
+            .filter(|x| x.status == Status::Done)
+            .map(|x| x.id)
+            .collect();
+
+        self.nodes.retain(|x| !done.contains(&x.id));
+        self.edges
+            .retain(|x| !done.contains(&x.todo) && !done.contains(&x.requires));
+
+        self
+    }
+
+    pub fn contains(&self, id: Id) -> bool {
+        self.nodes.iter().any(|x| x.id == id)
+    }
+
+    /// Whether `todo` requires `other`, either directly or through other todos
+    pub fn requires(&self, todo: Id, other: Id) -> bool {
+        let mut requirements: HashMap<Id, Vec<Id>> = HashMap::new();
+        for edge in &self.edges {
+            requirements
+                .entry(edge.todo)
+                .or_default()
+                .push(edge.requires);
+        }
+
+        let mut to_visit = requirements.get(&todo).cloned().unwrap_or_default();
+        let mut visited = HashSet::new();
+
+        while let Some(current) = to_visit.pop() {
+            if current == other {
+                return true;
+            }
+
+            if visited.insert(current) {
+                to_visit.extend(requirements.get(&current).into_iter().flatten());
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::Utc;
+
+    use crate::todo::Priority;
+
+    use super::*;
+
+    fn todo(id: usize, status: Status, requires: &[usize]) -> Todo {
+        let mut todo = Todo::new(
+            Id(id),
+            format!("Todo {id}"),
+            Priority::Medium,
+            requires
+                .iter()
+                .map(|x| Requirement::TodoDone(Id(*x)))
+                .collect(),
+            Duration::from_secs(60),
+            None,
+        );
+        todo.transition_to(status, Utc::now());
+
+        todo
+    }
+
+    #[test]
+    fn follows_requirements_transitively() {
+        let graph = Graph::new(&[
+            todo(3, Status::Todo, &[2]),
+            todo(1, Status::Done, &[]),
+            todo(2, Status::Todo, &[1, 4]),
+        ]);
+
+        assert_eq!(
+            vec![Id(1), Id(2), Id(3)],
+            graph.nodes.iter().map(|x| x.id).collect::<Vec<_>>()
+        );
+        assert!(graph.requires(Id(3), Id(1)));
+        assert!(graph.requires(Id(3), Id(4)));
+        assert!(!graph.requires(Id(1), Id(3)));
+        assert!(!graph.contains(Id(4)));
+
+        let graph = graph.without_done();
+
+        assert_eq!(
+            vec![Id(2), Id(3)],
+            graph.nodes.iter().map(|x| x.id).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![
+                Edge {
+                    todo: Id(2),
+                    requires: Id(4)
+                },
+                Edge {
+                    todo: Id(3),
+                    requires: Id(2)
+                }
+            ],
+            graph.edges
+        );
+    }
+}
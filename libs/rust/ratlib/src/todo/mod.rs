@@ -1,13 +1,26 @@
 use std::{fmt::Display, time::Duration};
 
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveTime, TimeDelta, Utc, Weekday};
 use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 use strum::EnumIter;
 
-use crate::datetime::{deserialize_date_time_tz_option, serialize_date_time_tz_option};
+use crate::{
+    datetime::{deserialize_date_time_tz_option, serialize_date_time_tz_option},
+    ownership::Ownership,
+};
 
+use self::{checklist::Checklist, recurrence::Recurrence};
+
+pub mod archive;
+pub mod checklist;
 pub mod client;
+pub mod filter;
+pub mod graph;
+pub mod recurrence;
+pub mod report;
+pub mod rules;
+pub mod urgency;
 
 pub struct IdGenerator(usize);
 
@@ -16,6 +29,7 @@ impl IdGenerator {
         Self(seed)
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Id {
         self.0 += 1;
         Id(self.0)
@@ -31,9 +45,12 @@ impl Display for Id {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, EnumIter)]
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, EnumIter, Default,
+)]
 pub enum Priority {
     Low,
+    #[default]
     Medium,
     High,
 }
@@ -52,29 +69,63 @@ impl Display for Priority {
     }
 }
 
-impl Default for Priority {
-    fn default() -> Self {
-        Self::Medium
-    }
-}
-
-#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Copy)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Copy, Default)]
 pub enum Status {
+    #[default]
     Todo,
     Doing,
     Done,
 }
 
-impl Default for Status {
-    fn default() -> Self {
-        Self::Todo
-    }
-}
-
+/// A condition for a todo to be ready. See [`rules::Evaluator`] for what each of them means.
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
 pub enum Requirement {
     TodoDone(Id),
     AfterDate(DateTime<Utc>),
+    BeforeDate(DateTime<Utc>),
+    AnyOf(Vec<Requirement>),
+    AllOf(Vec<Requirement>),
+    OnWeekday(Weekday, Tz),
+    // The end can be before the start, for ranges that go past midnight
+    DuringHours(NaiveTime, NaiveTime, Tz),
+    HostOnline(String),
+}
+
+impl Requirement {
+    /// This requirement, or all the ones it combines, without the combinators
+    pub fn flatten(&self) -> Vec<&Requirement> {
+        match self {
+            Requirement::AnyOf(requirements) | Requirement::AllOf(requirements) => {
+                requirements.iter().flat_map(Requirement::flatten).collect()
+            }
+            _ => vec![self],
+        }
+    }
+
+    // Replaces `TodoDone(id)` with a requirement that is always met
+    fn assume_done(&mut self, id: Id) {
+        match self {
+            Requirement::TodoDone(x) if *x == id => *self = Requirement::AllOf(vec![]),
+            Requirement::AnyOf(requirements) | Requirement::AllOf(requirements) => {
+                for requirement in requirements {
+                    requirement.assume_done(id);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn write_list(f: &mut std::fmt::Formatter<'_>, requirements: &[Requirement]) -> std::fmt::Result {
+    for (i, requirement) in requirements.iter().enumerate() {
+        if i > 0 {
+            write!(f, ",")?;
+        }
+
+        write!(f, "{requirement}")?;
+    }
+
+    Ok(())
 }
 
 impl Display for Requirement {
@@ -82,10 +133,35 @@ impl Display for Requirement {
         match self {
             Requirement::TodoDone(id) => write!(f, "done({id})"),
             Requirement::AfterDate(when) => write!(f, "after({when})"),
+            Requirement::BeforeDate(when) => write!(f, "before({when})"),
+            Requirement::AnyOf(requirements) => {
+                write!(f, "any(")?;
+                write_list(f, requirements)?;
+                write!(f, ")")
+            }
+            Requirement::AllOf(requirements) => {
+                write!(f, "all(")?;
+                write_list(f, requirements)?;
+                write!(f, ")")
+            }
+            Requirement::OnWeekday(weekday, timezone) => write!(f, "on({weekday}@{timezone})"),
+            Requirement::DuringHours(start, end, timezone) => write!(
+                f,
+                "between({}-{}@{timezone})",
+                start.format("%H:%M"),
+                end.format("%H:%M")
+            ),
+            Requirement::HostOnline(hostname) => write!(f, "host({hostname})"),
         }
     }
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub struct Transition {
+    pub status: Status,
+    pub at: DateTime<Utc>,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
 pub struct Todo {
     id: Id,
@@ -103,6 +179,30 @@ pub struct Todo {
         default
     )]
     deadline: Option<DateTime<Tz>>,
+    #[serde(default)]
+    recurrence: Option<Recurrence>,
+    // The todo that was created when this one got done, so it doesn't recur twice if it's moved
+    // out of done and back.
+    #[serde(default)]
+    next_instance: Option<Id>,
+    // Without the leading `#`
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    project: Option<String>,
+    // Markdown
+    #[serde(default)]
+    notes: String,
+    #[serde(default)]
+    checklist: Checklist,
+    // Todos from before this was tracked don't have it
+    #[serde(default)]
+    created_at: Option<DateTime<Utc>>,
+    // Every status change, oldest first. Todos from before this was tracked have none.
+    #[serde(default)]
+    history: Vec<Transition>,
+    #[serde(default)]
+    ownership: Ownership,
 }
 
 impl Todo {
@@ -122,6 +222,15 @@ impl Todo {
             status: Status::Todo,
             estimate,
             deadline,
+            recurrence: None,
+            next_instance: None,
+            tags: vec![],
+            project: None,
+            notes: String::new(),
+            checklist: Checklist::default(),
+            created_at: None,
+            history: vec![],
+            ownership: Ownership::default(),
         }
     }
 
@@ -169,13 +278,161 @@ impl Todo {
         self.title = title;
     }
 
-    pub fn transition_to(&mut self, status: Status) {
+    pub fn transition_to(&mut self, status: Status, at: DateTime<Utc>) {
+        if self.status == status {
+            return;
+        }
+
+        self.status = status;
+        self.history.push(Transition { status, at });
+    }
+
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Tags can be given with or without the leading `#`
+    pub fn add_tag(&mut self, tag: &str) {
+        let tag = tag.trim_start_matches('#');
+
+        if !tag.is_empty() && !self.has_tag(tag) {
+            self.tags.push(tag.to_string());
+        }
+    }
+
+    pub fn remove_tag(&mut self, tag: &str) {
+        let tag = tag.trim_start_matches('#');
+
+        self.tags.retain(|x| x != tag);
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        let tag = tag.trim_start_matches('#');
+
+        self.tags.iter().any(|x| x == tag)
+    }
+
+    pub fn project(&self) -> Option<&str> {
+        self.project.as_deref()
+    }
+
+    pub fn set_project(&mut self, project: Option<String>) {
+        self.project = project.filter(|x| !x.is_empty());
+    }
+
+    pub fn notes(&self) -> &str {
+        &self.notes
+    }
+
+    pub fn set_notes(&mut self, notes: String) {
+        self.notes = notes;
+    }
+
+    /// Treats the todo with `id` as done for good, e.g. because it got archived and won't be
+    /// around to check anymore
+    pub fn assume_done(&mut self, id: Id) {
+        for requirement in &mut self.requirements {
+            requirement.assume_done(id);
+        }
+
+        self.requirements
+            .retain(|x| *x != Requirement::AllOf(vec![]));
+    }
+
+    pub fn checklist(&self) -> &Checklist {
+        &self.checklist
+    }
+
+    pub fn checklist_mut(&mut self) -> &mut Checklist {
+        &mut self.checklist
+    }
+
+    pub fn created_at(&self) -> Option<DateTime<Utc>> {
+        self.created_at
+    }
+
+    pub fn set_created_at(&mut self, created_at: DateTime<Utc>) {
+        self.created_at = Some(created_at);
+    }
+
+    pub fn history(&self) -> &[Transition] {
+        &self.history
+    }
+
+    /// Puts back a status along with the history that led to it, e.g. when reading the todo from
+    /// storage. Unlike [`Todo::transition_to`] this doesn't record anything.
+    pub fn restore_status(&mut self, status: Status, history: Vec<Transition>) {
         self.status = status;
+        self.history = history;
+    }
+
+    /// When work on the todo first started
+    pub fn started_at(&self) -> Option<DateTime<Utc>> {
+        self.history
+            .iter()
+            .find(|x| x.status == Status::Doing)
+            .map(|x| x.at)
+    }
+
+    /// When the todo got done, if it still is
+    pub fn completed_at(&self) -> Option<DateTime<Utc>> {
+        self.history
+            .last()
+            .filter(|x| x.status == Status::Done && self.status == Status::Done)
+            .map(|x| x.at)
+    }
+
+    /// The time spent in `Doing`, up to `as_of` if that's the current status
+    pub fn time_spent(&self, as_of: DateTime<Utc>) -> Duration {
+        let mut spent = TimeDelta::zero();
+        let mut doing_since = None;
+
+        for transition in &self.history {
+            if let Some(since) = doing_since.take() {
+                spent += transition.at - since;
+            }
+
+            if transition.status == Status::Doing {
+                doing_since = Some(transition.at);
+            }
+        }
+
+        if let Some(since) = doing_since {
+            spent += as_of - since;
+        }
+
+        spent.to_std().unwrap_or_default()
+    }
+
+    pub fn recurrence(&self) -> Option<&Recurrence> {
+        self.recurrence.as_ref()
+    }
+
+    pub fn set_recurrence(&mut self, recurrence: Option<Recurrence>) {
+        self.recurrence = recurrence;
+    }
+
+    pub fn next_instance(&self) -> Option<Id> {
+        self.next_instance
+    }
+
+    pub fn set_next_instance(&mut self, id: Id) {
+        self.next_instance = Some(id);
+    }
+
+    pub fn ownership(&self) -> &Ownership {
+        &self.ownership
+    }
+
+    pub fn set_ownership(&mut self, ownership: Ownership) {
+        self.ownership = ownership;
     }
 }
 
 #[cfg(test)]
 mod test {
+    use chrono::TimeZone;
+
     use super::*;
 
     #[test]
@@ -186,6 +443,90 @@ mod test {
         assert_eq!(14, generator.next().0);
     }
 
+    #[test]
+    pub fn tracks_time_spent() {
+        let at = |hour| Utc.with_ymd_and_hms(2024, 7, 1, hour, 0, 0).unwrap();
+        let mut todo = Todo::new(
+            Id(1),
+            "Write the report".to_string(),
+            Priority::High,
+            vec![],
+            Duration::from_secs(3 * 3600),
+            None,
+        );
+
+        todo.transition_to(Status::Doing, at(9));
+        todo.transition_to(Status::Doing, at(10));
+        todo.transition_to(Status::Todo, at(11));
+        todo.transition_to(Status::Doing, at(13));
+
+        assert_eq!(Some(at(9)), todo.started_at());
+        assert_eq!(None, todo.completed_at());
+        assert_eq!(Duration::from_secs(3 * 3600), todo.time_spent(at(14)));
+
+        todo.transition_to(Status::Done, at(15));
+
+        assert_eq!(Some(at(15)), todo.completed_at());
+        assert_eq!(Duration::from_secs(4 * 3600), todo.time_spent(at(18)));
+        assert_eq!(4, todo.history().len());
+    }
+
+    #[test]
+    pub fn tags_ignore_the_hash() {
+        let mut todo = Todo::new(
+            Id(1),
+            "Replace the router".to_string(),
+            Priority::Medium,
+            vec![],
+            Duration::from_secs(3600),
+            None,
+        );
+
+        todo.add_tag("#infra");
+        todo.add_tag("infra");
+        todo.add_tag("home");
+        assert_eq!(&["infra".to_string(), "home".to_string()], todo.tags());
+        assert!(todo.has_tag("#home"));
+
+        todo.remove_tag("#infra");
+        assert_eq!(&["home".to_string()], todo.tags());
+
+        todo.set_project(Some(String::new()));
+        assert_eq!(None, todo.project());
+    }
+
+    #[test]
+    pub fn assuming_done_drops_met_requirements() {
+        let mut todo = Todo::new(
+            Id(3),
+            "Hang the shelves".to_string(),
+            Priority::Medium,
+            vec![
+                Requirement::TodoDone(Id(1)),
+                Requirement::AnyOf(vec![
+                    Requirement::TodoDone(Id(1)),
+                    Requirement::TodoDone(Id(2)),
+                ]),
+                Requirement::TodoDone(Id(2)),
+            ],
+            Duration::from_secs(3600),
+            None,
+        );
+
+        todo.assume_done(Id(1));
+
+        assert_eq!(
+            &[
+                Requirement::AnyOf(vec![
+                    Requirement::AllOf(vec![]),
+                    Requirement::TodoDone(Id(2))
+                ]),
+                Requirement::TodoDone(Id(2)),
+            ],
+            todo.requirements()
+        );
+    }
+
     #[test]
     pub fn display_todo_priority() {
         assert_eq!("Low", Priority::Low.to_string());
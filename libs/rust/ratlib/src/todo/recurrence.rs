@@ -0,0 +1,496 @@
+use std::{collections::BTreeSet, fmt::Display, str::FromStr};
+
+use chrono::{
+    DateTime, Datelike, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, TimeZone, Timelike,
+    Utc, Weekday,
+};
+use chrono_tz::Tz;
+use serde::{de, Deserialize, Deserializer, Serialize};
+use thiserror::Error;
+
+// How far into the future we look for a matching cron date before giving up. Covers leap days
+// (Feb 29th) with room to spare.
+const CRON_SEARCH_DAYS: u32 = 366 * 8;
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub enum Rule {
+    Daily {
+        #[serde(deserialize_with = "at_least_one")]
+        every: u32,
+        at: NaiveTime,
+    },
+    Weekly {
+        #[serde(deserialize_with = "at_least_one")]
+        every: u32,
+        on: Vec<Weekday>,
+        at: NaiveTime,
+    },
+    Monthly {
+        #[serde(deserialize_with = "at_least_one")]
+        every: u32,
+        day: u32,
+        at: NaiveTime,
+    },
+    Cron(CronSchedule),
+}
+
+// An interval of 0 would never move forward, so we don't accept one from the outside. Rules
+// built in code get clamped to 1 instead.
+fn at_least_one<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let every = u32::deserialize(deserializer)?;
+
+    if every == 0 {
+        return Err(de::Error::invalid_value(
+            de::Unexpected::Unsigned(0),
+            &"an interval of at least 1",
+        ));
+    }
+
+    Ok(every)
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct Recurrence {
+    rule: Rule,
+    timezone: Tz,
+}
+
+impl Recurrence {
+    pub fn new(rule: Rule, timezone: Tz) -> Self {
+        Self { rule, timezone }
+    }
+
+    pub fn rule(&self) -> &Rule {
+        &self.rule
+    }
+
+    pub fn timezone(&self) -> Tz {
+        self.timezone
+    }
+
+    /// Finds the first occurrence strictly after the given moment. Dates are computed in the
+    /// recurrence's own timezone, so "every day at 9:00" stays at 9:00 local time across DST
+    /// changes.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Tz>> {
+        let local = after.with_timezone(&self.timezone);
+        let today = local.date_naive();
+
+        match &self.rule {
+            Rule::Daily { every, at } => {
+                let day =
+                    today.checked_add_signed(TimeDelta::try_days(i64::from((*every).max(1)))?)?;
+
+                self.resolve(day.and_time(*at))
+            }
+            Rule::Weekly { every, on, at } => {
+                if on.is_empty() {
+                    return None;
+                }
+
+                let later_this_week = (1..7 - i64::from(today.weekday().num_days_from_monday()))
+                    .filter_map(|offset| today.checked_add_signed(TimeDelta::try_days(offset)?))
+                    .find(|day| on.contains(&day.weekday()));
+
+                let day = if let Some(day) = later_this_week.filter(|_| *every <= 1) {
+                    day
+                } else {
+                    let monday = today.checked_sub_signed(TimeDelta::try_days(i64::from(
+                        today.weekday().num_days_from_monday(),
+                    ))?)?;
+                    let next_monday = monday
+                        .checked_add_signed(TimeDelta::try_weeks(i64::from((*every).max(1)))?)?;
+
+                    (0..7)
+                        .filter_map(|offset| {
+                            next_monday.checked_add_signed(TimeDelta::try_days(offset)?)
+                        })
+                        .find(|day| on.contains(&day.weekday()))?
+                };
+
+                self.resolve(day.and_time(*at))
+            }
+            Rule::Monthly { every, day, at } => {
+                let first_of_month = today.with_day(1)?;
+                let target_month =
+                    first_of_month.checked_add_months(Months::new((*every).max(1)))?;
+                let day = clamp_to_month(target_month, *day)?;
+
+                self.resolve(day.and_time(*at))
+            }
+            Rule::Cron(schedule) => {
+                let start = local.naive_local().with_second(0)?.with_nanosecond(0)?;
+
+                for offset in 0..CRON_SEARCH_DAYS {
+                    let day = start
+                        .date()
+                        .checked_add_signed(TimeDelta::try_days(i64::from(offset))?)?;
+
+                    if !schedule.matches_date(day) {
+                        continue;
+                    }
+
+                    for hour in &schedule.hours {
+                        for minute in &schedule.minutes {
+                            let candidate =
+                                day.and_time(NaiveTime::from_hms_opt(*hour, *minute, 0)?);
+
+                            if candidate <= start {
+                                continue;
+                            }
+
+                            if let Some(resolved) = self.resolve(candidate) {
+                                if resolved.to_utc() > after {
+                                    return Some(resolved);
+                                }
+                            }
+                        }
+                    }
+                }
+
+                None
+            }
+        }
+    }
+
+    // Local times that fall into a DST gap don't exist, so we move them forward by the size of
+    // the gap (an hour in every zone we care about).
+    fn resolve(&self, local: NaiveDateTime) -> Option<DateTime<Tz>> {
+        self.timezone
+            .from_local_datetime(&local)
+            .earliest()
+            .or_else(|| {
+                self.timezone
+                    .from_local_datetime(&(local + TimeDelta::try_hours(1)?))
+                    .earliest()
+            })
+    }
+}
+
+fn clamp_to_month(first_of_month: NaiveDate, day: u32) -> Option<NaiveDate> {
+    let next_month = first_of_month.checked_add_months(Months::new(1))?;
+    let last_day = next_month.pred_opt()?.day();
+
+    first_of_month.with_day(day.clamp(1, last_day))
+}
+
+impl Display for Recurrence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.rule {
+            Rule::Daily { every, at } => write!(f, "every {every} day(s) at {at}"),
+            Rule::Weekly { every, on, at } => {
+                let days = on
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(",");
+
+                write!(f, "every {every} week(s) on {days} at {at}")
+            }
+            Rule::Monthly { every, day, at } => {
+                write!(f, "every {every} month(s) on day {day} at {at}")
+            }
+            Rule::Cron(schedule) => write!(f, "cron({schedule})"),
+        }?;
+
+        write!(f, " ({})", self.timezone.name())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum CronError {
+    #[error("Expected 5 fields, got {0}")]
+    WrongFieldCount(usize),
+    #[error("Invalid field \"{0}\"")]
+    InvalidField(String),
+    #[error("Value {value} out of range {min}-{max}")]
+    OutOfRange { value: u32, min: u32, max: u32 },
+}
+
+/// A subset of the classic cron syntax: `minute hour day-of-month month day-of-week`, where
+/// each field is `*`, a number, a range (`1-5`), a step (`*/15`, `1-10/2`), or a comma-separated
+/// list of those. As in vixie-cron, if both day-of-month and day-of-week are restricted, a day
+/// matching either of them matches.
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct CronSchedule {
+    expression: String,
+    minutes: BTreeSet<u32>,
+    hours: BTreeSet<u32>,
+    days_of_month: BTreeSet<u32>,
+    months: BTreeSet<u32>,
+    days_of_week: BTreeSet<u32>,
+    days_of_month_restricted: bool,
+    days_of_week_restricted: bool,
+}
+
+impl CronSchedule {
+    fn matches_date(&self, day: NaiveDate) -> bool {
+        if !self.months.contains(&day.month()) {
+            return false;
+        }
+
+        let day_of_month = self.days_of_month.contains(&day.day());
+        let day_of_week = self
+            .days_of_week
+            .contains(&day.weekday().num_days_from_sunday());
+
+        match (self.days_of_month_restricted, self.days_of_week_restricted) {
+            (true, true) => day_of_month || day_of_week,
+            (true, false) => day_of_month,
+            (false, true) => day_of_week,
+            (false, false) => true,
+        }
+    }
+
+    fn parse_field(field: &str, min: u32, max: u32) -> Result<BTreeSet<u32>, CronError> {
+        let mut values = BTreeSet::new();
+        let invalid = || CronError::InvalidField(field.to_string());
+
+        for part in field.split(',') {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+                None => (part, 1),
+            };
+
+            if step == 0 {
+                return Err(invalid());
+            }
+
+            let (start, end) = if range == "*" {
+                (min, max)
+            } else if let Some((start, end)) = range.split_once('-') {
+                (
+                    start.parse().map_err(|_| invalid())?,
+                    end.parse().map_err(|_| invalid())?,
+                )
+            } else {
+                let value = range.parse().map_err(|_| invalid())?;
+
+                (value, if part.contains('/') { max } else { value })
+            };
+
+            for value in [start, end] {
+                if value < min || value > max {
+                    return Err(CronError::OutOfRange { value, min, max });
+                }
+            }
+
+            if start > end {
+                return Err(invalid());
+            }
+
+            values.extend((start..=end).step_by(step as usize));
+        }
+
+        Ok(values)
+    }
+}
+
+impl FromStr for CronSchedule {
+    type Err = CronError;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<_> = expression.split_whitespace().collect();
+
+        let [minutes, hours, days_of_month, months, days_of_week] = fields[..] else {
+            return Err(CronError::WrongFieldCount(fields.len()));
+        };
+
+        // Both 0 and 7 mean Sunday.
+        let days_of_week = Self::parse_field(days_of_week, 0, 7)?
+            .into_iter()
+            .map(|x| x % 7)
+            .collect();
+
+        Ok(Self {
+            expression: fields.join(" "),
+            minutes: Self::parse_field(minutes, 0, 59)?,
+            hours: Self::parse_field(hours, 0, 23)?,
+            days_of_month: Self::parse_field(days_of_month, 1, 31)?,
+            months: Self::parse_field(months, 1, 12)?,
+            days_of_week,
+            days_of_month_restricted: fields[2] != "*",
+            days_of_week_restricted: fields[4] != "*",
+        })
+    }
+}
+
+impl TryFrom<String> for CronSchedule {
+    type Error = CronError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<CronSchedule> for String {
+    fn from(value: CronSchedule) -> Self {
+        value.expression
+    }
+}
+
+impl Display for CronSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveTime, TimeZone, Utc, Weekday};
+    use chrono_tz::{Europe::Berlin, Tz};
+
+    use super::{CronSchedule, Recurrence, Rule};
+
+    fn nine() -> NaiveTime {
+        NaiveTime::from_hms_opt(9, 0, 0).unwrap()
+    }
+
+    fn berlin(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> chrono::DateTime<Tz> {
+        Berlin
+            .with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn daily_is_on_the_next_day_even_if_done_early() {
+        let recurrence = Recurrence::new(
+            Rule::Daily {
+                every: 1,
+                at: nine(),
+            },
+            Berlin,
+        );
+
+        assert_eq!(
+            Some(berlin(2024, 5, 2, 9, 0)),
+            recurrence.next_after(berlin(2024, 5, 1, 7, 0).to_utc())
+        );
+    }
+
+    #[test]
+    fn daily_keeps_local_time_across_dst() {
+        let recurrence = Recurrence::new(
+            Rule::Daily {
+                every: 1,
+                at: nine(),
+            },
+            Berlin,
+        );
+
+        let next = recurrence
+            .next_after(berlin(2024, 3, 30, 12, 0).to_utc())
+            .unwrap();
+
+        assert_eq!(berlin(2024, 3, 31, 9, 0), next);
+        assert_eq!(Utc.with_ymd_and_hms(2024, 3, 31, 7, 0, 0).unwrap(), next);
+    }
+
+    #[test]
+    fn weekly_picks_the_next_listed_weekday() {
+        let recurrence = Recurrence::new(
+            Rule::Weekly {
+                every: 1,
+                on: vec![Weekday::Mon, Weekday::Thu],
+                at: nine(),
+            },
+            Berlin,
+        );
+
+        // 2024-05-06 is a Monday
+        assert_eq!(
+            Some(berlin(2024, 5, 9, 9, 0)),
+            recurrence.next_after(berlin(2024, 5, 6, 10, 0).to_utc())
+        );
+        assert_eq!(
+            Some(berlin(2024, 5, 13, 9, 0)),
+            recurrence.next_after(berlin(2024, 5, 10, 10, 0).to_utc())
+        );
+    }
+
+    #[test]
+    fn biweekly_skips_a_week() {
+        let recurrence = Recurrence::new(
+            Rule::Weekly {
+                every: 2,
+                on: vec![Weekday::Mon],
+                at: nine(),
+            },
+            Berlin,
+        );
+
+        assert_eq!(
+            Some(berlin(2024, 5, 20, 9, 0)),
+            recurrence.next_after(berlin(2024, 5, 6, 10, 0).to_utc())
+        );
+    }
+
+    #[test]
+    fn monthly_clamps_to_the_end_of_the_month() {
+        let recurrence = Recurrence::new(
+            Rule::Monthly {
+                every: 1,
+                day: 31,
+                at: nine(),
+            },
+            Berlin,
+        );
+
+        assert_eq!(
+            Some(berlin(2024, 2, 29, 9, 0)),
+            recurrence.next_after(berlin(2024, 1, 31, 10, 0).to_utc())
+        );
+    }
+
+    #[test]
+    fn cron_finds_the_next_matching_minute() {
+        let recurrence = Recurrence::new(Rule::Cron("30 9 * * 1-5".parse().unwrap()), Berlin);
+
+        // 2024-05-10 is a Friday
+        assert_eq!(
+            Some(berlin(2024, 5, 13, 9, 30)),
+            recurrence.next_after(berlin(2024, 5, 10, 9, 30).to_utc())
+        );
+        assert_eq!(
+            Some(berlin(2024, 5, 10, 9, 30)),
+            recurrence.next_after(berlin(2024, 5, 10, 9, 0).to_utc())
+        );
+    }
+
+    #[test]
+    fn cron_rejects_invalid_expressions() {
+        assert!("* * * *".parse::<CronSchedule>().is_err());
+        assert!("60 * * * *".parse::<CronSchedule>().is_err());
+        assert!("*/0 * * * *".parse::<CronSchedule>().is_err());
+        assert!("a * * * *".parse::<CronSchedule>().is_err());
+        assert!("* * * * 5-1".parse::<CronSchedule>().is_err());
+    }
+
+    #[test]
+    fn rejects_an_interval_of_zero() {
+        for rule in [
+            r#"{"Daily": {"every": 0, "at": "09:00:00"}}"#,
+            r#"{"Weekly": {"every": 0, "on": ["Mon"], "at": "09:00:00"}}"#,
+            r#"{"Monthly": {"every": 0, "day": 1, "at": "09:00:00"}}"#,
+        ] {
+            assert!(serde_json::from_str::<Rule>(rule).is_err(), "{rule}");
+        }
+
+        assert!(
+            serde_json::from_str::<Rule>(r#"{"Daily": {"every": 1, "at": "09:00:00"}}"#).is_ok()
+        );
+    }
+
+    #[test]
+    fn can_roundtrip_recurrence() {
+        let recurrence = Recurrence::new(Rule::Cron("*/15 8-17 * * 1,3".parse().unwrap()), Berlin);
+
+        let result: Recurrence =
+            serde_json::from_str(&serde_json::to_string(&recurrence).unwrap()).unwrap();
+
+        assert_eq!(recurrence, result);
+    }
+}
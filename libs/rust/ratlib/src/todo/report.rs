@@ -0,0 +1,163 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator as _;
+
+use super::{Priority, Todo};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PriorityReport {
+    pub priority: Priority,
+    pub done: usize,
+    // Todos that went straight to done without ever being in doing, so there's nothing to compare
+    // their estimate to. They're not included in `estimated` or `spent`.
+    pub untracked: usize,
+    pub estimated: Duration,
+    pub spent: Duration,
+}
+
+/// How the estimates of the todos done between `from` and `to` compare to the time spent on them
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Report {
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    // From the highest priority to the lowest
+    pub priorities: Vec<PriorityReport>,
+}
+
+impl Report {
+    pub fn new<'a>(
+        todos: impl IntoIterator<Item = &'a Todo>,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Self {
+        let mut priorities: Vec<_> = Priority::iter()
+            .rev()
+            .map(|priority| PriorityReport {
+                priority,
+                done: 0,
+                untracked: 0,
+                estimated: Duration::ZERO,
+                spent: Duration::ZERO,
+            })
+            .collect();
+
+        for todo in todos {
+            let Some(completed_at) = todo.completed_at() else {
+                continue;
+            };
+
+            if completed_at < from || completed_at >= to {
+                continue;
+            }
+
+            let Some(report) = priorities
+                .iter_mut()
+                .find(|x| x.priority == todo.priority())
+            else {
+                continue;
+            };
+
+            report.done += 1;
+
+            if todo.started_at().is_none() {
+                report.untracked += 1;
+                continue;
+            }
+
+            report.estimated += todo.estimate();
+            report.spent += todo.time_spent(completed_at);
+        }
+
+        Self {
+            from,
+            to,
+            priorities,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use crate::todo::{Id, Status};
+
+    use super::*;
+
+    #[test]
+    fn compares_estimates_per_priority() {
+        let at = |day, hour| Utc.with_ymd_and_hms(2024, 7, day, hour, 0, 0).unwrap();
+        let todo = |id, priority, estimate_hours: u64, transitions: &[(Status, u32, u32)]| {
+            let mut todo = Todo::new(
+                Id(id),
+                format!("todo {id}"),
+                priority,
+                vec![],
+                Duration::from_secs(estimate_hours * 3600),
+                None,
+            );
+
+            for (status, day, hour) in transitions {
+                todo.transition_to(*status, at(*day, *hour));
+            }
+
+            todo
+        };
+
+        let todos = [
+            todo(
+                1,
+                Priority::High,
+                2,
+                &[(Status::Doing, 2, 9), (Status::Done, 2, 12)],
+            ),
+            todo(
+                2,
+                Priority::High,
+                1,
+                &[(Status::Doing, 3, 9), (Status::Done, 3, 10)],
+            ),
+            todo(3, Priority::High, 1, &[(Status::Done, 3, 9)]),
+            // Done before the report starts
+            todo(
+                4,
+                Priority::Low,
+                1,
+                &[(Status::Doing, 1, 9), (Status::Done, 1, 10)],
+            ),
+            // Not done yet
+            todo(5, Priority::Low, 1, &[(Status::Doing, 3, 9)]),
+        ];
+
+        let report = Report::new(&todos, at(2, 0), at(4, 0));
+
+        assert_eq!(
+            vec![
+                PriorityReport {
+                    priority: Priority::High,
+                    done: 3,
+                    untracked: 1,
+                    estimated: Duration::from_secs(3 * 3600),
+                    spent: Duration::from_secs(4 * 3600),
+                },
+                PriorityReport {
+                    priority: Priority::Medium,
+                    done: 0,
+                    untracked: 0,
+                    estimated: Duration::ZERO,
+                    spent: Duration::ZERO,
+                },
+                PriorityReport {
+                    priority: Priority::Low,
+                    done: 0,
+                    untracked: 0,
+                    estimated: Duration::ZERO,
+                    spent: Duration::ZERO,
+                },
+            ],
+            report.priorities
+        );
+    }
+}
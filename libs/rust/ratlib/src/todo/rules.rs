@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Datelike, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::herd;
+
+use super::{Id, Requirement, Status, Todo};
+
+/// Why a requirement isn't met
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Blocker {
+    TodoNotDone(Id),
+    UnknownTodo(Id),
+    NotBefore(DateTime<Utc>),
+    Expired(DateTime<Utc>),
+    NotOnWeekday(Weekday, Tz),
+    OutsideHours(NaiveTime, NaiveTime, Tz),
+    HostOffline(String),
+    // None of the alternatives of an `AnyOf` are met, with the blockers of each of them
+    NoAlternative(Vec<Vec<Blocker>>),
+}
+
+impl Blocker {
+    /// A short explanation for humans, with times shown in `timezone`
+    pub fn describe(&self, timezone: Tz) -> String {
+        let format_time = |when: &DateTime<Utc>| {
+            when.with_timezone(&timezone)
+                .format("%a %Y-%m-%d %H:%M")
+                .to_string()
+        };
+
+        match self {
+            Blocker::TodoNotDone(id) => format!("blocked by #{id}"),
+            Blocker::UnknownTodo(id) => format!("requires #{id}, which doesn't exist"),
+            Blocker::NotBefore(when) => format!("available after {}", format_time(when)),
+            Blocker::Expired(when) => format!("only was available until {}", format_time(when)),
+            Blocker::NotOnWeekday(weekday, _) => format!("only on {weekday}"),
+            Blocker::OutsideHours(start, end, _) => format!(
+                "only between {} and {}",
+                start.format("%H:%M"),
+                end.format("%H:%M")
+            ),
+            Blocker::HostOffline(hostname) => format!("{hostname} is offline"),
+            Blocker::NoAlternative(alternatives) if alternatives.is_empty() => {
+                "can never be met".to_string()
+            }
+            Blocker::NoAlternative(alternatives) => {
+                let alternatives: Vec<_> = alternatives
+                    .iter()
+                    .map(|blockers| {
+                        blockers
+                            .iter()
+                            .map(|x| x.describe(timezone))
+                            .collect::<Vec<_>>()
+                            .join(" and ")
+                    })
+                    .collect();
+
+                format!("either {}", alternatives.join(" or "))
+            }
+        }
+    }
+}
+
+/// Decides whether requirements are met, and if not, why
+pub struct Evaluator<'a> {
+    now: DateTime<Utc>,
+    todos: &'a HashMap<Id, Todo>,
+    hosts_last_seen: &'a HashMap<String, DateTime<Utc>>,
+}
+
+impl<'a> Evaluator<'a> {
+    pub fn new(
+        now: DateTime<Utc>,
+        todos: &'a HashMap<Id, Todo>,
+        hosts_last_seen: &'a HashMap<String, DateTime<Utc>>,
+    ) -> Self {
+        Self {
+            now,
+            todos,
+            hosts_last_seen,
+        }
+    }
+
+    pub fn is_met(&self, requirements: &[Requirement]) -> bool {
+        self.blockers(requirements).is_empty()
+    }
+
+    /// Everything standing in the way of all of `requirements` being met
+    pub fn blockers(&self, requirements: &[Requirement]) -> Vec<Blocker> {
+        requirements
+            .iter()
+            .flat_map(|x| self.blockers_of(x))
+            .collect()
+    }
+
+    fn blockers_of(&self, requirement: &Requirement) -> Vec<Blocker> {
+        let blocked_unless = |met: bool, blocker: Blocker| if met { vec![] } else { vec![blocker] };
+
+        match requirement {
+            Requirement::TodoDone(id) => match self.todos.get(id) {
+                Some(todo) => {
+                    blocked_unless(todo.status() == Status::Done, Blocker::TodoNotDone(*id))
+                }
+                None => vec![Blocker::UnknownTodo(*id)],
+            },
+            Requirement::AfterDate(when) => {
+                blocked_unless(self.now > *when, Blocker::NotBefore(*when))
+            }
+            Requirement::BeforeDate(when) => {
+                blocked_unless(self.now < *when, Blocker::Expired(*when))
+            }
+            Requirement::AllOf(requirements) => self.blockers(requirements),
+            Requirement::AnyOf(requirements) => {
+                let mut alternatives = vec![];
+
+                for requirement in requirements {
+                    let blockers = self.blockers_of(requirement);
+
+                    if blockers.is_empty() {
+                        return vec![];
+                    }
+
+                    alternatives.push(blockers);
+                }
+
+                vec![Blocker::NoAlternative(alternatives)]
+            }
+            Requirement::OnWeekday(weekday, timezone) => blocked_unless(
+                self.now.with_timezone(timezone).weekday() == *weekday,
+                Blocker::NotOnWeekday(*weekday, *timezone),
+            ),
+            Requirement::DuringHours(start, end, timezone) => {
+                let time = self.now.with_timezone(timezone).time();
+                let during = if start <= end {
+                    *start <= time && time < *end
+                } else {
+                    *start <= time || time < *end
+                };
+
+                blocked_unless(during, Blocker::OutsideHours(*start, *end, *timezone))
+            }
+            Requirement::HostOnline(hostname) => blocked_unless(
+                self.hosts_last_seen
+                    .get(hostname)
+                    .is_some_and(|x| herd::is_online(*x, self.now)),
+                Blocker::HostOffline(hostname.clone()),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{TimeDelta, TimeZone};
+    use chrono_tz::Europe::Berlin;
+
+    use crate::todo::Priority;
+
+    use super::*;
+
+    #[test]
+    fn explains_why_requirements_are_not_met() {
+        let mut done = Todo::new(
+            Id(1),
+            "Done".to_string(),
+            Priority::Medium,
+            vec![],
+            Duration::from_secs(60),
+            None,
+        );
+        done.transition_to(Status::Done, Utc::now());
+        let not_done = Todo::new(
+            Id(2),
+            "Not done".to_string(),
+            Priority::Medium,
+            vec![],
+            Duration::from_secs(60),
+            None,
+        );
+        let todos = [done, not_done].into_iter().map(|x| (x.id(), x)).collect();
+
+        // A Friday, 19:30 in Berlin
+        let now = Utc.with_ymd_and_hms(2024, 7, 5, 17, 30, 0).unwrap();
+        let hosts = HashMap::from([
+            (
+                "hallewell".to_string(),
+                now - TimeDelta::try_minutes(1).unwrap(),
+            ),
+            (
+                "shadowmend".to_string(),
+                now - TimeDelta::try_hours(2).unwrap(),
+            ),
+        ]);
+        let evaluator = Evaluator::new(now, &todos, &hosts);
+
+        let evening = |start, end| {
+            Requirement::DuringHours(
+                NaiveTime::from_hms_opt(start, 0, 0).unwrap(),
+                NaiveTime::from_hms_opt(end, 0, 0).unwrap(),
+                Berlin,
+            )
+        };
+        let monday = Utc.with_ymd_and_hms(2024, 7, 8, 8, 0, 0).unwrap();
+
+        assert!(evaluator.is_met(&[
+            Requirement::TodoDone(Id(1)),
+            Requirement::OnWeekday(Weekday::Fri, Berlin),
+            evening(19, 2),
+            Requirement::HostOnline("hallewell".to_string()),
+            Requirement::BeforeDate(monday),
+            Requirement::AnyOf(vec![
+                Requirement::TodoDone(Id(2)),
+                Requirement::AllOf(vec![]),
+            ]),
+        ]));
+
+        let blockers = evaluator.blockers(&[
+            Requirement::TodoDone(Id(2)),
+            Requirement::TodoDone(Id(3)),
+            Requirement::AfterDate(monday),
+            Requirement::OnWeekday(Weekday::Sat, Berlin),
+            evening(20, 23),
+            Requirement::AnyOf(vec![
+                Requirement::HostOnline("shadowmend".to_string()),
+                Requirement::AllOf(vec![
+                    Requirement::HostOnline("moonfall".to_string()),
+                    Requirement::BeforeDate(now),
+                ]),
+            ]),
+        ]);
+
+        assert_eq!(
+            vec![
+                "blocked by #2",
+                "requires #3, which doesn't exist",
+                "available after Mon 2024-07-08 10:00",
+                "only on Sat",
+                "only between 20:00 and 23:00",
+                "either shadowmend is offline or moonfall is offline and only was available until \
+                 Fri 2024-07-05 19:30",
+            ],
+            blockers
+                .iter()
+                .map(|x| x.describe(Berlin))
+                .collect::<Vec<_>>()
+        );
+    }
+}
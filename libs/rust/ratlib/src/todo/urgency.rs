@@ -0,0 +1,159 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use super::{Priority, Status, Todo};
+
+// Todos older than this don't get any more urgent by waiting longer
+const MAX_AGE_DAYS: f64 = 30.0;
+
+// A todo is at risk when there's less than this much time to spare before its deadline
+const AT_RISK_MARGIN: Duration = Duration::from_secs(24 * 3600);
+
+/// How much each factor counts towards the urgency of a todo. Each factor is between 0 and 1,
+/// so the urgency is between 0 and the sum of the weights.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Weights {
+    /// Low is 0, medium 0.5 and high 1
+    pub priority: f64,
+    /// 1 once there's no time to spare before the deadline, going towards 0 the more there is
+    pub deadline: f64,
+    /// Grows from 0 when the todo gets created, to 1 after 30 days
+    pub age: f64,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            priority: 1.0,
+            deadline: 2.0,
+            age: 0.25,
+        }
+    }
+}
+
+fn days(seconds: i64) -> f64 {
+    // Precision doesn't matter much for a score
+    #[allow(clippy::cast_precision_loss)]
+    let seconds = seconds as f64;
+
+    seconds / (24.0 * 3600.0)
+}
+
+/// The time left until the deadline, minus what's left of the estimate. Negative once the todo
+/// can't be done in time anymore.
+fn slack(todo: &Todo, now: DateTime<Utc>) -> Option<i64> {
+    let deadline = todo.deadline()?;
+    let remaining = todo.estimate().saturating_sub(todo.time_spent(now));
+
+    Some(
+        deadline.signed_duration_since(now).num_seconds()
+            - i64::try_from(remaining.as_secs()).unwrap_or(i64::MAX),
+    )
+}
+
+impl Weights {
+    pub fn urgency(&self, todo: &Todo, now: DateTime<Utc>) -> f64 {
+        let priority = match todo.priority() {
+            Priority::Low => 0.0,
+            Priority::Medium => 0.5,
+            Priority::High => 1.0,
+        };
+
+        let deadline = match slack(todo, now) {
+            Some(slack) if slack <= 0 => 1.0,
+            Some(slack) => 1.0 / (1.0 + days(slack)),
+            None => 0.0,
+        };
+
+        let age = todo
+            .created_at()
+            .map_or(0.0, |x| days(now.signed_duration_since(x).num_seconds()))
+            .clamp(0.0, MAX_AGE_DAYS)
+            / MAX_AGE_DAYS;
+
+        self.priority * priority + self.deadline * deadline + self.age * age
+    }
+}
+
+/// A todo that isn't done and has less than a day to spare before its deadline, after taking out
+/// what's left of its estimate. Todos that are already past their deadline are at risk too.
+pub fn is_at_risk(todo: &Todo, now: DateTime<Utc>) -> bool {
+    todo.status() != Status::Done
+        && slack(todo, now)
+            .is_some_and(|x| x < i64::try_from(AT_RISK_MARGIN.as_secs()).unwrap_or(i64::MAX))
+}
+
+/// A todo along with how urgent it was when it was fetched
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoredTodo {
+    #[serde(flatten)]
+    pub todo: Todo,
+    pub urgency: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, TimeZone};
+    use chrono_tz::Europe::Berlin;
+
+    use crate::todo::Id;
+
+    use super::*;
+
+    #[test]
+    fn deadlines_make_todos_urgent() {
+        let now = Utc.with_ymd_and_hms(2024, 7, 1, 12, 0, 0).unwrap();
+        let todo = |priority, estimate_hours: u64, deadline_hours: Option<i64>| {
+            let mut todo = Todo::new(
+                Id(1),
+                "todo".to_string(),
+                priority,
+                vec![],
+                Duration::from_secs(estimate_hours * 3600),
+                deadline_hours
+                    .map(|x| (now + TimeDelta::try_hours(x).unwrap()).with_timezone(&Berlin)),
+            );
+            todo.set_created_at(now - TimeDelta::try_days(15).unwrap());
+
+            todo
+        };
+        let weights = Weights::default();
+
+        let relaxed = todo(Priority::High, 1, None);
+        // A day to spare
+        let pressing = todo(Priority::Low, 1, Some(25));
+        let late = todo(Priority::Low, 3, Some(2));
+
+        assert!((weights.urgency(&relaxed, now) - 1.125).abs() < 1e-9);
+        assert!((weights.urgency(&pressing, now) - 1.125).abs() < 1e-9);
+        assert!((weights.urgency(&late, now) - 2.125).abs() < 1e-9);
+
+        assert!(!is_at_risk(&relaxed, now));
+        assert!(!is_at_risk(&pressing, now));
+        assert!(is_at_risk(&todo(Priority::Low, 2, Some(25)), now));
+        assert!(is_at_risk(&late, now));
+    }
+
+    #[test]
+    fn urgency_is_returned_alongside_the_todo() {
+        let scored = ScoredTodo {
+            todo: Todo::new(
+                Id(1),
+                "todo".to_string(),
+                Priority::Low,
+                vec![],
+                Duration::from_secs(60),
+                None,
+            ),
+            urgency: 0.5,
+        };
+        let json = serde_json::to_string(&scored).unwrap();
+
+        assert_eq!(scored, serde_json::from_str(&json).unwrap());
+        // Clients that don't know about urgency can still read it as a todo
+        assert_eq!(scored.todo, serde_json::from_str::<Todo>(&json).unwrap());
+    }
+}
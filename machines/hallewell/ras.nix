@@ -1,8 +1,19 @@
-{pkgs, ...}: {
+{
+  config,
+  pkgs,
+  ...
+}: {
   config = {
+    # The users of ras along with their scopes, by their token
+    age.secrets.ras-tokens = {
+      file = ../../secrets/ras-tokens.age;
+      owner = "ras";
+    };
     services.ramona.ras = {
       enable = true;
-      dataFile = "/mnt/nas3/data/shared/todos.json";
+      # Where everything was kept before moving to the database
+      importDataFile = "/mnt/nas3/data/shared/todos.json";
+      tokensFile = config.age.secrets.ras-tokens.path;
     };
 
     networking.firewall.allowedTCPPorts = [8438];
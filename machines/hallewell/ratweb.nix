@@ -1,15 +1,25 @@
-{pkgs, ...}: {
+{
+  config,
+  pkgs,
+  ...
+}: {
   config = {
+    # RAS_TOKEN, for the user ratweb acts as
+    age.secrets.ratweb-environment = {
+      file = ../../secrets/ratweb-environment.age;
+    };
     systemd.services.ratweb = {
       wantedBy = ["multi-user.target"];
       description = "ratweb!";
       serviceConfig = {
         DynamicUser = true;
-        Environment = "LEPTOS_SITE_ADDR=0.0.0.0:8087";
+        # ratweb has no login of its own and acts with its token for whoever reaches it, so it
+        # stays local. Use an SSH tunnel to get to it from elsewhere.
+        Environment = "LEPTOS_SITE_ADDR=127.0.0.1:8087";
+        EnvironmentFile = config.age.secrets.ratweb-environment.path;
         ExecStart = "${pkgs.ramona.ratweb}/bin/ratweb";
         WorkingDirectory = "${pkgs.ramona.ratweb}/bin/";
       };
     };
-    networking.firewall.allowedTCPPorts = [8087];
   };
 }
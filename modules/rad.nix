@@ -1,17 +1,41 @@
 {
+  lib,
   config,
   pkgs,
   ...
 }: {
-  config = {
+  options = {
+    services.ramona.rad = {
+      serverUrl = lib.mkOption {
+        type = lib.types.str;
+        default = "http://hallewell:8438/";
+        description = "The ras instance to report to";
+      };
+      interval = lib.mkOption {
+        type = lib.types.ints.positive;
+        default = 60;
+        description = "Seconds between reports";
+      };
+    };
+  };
+  config = let
+    radConfig = config.services.ramona.rad;
+  in {
     age.secrets.rad-environment = {
       file = ../secrets/rad-environment.age;
     };
     systemd.services.rad = {
       wantedBy = ["multi-user.target"];
+      environment = {
+        RAD_SERVER_URL = radConfig.serverUrl;
+        RAD_INTERVAL = toString radConfig.interval;
+      };
       serviceConfig = {
         DynamicUser = true;
+        # Keeps the reports ras didn't accept yet across restarts
+        StateDirectory = "rad";
         ExecStart = "${pkgs.ramona.rad}/bin/rad";
+        # Holds RAS_TOKEN
         EnvironmentFile = config.age.secrets.rad-environment.path;
         Restart = "always";
         RestartSec = "5s";
@@ -8,7 +8,21 @@
     services.ramona.ras = {
       enable = lib.mkEnableOption "Enable ras";
       dataFile = lib.mkOption {
+        type = lib.types.nullOr lib.types.str;
+        default = null;
+        description = "Keep todos and events in this JSON file instead of the database";
+      };
+      importDataFile = lib.mkOption {
+        type = lib.types.nullOr lib.types.str;
+        default = null;
+        description = "Copy the todos and events from this JSON file into the database on start, as long as the database is still empty";
+      };
+      tokensFile = lib.mkOption {
         type = lib.types.str;
+        description = ''
+          JSON file with the users of ras along with their scopes, by their token, e.g. an agenix secret.
+          ras refuses to start without any tokens, as it would reject every request.
+        '';
       };
     };
   };
@@ -21,10 +35,15 @@
       };
       systemd.services.ras = {
         wantedBy = ["multi-user.target"];
+        environment = {
+          RAS_TOKENS_FILE = rasConfig.tokensFile;
+        };
         serviceConfig = {
           User = "ras";
-          ExecStart = "${pkgs.ramona.ras}/bin/ras ${rasConfig.dataFile}";
-          ReadWritePaths = "${rasConfig.dataFile}";
+          ExecStartPre = lib.optional (rasConfig.importDataFile != null) "${pkgs.ramona.ras}/bin/ras import --if-empty ${rasConfig.importDataFile}";
+          ExecStart = "${pkgs.ramona.ras}/bin/ras ${lib.optionalString (rasConfig.dataFile != null) rasConfig.dataFile}";
+          # The directory, not just the file - writes go through a temporary file, a lock file and backups next to it
+          ReadWritePaths = map builtins.dirOf (lib.filter (x: x != null) [rasConfig.dataFile rasConfig.importDataFile]);
           EnvironmentFile = config.age.secrets.ras-environment.path;
           Restart = "always";
           RestartSec = "5s";
@@ -26,6 +26,8 @@ in {
   "root-password.age".publicKeys = users ++ allMachines;
   "rad-environment.age".publicKeys = users ++ allMachines;
   "ras-environment.age".publicKeys = users ++ [hallewell];
+  "ras-tokens.age".publicKeys = users ++ [hallewell];
+  "ratweb-environment.age".publicKeys = users ++ [hallewell];
   "transmission-credentials.age".publicKeys = users ++ [shadowsoul];
   "lix-repo-credentials.age".publicKeys = users ++ allMachines;
   "photoprism-password.age".publicKeys = users ++ [hallewell];